@@ -0,0 +1,76 @@
+//! Times working out the forces between particles, which is most of a step.
+//!
+//! ```commandline
+//! cargo bench --bench forces
+//! ```
+//!
+//! Every setup spawns the same particles with the same settings each run, and
+//! checks a checksum of the forces before timing anything, so an optimization
+//! that changes the results fails here instead of looking faster. If a change
+//! is meant to alter the results, update the checksums along with it. The
+//! spawns come from `SmallRng`, which picks a different generator on 32-bit
+//! machines, so the checksums only hold on 64-bit ones.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use egui::{Rgba, Vec2};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use smarticles::{Simulation, INIT_SIZE, MAX_POWER, MIN_POWER};
+
+/// Builds a simulation with `count` particles of each type and random forces between them.
+fn setup<const N: usize>(count: usize) -> Simulation<N> {
+    let classes = [(); N].map(|_| ("class", Rgba::WHITE));
+    let mut sim = Simulation::new(INIT_SIZE, INIT_SIZE, classes);
+    let mut rand = SmallRng::seed_from_u64(N as u64);
+    for p in &mut sim.params {
+        p.count = count;
+        for j in 0..N {
+            p.power[j] = rand.gen_range(MIN_POWER..=MAX_POWER);
+            p.radius[j] = rand.gen_range(10.0..=200.0);
+            p.spin[j] = 0.0;
+        }
+    }
+    sim.spawn_with(&mut rand);
+    sim
+}
+
+// FNV-1a, like the checksum printed by `--bench`
+fn checksum<const N: usize>(forces: &[Vec<Vec2>; N]) -> u64 {
+    forces
+        .iter()
+        .flatten()
+        .flat_map(|f| [f.x.to_bits(), f.y.to_bits()])
+        .fold(0xcbf2_9ce4_8422_2325, |hash, bits| {
+            (hash ^ bits as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn bench<const N: usize>(c: &mut Criterion, count: usize, expected: u64) {
+    let sim = setup::<N>(count);
+    let mut forces = std::array::from_fn(|_| Vec::new());
+    sim.forces(&mut forces);
+    let found = checksum(&forces);
+    assert!(
+        found == expected,
+        "the forces for {}x{} changed, the checksum is now {:016x}",
+        N,
+        count,
+        found
+    );
+
+    let mut group = c.benchmark_group("forces");
+    // the biggest setup takes long enough per run that fewer samples are plenty
+    group.sample_size(if N * count > 2000 { 10 } else { 50 });
+    let id = BenchmarkId::from_parameter(format!("{}x{}", N, count));
+    group.bench_function(id, |b| b.iter(|| sim.forces(&mut forces)));
+    group.finish();
+}
+
+fn forces(c: &mut Criterion) {
+    bench::<3>(c, 200, 0x6335_59f2_5b84_e5fe);
+    bench::<5>(c, 600, 0xbb43_23a2_907b_c9b3);
+    bench::<8>(c, 1200, 0x59e6_3ffc_b4a9_b9e1);
+}
+
+criterion_group!(benches, forces);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+//! Receives the particle positions the app streams over the network, and prints
+//! a summary of every frame.
+//!
+//! Start it, then turn on `Stream` under "Network Stream" in the app with the
+//! same host and port:
+//!
+//! ```commandline
+//! cargo run --example osc_receiver -- 127.0.0.1:9000
+//! ```
+//!
+//! Each UDP packet is one OSC message with the address `/class/N/positions`
+//! and the arguments `frame: i32, first: i32, total: i32, positions: blob`.
+//! The blob holds x/y pairs of big-endian f32s, scaled so 0..1 spans the world,
+//! for the particles `first..first + len` out of the `total` sent for that
+//! class this frame. Big classes are split over several packets.
+
+use std::collections::BTreeMap;
+use std::net::UdpSocket;
+
+struct Packet {
+    class: usize,
+    frame: i32,
+    first: usize,
+    total: usize,
+    positions: Vec<(f32, f32)>,
+}
+
+fn main() -> std::io::Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9000".to_string());
+    let socket = UdpSocket::bind(&addr)?;
+    println!("listening on {}", addr);
+
+    // positions of the frame being received, by class
+    let mut frame = None;
+    let mut classes: BTreeMap<usize, Vec<(f32, f32)>> = BTreeMap::new();
+    let mut buf = [0; 65536];
+    loop {
+        let len = socket.recv(&mut buf)?;
+        let Some(packet) = parse(&buf[..len]) else {
+            eprintln!("skipped a packet that isn't a position message");
+            continue;
+        };
+
+        if frame != Some(packet.frame) {
+            if let Some(frame) = frame {
+                print_frame(frame, &classes);
+            }
+            frame = Some(packet.frame);
+            classes.clear();
+        }
+        let positions = classes.entry(packet.class).or_default();
+        positions.resize(packet.total, (0.0, 0.0));
+        for (i, pos) in packet.positions.into_iter().enumerate() {
+            if let Some(slot) = positions.get_mut(packet.first + i) {
+                *slot = pos;
+            }
+        }
+    }
+}
+
+fn print_frame(frame: i32, classes: &BTreeMap<usize, Vec<(f32, f32)>>) {
+    let summary: Vec<_> = classes
+        .iter()
+        .map(|(class, positions)| {
+            let n = positions.len().max(1) as f32;
+            let (x, y) = positions
+                .iter()
+                .fold((0.0, 0.0), |(x, y), pos| (x + pos.0, y + pos.1));
+            format!(
+                "class {}: {} particles around ({:.2}, {:.2})",
+                class,
+                positions.len(),
+                x / n,
+                y / n
+            )
+        })
+        .collect();
+    println!("frame {}: {}", frame, summary.join(", "));
+}
+
+fn parse(mut bytes: &[u8]) -> Option<Packet> {
+    let address = read_string(&mut bytes)?;
+    let class = address
+        .strip_prefix("/class/")?
+        .strip_suffix("/positions")?
+        .parse()
+        .ok()?;
+    if read_string(&mut bytes)? != ",iiib" {
+        return None;
+    }
+    let frame = read_i32(&mut bytes)?;
+    let first = read_i32(&mut bytes)? as usize;
+    let total = read_i32(&mut bytes)? as usize;
+    let len = read_i32(&mut bytes)? as usize;
+    let blob = bytes.get(..len)?;
+    let positions = blob
+        .chunks_exact(8)
+        .map(|pair| {
+            let x = f32::from_be_bytes(pair[..4].try_into().unwrap());
+            let y = f32::from_be_bytes(pair[4..].try_into().unwrap());
+            (x, y)
+        })
+        .collect();
+    Some(Packet {
+        class,
+        frame,
+        first,
+        total,
+        positions,
+    })
+}
+
+// OSC strings are null terminated and padded to a multiple of 4 bytes
+fn read_string<'a>(bytes: &mut &'a [u8]) -> Option<&'a str> {
+    let end = bytes.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&bytes[..end]).ok()?;
+    let padded = (end + 4) & !3;
+    *bytes = bytes.get(padded..)?;
+    Some(s)
+}
+
+fn read_i32(bytes: &mut &[u8]) -> Option<i32> {
+    let value = i32::from_be_bytes(bytes.get(..4)?.try_into().ok()?);
+    *bytes = &bytes[4..];
+    Some(value)
+}
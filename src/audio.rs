@@ -0,0 +1,302 @@
+use egui::Context;
+use std::sync::mpsc::Receiver;
+
+// The sound from the input device is mixed down to mono and split into three
+// rough bands, each with a pair of one-pole filters:
+//
+//   bass: below 200 Hz, mids: 200 Hz to 2 kHz, treble: above 2 kHz
+//
+// About 60 times a second, the loudness of each band over that window is
+// mapped from -60..0 dB to 0..1 and smoothed, rising quickly and falling
+// slowly so beats come through as pulses, and sent to the app.
+//
+// cpal needs the ALSA development files on Linux, so audio input is behind
+// the `audio` feature:
+//
+//   cargo run --features audio
+
+/// A property of the sound that can drive a setting.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Loudness,
+    Bass,
+    Mids,
+    Treble,
+}
+
+impl Feature {
+    pub const ALL: [Self; 4] = [Self::Loudness, Self::Bass, Self::Mids, Self::Treble];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Loudness => "Loudness",
+            Self::Bass => "Bass",
+            Self::Mids => "Mids",
+            Self::Treble => "Treble",
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Loudness => "loudness",
+            Self::Bass => "bass",
+            Self::Mids => "mids",
+            Self::Treble => "treble",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.key() == key)
+    }
+}
+
+/// How loud the sound is, overall and in each band, smoothed and from 0 to 1.
+#[derive(Clone, Copy, Default)]
+pub struct Levels {
+    pub loudness: f32,
+    pub bass: f32,
+    pub mids: f32,
+    pub treble: f32,
+}
+
+impl Levels {
+    pub fn get(&self, feature: Feature) -> f32 {
+        match feature {
+            Feature::Loudness => self.loudness,
+            Feature::Bass => self.bass,
+            Feature::Mids => self.mids,
+            Feature::Treble => self.treble,
+        }
+    }
+}
+
+/// Listens to an input device, working out its levels on the audio thread.
+pub struct AudioInput {
+    // the stream stops when it's dropped
+    #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+    _stream: cpal::Stream,
+    device: String,
+    levels: Receiver<Result<Levels, String>>,
+    latest: Levels,
+    error: Option<String>,
+}
+
+impl AudioInput {
+    /// The names of the input devices, or nothing if this build can't listen to any.
+    pub fn devices() -> Vec<String> {
+        #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+        {
+            use cpal::traits::{DeviceTrait, HostTrait};
+            let host = cpal::default_host();
+            if let Ok(devices) = host.input_devices() {
+                return devices.filter_map(|device| device.name().ok()).collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Starts listening to the named device, or the default one, waking up `ctx` whenever
+    /// there are new levels.
+    #[cfg_attr(
+        not(all(feature = "audio", not(target_arch = "wasm32"))),
+        allow(unused_variables)
+    )]
+    pub fn start(device: Option<&str>, ctx: Context) -> Result<Self, String> {
+        #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+        return input::start(device, ctx);
+        #[cfg(not(all(feature = "audio", not(target_arch = "wasm32"))))]
+        Err("this build can't listen to audio, it needs the `audio` feature".to_string())
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Takes the levels sent since the last call, and returns the newest.
+    pub fn levels(&mut self) -> Levels {
+        for levels in self.levels.try_iter() {
+            match levels {
+                Ok(levels) => self.latest = levels,
+                Err(err) => self.error = Some(err),
+            }
+        }
+        self.latest
+    }
+
+    /// Why the stream stopped, if it did.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+mod input {
+    use super::*;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, Sample, SampleFormat, SizedSample, StreamConfig};
+    use std::sync::mpsc::{channel, Sender};
+
+    /// How many times a second the levels are sent.
+    const LEVELS_PER_SEC: u32 = 60;
+    const BASS_CUTOFF: f32 = 200.0;
+    const TREBLE_CUTOFF: f32 = 2000.0;
+    /// The quietest sound that registers, in dB below full scale.
+    const FLOOR_DB: f32 = 60.0;
+    /// How far the levels move towards a louder or quieter sound each time they're sent.
+    const ATTACK: f32 = 0.6;
+    const RELEASE: f32 = 0.08;
+
+    pub fn start(name: Option<&str>, ctx: Context) -> Result<AudioInput, String> {
+        let host = cpal::default_host();
+        let device = match name {
+            Some(name) => (host.input_devices().map_err(|err| err.to_string())?)
+                .find(|device| device.name().ok().as_deref() == Some(name))
+                .ok_or_else(|| format!("there's no input device called {}", name))?,
+            None => (host.default_input_device())
+                .ok_or_else(|| "there's no input device".to_string())?,
+        };
+        let config = (device.default_input_config()).map_err(|err| err.to_string())?;
+        let format = config.sample_format();
+        let config = config.config();
+
+        let (sender, levels) = channel();
+        let stream = match format {
+            SampleFormat::F32 => build::<f32>(&device, &config, sender, ctx),
+            SampleFormat::I16 => build::<i16>(&device, &config, sender, ctx),
+            SampleFormat::U16 => build::<u16>(&device, &config, sender, ctx),
+            SampleFormat::I32 => build::<i32>(&device, &config, sender, ctx),
+            format => return Err(format!("{} samples aren't supported", format)),
+        }
+        .map_err(|err| err.to_string())?;
+        stream.play().map_err(|err| err.to_string())?;
+
+        Ok(AudioInput {
+            _stream: stream,
+            device: device.name().unwrap_or_default(),
+            levels,
+            latest: Levels::default(),
+            error: None,
+        })
+    }
+
+    fn build<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        sender: Sender<Result<Levels, String>>,
+        ctx: Context,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let channels = config.channels.max(1) as usize;
+        let mut analyzer = Analyzer::new(config.sample_rate.0 as f32);
+        let (errors, error_ctx) = (sender.clone(), ctx.clone());
+        device.build_input_stream(
+            config,
+            move |data: &[T], _| {
+                for frame in data.chunks(channels) {
+                    let sum = frame.iter().map(|&s| f32::from_sample(s)).sum::<f32>();
+                    if let Some(levels) = analyzer.push(sum / channels as f32) {
+                        // the app may have stopped listening already, which is fine
+                        let _ = sender.send(Ok(levels));
+                        ctx.request_repaint();
+                    }
+                }
+            },
+            move |err| {
+                let _ = errors.send(Err(err.to_string()));
+                error_ctx.request_repaint();
+            },
+            None,
+        )
+    }
+
+    /// A one-pole low-pass filter, which gives a high-pass by taking it away from the sound.
+    #[derive(Clone, Copy)]
+    struct OnePole {
+        amount: f32,
+        out: f32,
+    }
+
+    impl OnePole {
+        fn new(cutoff: f32, sample_rate: f32) -> Self {
+            let amount = 1.0 - (-std::f32::consts::TAU * cutoff / sample_rate).exp();
+            Self { amount, out: 0.0 }
+        }
+
+        fn low(&mut self, sample: f32) -> f32 {
+            self.out += (sample - self.out) * self.amount;
+            self.out
+        }
+
+        fn high(&mut self, sample: f32) -> f32 {
+            sample - self.low(sample)
+        }
+    }
+
+    /// Works out smoothed levels from a stream of mono samples.
+    struct Analyzer {
+        window: usize,
+        // one filter alone lets too much of the other bands through
+        bass: [OnePole; 2],
+        mids: [OnePole; 4],
+        treble: [OnePole; 2],
+        /// The sum of squares of the whole sound and each band over the current window.
+        sums: [f32; 4],
+        count: usize,
+        levels: [f32; 4],
+    }
+
+    impl Analyzer {
+        fn new(sample_rate: f32) -> Self {
+            Self {
+                window: (sample_rate as usize / LEVELS_PER_SEC as usize).max(1),
+                bass: [OnePole::new(BASS_CUTOFF, sample_rate); 2],
+                mids: [
+                    OnePole::new(BASS_CUTOFF, sample_rate),
+                    OnePole::new(BASS_CUTOFF, sample_rate),
+                    OnePole::new(TREBLE_CUTOFF, sample_rate),
+                    OnePole::new(TREBLE_CUTOFF, sample_rate),
+                ],
+                treble: [OnePole::new(TREBLE_CUTOFF, sample_rate); 2],
+                sums: [0.0; 4],
+                count: 0,
+                levels: [0.0; 4],
+            }
+        }
+
+        /// Adds a sample, returning new levels at the end of each window.
+        fn push(&mut self, sample: f32) -> Option<Levels> {
+            let bass = self.bass.iter_mut().fold(sample, |s, f| f.low(s));
+            let [m1, m2, m3, m4] = &mut self.mids;
+            let mids = m4.low(m3.low(m2.high(m1.high(sample))));
+            let treble = self.treble.iter_mut().fold(sample, |s, f| f.high(s));
+            let bands = [sample, bass, mids, treble];
+            for (sum, band) in self.sums.iter_mut().zip(bands) {
+                *sum += band * band;
+            }
+            self.count += 1;
+            if self.count < self.window {
+                return None;
+            }
+
+            for (level, sum) in self.levels.iter_mut().zip(&mut self.sums) {
+                let rms = (*sum / self.count as f32).sqrt();
+                let db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+                let target = ((db + FLOOR_DB) / FLOOR_DB).clamp(0.0, 1.0);
+                let ease = if target > *level { ATTACK } else { RELEASE };
+                *level += (target - *level) * ease;
+                *sum = 0.0;
+            }
+            self.count = 0;
+            let [loudness, bass, mids, treble] = self.levels;
+            Some(Levels {
+                loudness,
+                bass,
+                mids,
+                treble,
+            })
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{Attractor, BorderBehavior, Death, Flow, Integrator, Physics, Reaction, Simulation};
+use crate::{INIT_SIZE, MAX_ATTRACTORS, MAX_COUNT, MAX_LIFETIME, MAX_REACTIONS};
+use crate::{MAX_BORDER_MARGIN, MAX_FORCE_SCALE, MAX_GRAVITY, MAX_SUBSTEPS, MIN_SPAWN_RADIUS};
+use crate::{MAX_POWER, MAX_RADIUS, MAX_SPIN, MIN_POWER, MIN_RADIUS, MIN_SPIN};
+use egui::{Color32, Rgba};
+use ron::ser::PrettyConfig;
+use ron::Value;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// The config format version written by [`Config::to_ron`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The full simulation setup at full precision, saved as RON so it can be edited by hand.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    format_version: u32,
+    world_w: f32,
+    world_h: f32,
+    spawn_radius: f32,
+    spawn_rings: bool,
+    border: BorderBehavior,
+    restitution: f32,
+    integrator: Integrator,
+    physics: Physics,
+    force_script: String,
+    classes: Vec<ClassConfig>,
+    reactions: Vec<Reaction>,
+    attractors: Vec<Attractor>,
+    flow: Flow,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct ClassConfig {
+    name: String,
+    color: [u8; 3],
+    count: usize,
+    power: Vec<f32>,
+    radius: Vec<f32>,
+    spin: Vec<f32>,
+    enabled: Vec<bool>,
+    lifetime: f32,
+    death: Death,
+}
+
+const FIELDS: &[&str] = &[
+    "format_version",
+    "world_w",
+    "world_h",
+    "spawn_radius",
+    "spawn_rings",
+    "border",
+    "restitution",
+    "integrator",
+    "physics",
+    "force_script",
+    "classes",
+    "reactions",
+    "attractors",
+    "flow",
+];
+const CLASS_FIELDS: &[&str] = &[
+    "name", "color", "count", "power", "radius", "spin", "enabled", "lifetime", "death",
+];
+/// Fields that keep the simulation's current values when they're missing, rather than
+/// taking a default.
+const KEPT_FIELDS: &[&str] = &["power", "radius", "spin"];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            world_w: INIT_SIZE,
+            world_h: INIT_SIZE,
+            spawn_radius: INIT_SIZE / 2.0,
+            spawn_rings: false,
+            border: BorderBehavior::Soft,
+            restitution: 1.0,
+            integrator: Integrator::SemiImplicitEuler,
+            physics: Physics::default(),
+            force_script: String::new(),
+            classes: Vec::new(),
+            reactions: Vec::new(),
+            attractors: Vec::new(),
+            flow: Flow::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Captures the settings of a simulation, but not its particles.
+    pub fn from_sim<const N: usize>(sim: &Simulation<N>) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            world_w: sim.world_w,
+            world_h: sim.world_h,
+            spawn_radius: sim.spawn_radius,
+            spawn_rings: sim.spawn_rings,
+            border: sim.border,
+            restitution: sim.restitution,
+            integrator: sim.integrator,
+            physics: sim.physics,
+            force_script: sim.force_script().to_string(),
+            classes: sim
+                .params
+                .iter()
+                .map(|p| {
+                    let [r, g, b, _] = Color32::from(p.color).to_array();
+                    ClassConfig {
+                        name: p.name.clone(),
+                        color: [r, g, b],
+                        count: p.count,
+                        power: p.power.to_vec(),
+                        radius: p.radius.to_vec(),
+                        spin: p.spin.to_vec(),
+                        enabled: p.enabled.to_vec(),
+                        lifetime: p.lifetime,
+                        death: p.death,
+                    }
+                })
+                .collect(),
+            reactions: sim.reactions.clone(),
+            attractors: sim.attractors.clone(),
+            flow: sim.flow,
+        }
+    }
+
+    /// Writes the config as pretty-printed RON.
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|err| err.to_string())
+    }
+
+    /// Parses a config, along with warnings about fields it didn't recognize or had to default.
+    pub fn from_ron(text: &str) -> Result<(Self, Vec<String>), String> {
+        let value: Value = ron::from_str(text).map_err(|err| err.to_string())?;
+        let mut warnings = Vec::new();
+        check_fields(&value, FIELDS, "", &mut warnings);
+        if let Value::Map(map) = &value {
+            if let Some((_, Value::Seq(classes))) = map
+                .iter()
+                .find(|(key, _)| **key == Value::String("classes".into()))
+            {
+                for (i, class) in classes.iter().enumerate() {
+                    check_fields(
+                        class,
+                        CLASS_FIELDS,
+                        &format!("classes[{}].", i),
+                        &mut warnings,
+                    );
+                }
+            }
+        }
+
+        // enum variants don't survive the trip through `Value`, so parse the text again
+        let config: Self = ron::from_str(text).map_err(|err| err.to_string())?;
+        if config.format_version > FORMAT_VERSION {
+            warnings.push(format!(
+                "format_version {} is newer than this version understands",
+                config.format_version
+            ));
+        }
+        Ok((config, warnings))
+    }
+
+    /// Copies the config into a simulation, returning warnings for anything that didn't fit.
+    ///
+    /// Values out of range are clamped into it, and missing forces keep their current values:
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use smarticles::{config::Config, Simulation, MAX_POWER};
+    ///
+    /// let mut sim = Simulation::new(400.0, 400.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].radius = [80.0, 80.0];
+    /// let text = "(
+    ///     spawn_radius: NaN,
+    ///     restitution: NaN,
+    ///     physics: (friction: 3.0),
+    ///     classes: [(power: [1000.0, NaN], lifetime: NaN), ()],
+    /// )";
+    /// let (config, _) = Config::from_ron(text).unwrap();
+    /// let warnings = config.apply(&mut sim);
+    ///
+    /// assert_eq!(sim.physics.friction, 1.0);
+    /// assert_eq!(sim.params[0].power, [MAX_POWER, 0.0]);
+    /// assert_eq!(sim.params[0].radius, [80.0, 80.0]);
+    /// assert!(warnings.iter().any(|w| w.contains("classes[0].power[0]")));
+    /// assert!(warnings.iter().any(|w| w.contains("classes[0].power[1]")));
+    ///
+    /// // numbers that aren't numbers fall back to the defaults
+    /// assert_eq!(sim.spawn_radius, 400.0);
+    /// assert_eq!(sim.restitution, 1.0);
+    /// assert_eq!(sim.params[0].lifetime, 0.0);
+    /// for field in ["spawn_radius", "restitution", "classes[0].lifetime"] {
+    ///     assert!(warnings.iter().any(|w| w.contains(field)));
+    /// }
+    ///
+    /// // a world too small for the smallest spawn circle still loads
+    /// let (config, _) = Config::from_ron("(world_w: 5.0, world_h: 0.0)").unwrap();
+    /// let warnings = config.apply(&mut sim);
+    /// assert_eq!((sim.world_w, sim.world_h), (5.0, 1.0));
+    /// assert!(warnings.iter().any(|w| w.contains("world_h")));
+    /// ```
+    pub fn apply<const N: usize>(self, sim: &mut Simulation<N>) -> Vec<String> {
+        let mut warnings = Vec::new();
+        // the same range seeds allow
+        let size = 1.0..=u16::MAX as f32;
+        let (w, h) = (self.world_w, self.world_h);
+        sim.world_w = clamp_field(w, size.clone(), INIT_SIZE, "world_w", &mut warnings);
+        sim.world_h = clamp_field(h, size, INIT_SIZE, "world_h", &mut warnings);
+        // a tiny world still gets the smallest spawn circle
+        let spawn = MIN_SPAWN_RADIUS..=sim.max_spawn_radius().max(MIN_SPAWN_RADIUS);
+        let (radius, default) = (self.spawn_radius, INIT_SIZE / 2.0);
+        sim.spawn_radius = clamp_field(radius, spawn, default, "spawn_radius", &mut warnings);
+        sim.spawn_rings = self.spawn_rings;
+        sim.border = self.border;
+        let restitution = self.restitution;
+        sim.restitution = clamp_field(restitution, 0.0..=1.0, 1.0, "restitution", &mut warnings);
+        sim.integrator = self.integrator;
+        let default = Physics::default();
+        let physics = &mut sim.physics;
+        *physics = self.physics;
+        physics.substeps = physics.substeps.clamp(1, MAX_SUBSTEPS);
+        for (name, value, max, default) in [
+            (
+                "force_scale",
+                &mut physics.force_scale,
+                MAX_FORCE_SCALE,
+                default.force_scale,
+            ),
+            ("friction", &mut physics.friction, 1.0, default.friction),
+            (
+                "border_margin",
+                &mut physics.border_margin,
+                MAX_BORDER_MARGIN,
+                default.border_margin,
+            ),
+            (
+                "gravity",
+                &mut physics.gravity,
+                MAX_GRAVITY,
+                default.gravity,
+            ),
+        ] {
+            let field = format!("physics.{}", name);
+            *value = clamp_field(*value, 0.0..=max, default, &field, &mut warnings);
+        }
+        sim.flow = if self.flow.is_valid() {
+            self.flow
+        } else {
+            warnings.push("flow is out of range, turned off".to_string());
+            Flow::default()
+        };
+        if let Err(err) = sim.set_force_script(&self.force_script) {
+            warnings.push(format!("force_script: {}, using the builtin kernel", err));
+        }
+
+        if self.classes.len() != N {
+            warnings.push(format!(
+                "config has {} types but the simulation has {}",
+                self.classes.len(),
+                N
+            ));
+        }
+        for (i, (p, class)) in sim.params.iter_mut().zip(self.classes).enumerate() {
+            if !class.name.is_empty() {
+                p.name = class.name;
+            }
+            let [r, g, b] = class.color;
+            p.color = Rgba::from(Color32::from_rgb(r, g, b));
+            p.count = class.count.min(MAX_COUNT);
+            let field = format!("classes[{}].lifetime", i);
+            let lifetime = class.lifetime;
+            p.lifetime = clamp_field(lifetime, 0.0..=MAX_LIFETIME, 0.0, &field, &mut warnings);
+            p.death = class.death;
+            for (name, dst, src, range) in [
+                ("power", &mut p.power, class.power, MIN_POWER..=MAX_POWER),
+                (
+                    "radius",
+                    &mut p.radius,
+                    class.radius,
+                    MIN_RADIUS..=MAX_RADIUS,
+                ),
+                ("spin", &mut p.spin, class.spin, MIN_SPIN..=MAX_SPIN),
+            ] {
+                // values past the end of a short list are left as they are
+                for (j, (dst, src)) in dst.iter_mut().zip(src).enumerate() {
+                    let field = format!("classes[{}].{}[{}]", i, name, j);
+                    *dst = clamp_field(src, range.clone(), *dst, &field, &mut warnings);
+                }
+            }
+            for (dst, src) in p
+                .enabled
+                .iter_mut()
+                .zip(class.enabled.into_iter().chain(std::iter::repeat(true)))
+            {
+                *dst = src;
+            }
+        }
+
+        sim.reactions.clear();
+        for (i, reaction) in self.reactions.into_iter().enumerate() {
+            if sim.reactions.len() == MAX_REACTIONS {
+                warnings.push(format!(
+                    "only the first {} reactions were used",
+                    MAX_REACTIONS
+                ));
+                break;
+            }
+            match reaction.is_valid(N) {
+                true => sim.reactions.push(reaction),
+                false => warnings.push(format!("reactions[{}] is out of range, skipped", i)),
+            }
+        }
+
+        sim.attractors.clear();
+        for (i, attractor) in self.attractors.into_iter().enumerate() {
+            if sim.attractors.len() == MAX_ATTRACTORS {
+                warnings.push(format!(
+                    "only the first {} attractors were used",
+                    MAX_ATTRACTORS
+                ));
+                break;
+            }
+            match attractor.is_valid() {
+                true => sim.attractors.push(attractor),
+                false => warnings.push(format!("attractors[{}] is out of range, skipped", i)),
+            }
+        }
+        warnings
+    }
+}
+
+fn check_fields(value: &Value, known: &[&str], prefix: &str, warnings: &mut Vec<String>) {
+    let Value::Map(map) = value else {
+        return;
+    };
+    let keys: Vec<_> = map
+        .keys()
+        .filter_map(|key| match key {
+            Value::String(key) => Some(key.as_str()),
+            _ => None,
+        })
+        .collect();
+    for key in &keys {
+        if !known.contains(key) {
+            warnings.push(format!("ignored unknown field `{}{}`", prefix, key));
+        }
+    }
+    for field in known {
+        if keys.contains(field) {
+            continue;
+        }
+        match KEPT_FIELDS.contains(field) {
+            true => warnings.push(format!(
+                "missing `{}{}`, keeping the current values",
+                prefix, field
+            )),
+            false => warnings.push(format!("missing `{}{}`, using the default", prefix, field)),
+        }
+    }
+}
+
+/// Clamps a value from the file into `range`, warning if it wasn't in it. A value that isn't
+/// a number at all is replaced with `default`.
+fn clamp_field(
+    value: f32,
+    range: RangeInclusive<f32>,
+    default: f32,
+    field: &str,
+    warnings: &mut Vec<String>,
+) -> f32 {
+    if value.is_nan() {
+        let default = default.clamp(*range.start(), *range.end());
+        warnings.push(format!("`{}` isn't a number, using {}", field, default));
+        return default;
+    }
+    if !range.contains(&value) {
+        warnings.push(format!(
+            "`{}` is out of range, clamped to {}..={}",
+            field,
+            range.start(),
+            range.end()
+        ));
+    }
+    value.clamp(*range.start(), *range.end())
+}
@@ -0,0 +1,230 @@
+use egui::Context;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the listener threads check whether they should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The longest line a client can send, which leaves plenty of room for any seed code.
+const MAX_LINE: usize = 64 * 1024;
+
+/// How many clients can be connected at once, each with its own thread.
+const MAX_CLIENTS: usize = 16;
+
+// Clients send one command per line:
+//
+//   play, pause, respawn, randomize, seed <seed>
+//
+// and get one line back for each, either `ok` (followed by the seed for
+// `randomize` and `seed`) or `err <reason>`. A line that's too long, or a
+// client past the limit, gets an `err` and is disconnected. Try it with:
+//
+//   echo play | nc 127.0.0.1 7878
+
+/// A command sent by a remote client.
+pub enum Command {
+    Play,
+    Pause,
+    Respawn,
+    Randomize,
+    Seed(String),
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, String> {
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match (name, arg.is_empty()) {
+            ("play", true) => Ok(Self::Play),
+            ("pause", true) => Ok(Self::Pause),
+            ("respawn", true) => Ok(Self::Respawn),
+            ("randomize", true) => Ok(Self::Randomize),
+            ("seed", false) => Ok(Self::Seed(arg.to_string())),
+            ("seed", true) => Err("seed expects a word seed or an @ code".to_string()),
+            ("play" | "pause" | "respawn" | "randomize", false) => {
+                Err(format!("{} doesn't take an argument", name))
+            }
+            _ => Err(format!("unknown command `{}`", name)),
+        }
+    }
+}
+
+/// A command waiting for the app to run it and reply.
+pub struct Request {
+    pub command: Command,
+    pub reply: Reply,
+}
+
+/// Where the result of a command goes, which the client is waiting for.
+pub struct Reply(Sender<Result<String, String>>);
+
+impl Reply {
+    pub fn send(self, result: Result<String, String>) {
+        // the client may have hung up already, which is fine
+        let _ = self.0.send(result);
+    }
+}
+
+/// Listens for remote commands on a background thread, with a thread per client.
+pub struct ControlServer {
+    addr: SocketAddr,
+    requests: Receiver<Request>,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Starts listening, waking up `ctx` whenever a command arrives.
+    pub fn start(addr: &str, ctx: Context) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        // accepting without blocking lets the thread notice when it should stop
+        listener.set_nonblocking(true)?;
+
+        let (sender, requests) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let listener = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut clients = Vec::new();
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((mut stream, _)) if clients.len() >= MAX_CLIENTS => {
+                            // dropping the stream hangs up on them
+                            let _ = stream.write_all(b"err too many clients\n");
+                        }
+                        Ok((stream, _)) => {
+                            let (sender, stop, ctx) = (sender.clone(), stop.clone(), ctx.clone());
+                            clients.push(std::thread::spawn(move || {
+                                // the client is gone, so there's no one to report errors to
+                                let _ = serve(stream, &sender, &stop, &ctx);
+                            }));
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                            std::thread::sleep(POLL_INTERVAL);
+                        }
+                        Err(_) => std::thread::sleep(POLL_INTERVAL),
+                    }
+                    clients.retain(|client: &JoinHandle<()>| !client.is_finished());
+                }
+                for client in clients {
+                    let _ = client.join();
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            requests,
+            stop,
+            listener: Some(listener),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The commands that arrived since the last call, which must each be replied to.
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        // clients notice within a poll interval, so this doesn't hold up quitting
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+/// Reads commands from a client until it disconnects or the server stops.
+fn serve(
+    stream: TcpStream,
+    sender: &Sender<Request>,
+    stop: &AtomicBool,
+    ctx: &Context,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        // a timeout keeps what was read so far in `line`, so partial lines aren't lost,
+        // and reading one byte past the limit is enough to tell a line is too long
+        let limit = (MAX_LINE + 1 - line.len()) as u64;
+        match reader.by_ref().take(limit).read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(err) => return Err(err),
+        }
+        if line.len() > MAX_LINE && line.last() != Some(&b'\n') {
+            writer.write_all(b"err line too long\n")?;
+            return Ok(());
+        }
+        if line.last() != Some(&b'\n') {
+            // the client closed the connection halfway through a line
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line).trim().to_string();
+        line.clear();
+        if text.is_empty() {
+            continue;
+        }
+        let result = match Command::parse(&text) {
+            Ok(command) => {
+                let (reply, result) = channel();
+                let reply = Reply(reply);
+                if sender.send(Request { command, reply }).is_err() {
+                    return Ok(());
+                }
+                ctx.request_repaint();
+                match wait(&result, stop) {
+                    Some(result) => result,
+                    None => return Ok(()),
+                }
+            }
+            Err(err) => Err(err),
+        };
+        let reply = match result {
+            Ok(msg) if msg.is_empty() => "ok\n".to_string(),
+            Ok(msg) => format!("ok {}\n", msg),
+            Err(err) => format!("err {}\n", err),
+        };
+        writer.write_all(reply.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Waits for the app to reply, giving up if the server stops or the app goes away.
+fn wait(
+    result: &Receiver<Result<String, String>>,
+    stop: &AtomicBool,
+) -> Option<Result<String, String>> {
+    while !stop.load(Ordering::Relaxed) {
+        match result.recv_timeout(POLL_INTERVAL) {
+            Ok(result) => return Some(result),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+    None
+}
@@ -0,0 +1,261 @@
+//! A small expression language for custom force kernels.
+//!
+//! Expressions can use the distance to the other particle `r`, the sensing
+//! `radius` and the `force` (the power setting), numbers, `+ - * / ^`,
+//! parentheses and the functions `abs`, `sqrt`, `exp`, `ln`, `sin`, `cos`,
+//! `tanh`, `min` and `max`.
+//!
+//! ```
+//! use smarticles::expr::Expr;
+//!
+//! // fades out towards the edge of the radius
+//! let expr = Expr::parse("force * (1 - r / radius)").unwrap();
+//! assert_eq!(expr.eval(25.0, 100.0, 8.0), 6.0);
+//!
+//! assert!(Expr::parse("force * (1 - d)").is_err());
+//! ```
+//!
+//! Expressions are limited in length and nesting, so a script pasted in from somewhere can't
+//! run the parser out of stack.
+//!
+//! ```
+//! use smarticles::expr::{Expr, MAX_LEN};
+//!
+//! let too_deep = Some("the expression nests too deeply".to_string());
+//! assert_eq!(Expr::parse(&format!("{}1", "-".repeat(500))).err(), too_deep);
+//! let parens = format!("{}1{}", "(".repeat(500), ")".repeat(500));
+//! assert_eq!(Expr::parse(&parens).err(), too_deep);
+//! assert!(Expr::parse(&format!("1{}", "+1".repeat(MAX_LEN / 2))).is_err());
+//! assert!(Expr::parse(&format!("{}1{}", "(".repeat(20), ")".repeat(20))).is_ok());
+//! ```
+
+/// A parsed expression, compiled to closures so evaluating it doesn't walk a syntax tree.
+pub struct Expr {
+    eval: Compiled,
+}
+
+type Compiled = Box<dyn Fn(&[f32; 3]) -> f32 + Send + Sync>;
+
+const VARS: [&str; 3] = ["r", "radius", "force"];
+
+/// The longest expression, in bytes, that [`Expr::parse`] accepts.
+pub const MAX_LEN: usize = 1024;
+
+/// How deeply parentheses, calls, signs and powers can nest.
+const MAX_DEPTH: usize = 64;
+
+impl Expr {
+    /// Parses and compiles an expression, or describes the first problem with it.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.len() > MAX_LEN {
+            return Err(format!("the expression is longer than {} bytes", MAX_LEN));
+        }
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            depth: 0,
+        };
+        let eval = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Self { eval }),
+            Some(token) => Err(format!("unexpected `{}`", token)),
+        }
+    }
+
+    pub fn eval(&self, r: f32, radius: f32, force: f32) -> f32 {
+        (self.eval)(&[r, radius, force])
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Num(f32),
+    Name(String),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Num(num) => write!(f, "{}", num),
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let num = &text[start..end];
+            let num = num
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", num))?;
+            tokens.push(Token::Num(num));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(text[start..end].to_string()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected `{}`", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&Token::Op(op));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), String> {
+        match self.eat(op) {
+            true => Ok(()),
+            false => Err(format!("expected `{}`", op)),
+        }
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn expr(&mut self) -> Result<Compiled, String> {
+        let mut lhs = self.product()?;
+        loop {
+            if self.eat('+') {
+                let rhs = self.product()?;
+                lhs = Box::new(move |v| lhs(v) + rhs(v));
+            } else if self.eat('-') {
+                let rhs = self.product()?;
+                lhs = Box::new(move |v| lhs(v) - rhs(v));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    // product := unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Compiled, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            if self.eat('*') {
+                let rhs = self.unary()?;
+                lhs = Box::new(move |v| lhs(v) * rhs(v));
+            } else if self.eat('/') {
+                let rhs = self.unary()?;
+                lhs = Box::new(move |v| lhs(v) / rhs(v));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    // every way of nesting comes back through here, so it's where the depth is counted
+    fn unary(&mut self) -> Result<Compiled, String> {
+        if self.depth == MAX_DEPTH {
+            return Err("the expression nests too deeply".to_string());
+        }
+        self.depth += 1;
+        let result = self.signed();
+        self.depth -= 1;
+        result
+    }
+
+    // unary := '-' unary | power
+    fn signed(&mut self) -> Result<Compiled, String> {
+        if self.eat('-') {
+            let inner = self.unary()?;
+            return Ok(Box::new(move |v| -inner(v)));
+        }
+        self.power()
+    }
+
+    // power := atom ('^' unary)?, so `2^-1` works and `2^3^2` is `2^(3^2)`
+    fn power(&mut self) -> Result<Compiled, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exp = self.unary()?;
+            return Ok(Box::new(move |v| base(v).powf(exp(v))));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Compiled, String> {
+        match self.next() {
+            Some(Token::Num(num)) => Ok(Box::new(move |_| num)),
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(Token::Name(name)) => {
+                if let Some(i) = VARS.iter().position(|&var| var == name) {
+                    return Ok(Box::new(move |v| v[i]));
+                }
+                self.call(&name)
+            }
+            Some(token) => Err(format!("unexpected `{}`", token)),
+            None => Err("the expression ends too early".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Compiled, String> {
+        let unary: fn(f32) -> f32 = match name {
+            "abs" => f32::abs,
+            "sqrt" => f32::sqrt,
+            "exp" => f32::exp,
+            "ln" => f32::ln,
+            "sin" => f32::sin,
+            "cos" => f32::cos,
+            "tanh" => f32::tanh,
+            "min" | "max" => {
+                let binary: fn(f32, f32) -> f32 = if name == "min" { f32::min } else { f32::max };
+                self.expect('(')?;
+                let a = self.expr()?;
+                self.expect(',')?;
+                let b = self.expr()?;
+                self.expect(')')?;
+                return Ok(Box::new(move |v| binary(a(v), b(v))));
+            }
+            _ => return Err(format!("unknown name `{}`", name)),
+        };
+        self.expect('(')?;
+        let arg = self.expr()?;
+        self.expect(')')?;
+        Ok(Box::new(move |v| unary(arg(v))))
+    }
+}
@@ -0,0 +1,2270 @@
+//! The particle life engine behind the Smarticles app, usable without its UI.
+//!
+//! A [`Simulation`] holds a fixed number of particle types, each with a count
+//! and a row of forces it feels towards every type (including its own). Call
+//! [`Simulation::spawn_with`] to scatter the particles, then
+//! [`Simulation::step`] to move them along.
+//!
+//! ```
+//! use egui::Rgba;
+//! use smarticles::Simulation;
+//!
+//! let mut sim = Simulation::new(400.0, 400.0, [("red", Rgba::RED), ("blue", Rgba::BLUE)]);
+//! sim.params[0].count = 50;
+//! sim.params[1].count = 50;
+//! // red chases blue, blue runs from red
+//! sim.params[0].power[1] = 20.0;
+//! sim.params[0].radius[1] = 80.0;
+//! sim.params[1].power[0] = -20.0;
+//! sim.params[1].radius[0] = 80.0;
+//!
+//! sim.spawn_with(&mut rand::thread_rng());
+//! for _ in 0..10 {
+//!     sim.step();
+//! }
+//! assert_eq!(sim.dots[0].len(), 50);
+//! ```
+
+pub mod config;
+pub mod expr;
+pub mod matrix;
+pub mod preset;
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use config::Config;
+use egui::{Rgba, Vec2};
+use expr::Expr;
+use rand::distributions::OpenClosed01;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// The default width and height of the world.
+pub const INIT_SIZE: f32 = 800.0;
+pub const MIN_COUNT: usize = 0;
+pub const MAX_COUNT: usize = 1000;
+pub const MIN_POWER: f32 = -100.0;
+pub const MAX_POWER: f32 = 100.0;
+pub const MIN_RADIUS: f32 = 0.0;
+pub const MAX_RADIUS: f32 = 500.0;
+pub const MIN_SPIN: f32 = -20.0;
+pub const MAX_SPIN: f32 = 20.0;
+pub const MAX_FORCE_SCALE: f32 = 4.0;
+pub const MAX_BORDER_MARGIN: f32 = 100.0;
+pub const MAX_SUBSTEPS: u32 = 8;
+pub const MIN_SPAWN_RADIUS: f32 = 10.0;
+pub const MAX_REACTIONS: usize = 16;
+pub const MAX_REACTION_RADIUS: f32 = 100.0;
+pub const MAX_REACTION_STEPS: u32 = 600;
+pub const MAX_LIFETIME: f32 = 120.0;
+pub const MAX_ATTRACTORS: usize = 16;
+pub const MAX_ATTRACTOR_STRENGTH: f32 = 1000.0;
+pub const MAX_FLOW_STRENGTH: f32 = 500.0;
+pub const MAX_GRAVITY: f32 = 100.0;
+pub const MAX_SETTLE_SPEED: f32 = 1.0;
+pub const MAX_SETTLE_STEPS: u32 = 1200;
+pub const MAX_BONDS: usize = 5000;
+pub const MAX_STIFFNESS: f32 = 1.0;
+/// How often the app steps the simulation, which lifetimes in seconds are counted in.
+pub const STEPS_PER_SEC: f32 = 60.0;
+
+/// Marks a versioned seed code, since older codes started directly with the world size.
+pub const SEED_MAGIC: u16 = 0xFFFF;
+/// The seed code version written by [`Simulation::export`].
+pub const SEED_VERSION: u8 = 18;
+
+/// How far the particles are moved towards the center of the world each step when
+/// recentering, which is slow enough not to be noticed.
+const RECENTER_RATE: f32 = 0.001;
+
+/// How far past its rest length a bond can stretch before it snaps, so a particle that
+/// respawns doesn't drag its partners across the world.
+const BOND_SNAP: f32 = 100.0;
+
+/// How many cells the longer side of the world is split into for gravity. Each particle is
+/// pulled by every cell with particles in it, so this is what gravity costs per particle.
+const GRAVITY_CELLS: usize = 16;
+
+/// How far the border moves towards a new world size each step, which gets it
+/// most of the way there in about a second at 60 steps per second.
+const BORDER_EASE: f32 = 0.05;
+
+/// A particle simulation with `N` types of particle.
+pub struct Simulation<const N: usize> {
+    pub world_w: f32,
+    pub world_h: f32,
+    /// Particles spawn within this distance of the center of the world.
+    pub spawn_radius: f32,
+    /// Splits the spawn circle into a ring for each type, from the middle outwards, so the
+    /// types start apart instead of mixed together. Particles respawned at the end of their
+    /// lifetime go back to their ring too.
+    ///
+    /// ```
+    /// use egui::{Rgba, Vec2};
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::Simulation;
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].count = 200;
+    /// sim.params[1].count = 200;
+    /// sim.spawn_radius = 300.0;
+    /// sim.spawn_rings = true;
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+    ///
+    /// let center = Vec2::splat(400.0);
+    /// let dist = |class: usize| sim.dots[class].iter().map(|d| (d.pos - center).length());
+    /// assert!(dist(0).all(|r| r <= 150.0));
+    /// assert!(dist(1).all(|r| r >= 150.0 && r <= 300.0));
+    /// ```
+    pub spawn_rings: bool,
+    pub params: [Params<N>; N],
+    /// The particles of each type. [`Simulation::fit_counts`] adds or removes only the
+    /// difference when the counts change, leaving the rest where they are.
+    pub dots: [Vec<Dot>; N],
+    /// Scales the counts down when spawning, so a machine can run seeds that are too big for
+    /// it. It isn't part of seeds, and 1.0 spawns the counts as they are.
+    pub count_scale: f32,
+    /// How many steps apart the forces from each type are worked out, reusing them in
+    /// between. 1 updates them every step, and higher is faster but less accurate. Like
+    /// `count_scale`, it isn't part of seeds.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::Simulation;
+    ///
+    /// // `None` leaves the strides alone, so every force is worked out without the cache
+    /// let run = |strides: Option<[u32; 2]>| {
+    ///     let mut sim = Simulation::new(300.0, 300.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    ///     if let Some(strides) = strides {
+    ///         sim.strides = strides;
+    ///     }
+    ///     sim.params[0].count = 200;
+    ///     sim.params[0].power = [-30.0, 40.0];
+    ///     sim.params[0].radius = [60.0, 90.0];
+    ///     sim.params[1].count = 200;
+    ///     sim.params[1].power = [20.0, -10.0];
+    ///     sim.params[1].radius = [70.0, 50.0];
+    ///     sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+    ///     for _ in 0..100 {
+    ///         sim.step();
+    ///     }
+    ///     let dots = sim.dots.iter().flatten();
+    ///     dots.map(|d| (d.pos.x, d.pos.y)).collect::<Vec<_>>()
+    /// };
+    /// let bits = |dots: &[(f32, f32)]| {
+    ///     dots.iter().map(|(x, y)| (x.to_bits(), y.to_bits())).collect::<Vec<_>>()
+    /// };
+    /// let every = run(Some([1, 1]));
+    /// assert_eq!(bits(&every), bits(&run(None)));
+    ///
+    /// // reusing b's forces drifts away from that, but never blows up
+    /// let reused = run(Some([1, 3]));
+    /// assert_ne!(bits(&every), bits(&reused));
+    /// assert!(reused.iter().all(|(x, y)| x.is_finite() && y.is_finite()));
+    /// ```
+    pub strides: [u32; N],
+    pub border: BorderBehavior,
+    /// How much speed particles keep when they bounce off the border.
+    pub restitution: f32,
+    pub physics: Physics,
+    pub integrator: Integrator,
+    /// Rules for particles turning into other types. Conversions move particles between
+    /// `dots`, but leave the counts in `params` as they were spawned.
+    pub reactions: Vec<Reaction>,
+    /// Fixed points in the world that pull on every particle in range.
+    pub attractors: Vec<Attractor>,
+    /// A push on every particle that depends only on where it is.
+    pub flow: Flow,
+    /// The simulation counts as settled once the particles move less than this far per step
+    /// on average for `settle_steps` steps in a row. 0 turns it off. Like `count_scale`, it
+    /// isn't part of seeds.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::Simulation;
+    ///
+    /// // with nothing pulling on them, the particles never move at all
+    /// let mut sim = Simulation::new(300.0, 300.0, [("a", Rgba::RED)]);
+    /// sim.params[0].count = 50;
+    /// sim.settle_speed = 0.01;
+    /// sim.settle_steps = 10;
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+    /// for _ in 0..9 {
+    ///     sim.step();
+    /// }
+    /// assert!(!sim.settled());
+    /// sim.step();
+    /// assert!(sim.settled());
+    ///
+    /// // a strong pull keeps them moving for a while
+    /// sim.params[0].power = [100.0];
+    /// sim.params[0].radius = [100.0];
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+    /// for _ in 0..10 {
+    ///     sim.step();
+    /// }
+    /// assert!(sim.motion() > 0.01 && !sim.settled());
+    /// ```
+    pub settle_speed: f32,
+    pub settle_steps: u32,
+    /// Multipliers on top of the settings, for making them pulse along with music. Like
+    /// `count_scale`, it isn't part of seeds.
+    pub modulation: Modulation,
+    /// Springs between particles, which are cleared on spawning.
+    bonds: Vec<Bond>,
+    force_script: String,
+    kernel: Option<Expr>,
+    script_error: Option<String>,
+    border_size: Vec2,
+    steps: u64,
+    forces: [Vec<Vec2>; N],
+    mid: [Vec<Dot>; N],
+    /// The force from each type on every particle, kept for the types not updated every step.
+    stride_cache: StrideCache<N>,
+    cache_stale: bool,
+    /// How many steps in a row each particle has been in contact, for each reaction.
+    contact: Vec<Vec<u32>>,
+    /// Rolls reactions and respawns, seeded on spawning so runs can be repeated.
+    rand: SmallRng,
+    /// How far the particles moved on average in the last step.
+    motion: f32,
+    /// How many steps in a row the motion has been below `settle_speed`.
+    still_steps: u32,
+}
+
+/// The settings of one particle type, with a force towards each type.
+#[derive(Clone)]
+pub struct Params<const N: usize> {
+    pub name: String,
+    pub color: Rgba,
+    pub count: usize,
+    /// Attraction towards each type, or repulsion when negative.
+    pub power: [f32; N],
+    /// How far away each type can be sensed.
+    pub radius: [f32; N],
+    /// Sideways push around each type, which makes clusters rotate.
+    pub spin: [f32; N],
+    /// Whether each type is felt at all. Unlike a power of 0, a disabled pair isn't
+    /// worked out, so it takes no time.
+    pub enabled: [bool; N],
+    /// How many seconds the particles live for, or 0 to live forever.
+    pub lifetime: f32,
+    /// What happens to particles that outlive their `lifetime`.
+    pub death: Death,
+}
+
+/// A single particle.
+#[derive(Clone, Default)]
+pub struct Dot {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    /// The force from the last step, which the Verlet integrator carries over.
+    pub acc: Vec2,
+    /// How many steps ago the particle spawned.
+    pub age: u32,
+}
+
+impl Dot {
+    fn new(pos: Vec2) -> Self {
+        Self {
+            pos,
+            vel: Vec2::ZERO,
+            acc: Vec2::ZERO,
+            age: 0,
+        }
+    }
+}
+
+/// Why a seed code couldn't be applied.
+#[derive(Debug)]
+pub enum SeedError {
+    InvalidBase64,
+    Truncated,
+    NewerVersion(u8),
+    OutOfRange(&'static str),
+}
+
+impl std::fmt::Display for SeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidBase64 => write!(f, "The seed code contains invalid characters."),
+            Self::Truncated => write!(f, "The seed code is incomplete."),
+            Self::NewerVersion(version) => {
+                write!(f, "The seed code is from a newer version ({}).", version)
+            }
+            Self::OutOfRange(what) => write!(f, "The seed code has an invalid {}.", what),
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+/// What happens to particles at the edge of the world.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BorderBehavior {
+    /// Particles turn around once they're within the border margin.
+    Soft,
+    /// Particles bounce off the edge, keeping some of their speed.
+    Bounce,
+    /// Particles that leave the world respawn inside the spawn circle, or in their own ring
+    /// if types spawn in rings.
+    Absorb,
+}
+
+impl BorderBehavior {
+    pub const ALL: [Self; 3] = [Self::Soft, Self::Bounce, Self::Absorb];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Soft => "Soft",
+            Self::Bounce => "Bounce",
+            Self::Absorb => "Absorb",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Bounce,
+            2 => Self::Absorb,
+            _ => Self::Soft,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Bounds {
+    world_w: f32,
+    world_h: f32,
+    spawn_radius: f32,
+    /// How many rings the spawn circle is split into, if each type spawns in its own.
+    spawn_rings: Option<usize>,
+    behavior: BorderBehavior,
+    restitution: f32,
+}
+
+/// What happens to a particle at the end of its lifetime.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Death {
+    /// It starts over inside the spawn circle.
+    #[default]
+    Respawn,
+    /// It's removed until the next respawn.
+    Vanish,
+}
+
+impl Death {
+    pub const ALL: [Self; 2] = [Self::Respawn, Self::Vanish];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Respawn => "Respawn",
+            Self::Vanish => "Vanish",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Vanish,
+            _ => Self::Respawn,
+        }
+    }
+}
+
+/// How particles are moved from one step to the next.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    SemiImplicitEuler,
+    Verlet,
+    Midpoint,
+}
+
+impl Integrator {
+    pub const ALL: [Self; 3] = [Self::SemiImplicitEuler, Self::Verlet, Self::Midpoint];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SemiImplicitEuler => "Euler",
+            Self::Verlet => "Verlet",
+            Self::Midpoint => "RK2 (Midpoint)",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Verlet,
+            2 => Self::Midpoint,
+            _ => Self::SemiImplicitEuler,
+        }
+    }
+}
+
+/// Settings that apply to every particle.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Physics {
+    pub force_scale: f32,
+    /// The share of speed lost every step.
+    pub friction: f32,
+    /// How close to the edge soft borders start turning particles around.
+    pub border_margin: f32,
+    /// How many smaller steps each [`Simulation::step`] is split into.
+    pub substeps: u32,
+    /// Cancels out the net movement of all the particles after each step, so uneven forces
+    /// can't push the whole system off to one side.
+    ///
+    /// ```
+    /// use egui::{Rgba, Vec2};
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::Simulation;
+    ///
+    /// // a chases b, and b runs away from a
+    /// let mut sim = Simulation::new(400.0, 400.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].count = 20;
+    /// sim.params[1].count = 20;
+    /// sim.params[0].power = [0.0, 40.0];
+    /// sim.params[0].radius = [0.0, 200.0];
+    /// sim.params[1].power = [0.0, -40.0];
+    /// sim.params[1].radius = [0.0, 200.0];
+    /// sim.physics.remove_drift = true;
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+    ///
+    /// let centroid = |sim: &Simulation<2>| {
+    ///     sim.dots.iter().flatten().fold(Vec2::ZERO, |sum, d| sum + d.pos) / 40.0
+    /// };
+    /// let start = centroid(&sim);
+    /// for _ in 0..10_000 {
+    ///     sim.step();
+    /// }
+    /// assert!((centroid(&sim) - start).length() < 1.0);
+    /// ```
+    pub remove_drift: bool,
+    /// Along with `remove_drift`, slowly moves the particles back to the center of the world.
+    pub recenter: bool,
+    /// A weak pull on every particle towards where the others are, which reaches across the
+    /// whole world, unlike the forces between types. 0 turns it off.
+    ///
+    /// ```
+    /// use egui::{Rgba, Vec2};
+    /// use smarticles::{Dot, Simulation, MAX_GRAVITY};
+    ///
+    /// // two clumps far out of each other's range
+    /// let mut sim = Simulation::new(1200.0, 1200.0, [("a", Rgba::RED)]);
+    /// for i in 0..20 {
+    ///     let offset = Vec2::new((i % 5) as f32, (i / 5) as f32) * 4.0;
+    ///     for x in [200.0, 1000.0] {
+    ///         let pos = Vec2::new(x, 600.0) + offset;
+    ///         sim.dots[0].push(Dot { pos, ..Default::default() });
+    ///     }
+    /// }
+    /// let gap = |sim: &Simulation<1>| {
+    ///     let (left, right) = sim.dots[0].iter().partition::<Vec<_>, _>(|d| d.pos.x < 600.0);
+    ///     let center = |dots: Vec<&Dot>| dots.iter().map(|d| d.pos.x).sum::<f32>() / 20.0;
+    ///     center(right) - center(left)
+    /// };
+    /// let start = gap(&sim);
+    ///
+    /// // at full strength, they close most of the gap in under a minute
+    /// sim.physics.gravity = MAX_GRAVITY;
+    /// for _ in 0..1200 {
+    ///     sim.step();
+    /// }
+    /// assert!(gap(&sim) < start - 100.0);
+    /// ```
+    pub gravity: f32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            force_scale: 1.0,
+            friction: 0.5,
+            border_margin: 10.0,
+            substeps: 1,
+            remove_drift: false,
+            recenter: false,
+            gravity: 0.0,
+        }
+    }
+}
+
+/// Multipliers that vary the settings while the simulation runs, without changing them. At
+/// their defaults of 1.0 they change nothing at all, so turning modulation off steps exactly
+/// as if it had never been on.
+///
+/// ```
+/// use egui::Rgba;
+/// use rand::rngs::SmallRng;
+/// use rand::SeedableRng;
+/// use smarticles::{Modulation, Simulation};
+///
+/// let run = |modulation: &[Modulation]| {
+///     let mut sim = Simulation::new(300.0, 300.0, [("a", Rgba::RED)]);
+///     sim.params[0].count = 100;
+///     sim.params[0].power = [30.0];
+///     sim.params[0].radius = [80.0];
+///     sim.spawn_with(&mut SmallRng::seed_from_u64(1));
+///     for m in modulation {
+///         sim.modulation = *m;
+///         sim.step();
+///     }
+///     let dots = sim.dots[0].iter();
+///     dots.map(|d| (d.pos.x.to_bits(), d.pos.y.to_bits())).collect::<Vec<_>>()
+/// };
+/// let off = Modulation::default();
+/// let pulse = Modulation { force: 2.5, world: 0.8, friction: 0.5 };
+/// assert_ne!(run(&[off; 50]), run(&[&[pulse; 10][..], &[off; 40]].concat()));
+///
+/// // without any force, the particles stay where they spawned
+/// let still = Modulation { force: 0.0, ..off };
+/// assert_eq!(run(&[]), run(&[still; 50]));
+/// ```
+#[derive(Clone, Copy, PartialEq)]
+pub struct Modulation {
+    /// Scales every force, like the force scale does.
+    pub force: f32,
+    /// Scales the size of the world. The border eases towards it like it does when the world
+    /// is resized.
+    pub world: f32,
+    /// Scales the friction, which is kept from going over 1.
+    pub friction: f32,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            force: 1.0,
+            world: 1.0,
+            friction: 1.0,
+        }
+    }
+}
+
+/// A particle of type `a` that stays close to one of type `b` for long enough may turn into
+/// type `result`.
+///
+/// ```
+/// use egui::Rgba;
+/// use smarticles::{Reaction, Simulation};
+///
+/// let mut sim = Simulation::new(50.0, 50.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+/// sim.params[0].count = 10;
+/// sim.params[1].count = 1;
+/// sim.spawn_with(&mut rand::thread_rng());
+/// // the whole world is in range, so every a turns into a b after a single step
+/// sim.reactions.push(Reaction {
+///     a: 0,
+///     b: 1,
+///     result: 1,
+///     radius: 100.0,
+///     steps: 0,
+///     chance: 1.0,
+/// });
+/// sim.step();
+/// assert_eq!((sim.dots[0].len(), sim.dots[1].len()), (0, 11));
+/// ```
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reaction {
+    pub a: usize,
+    pub b: usize,
+    pub result: usize,
+    /// How close the particles have to be.
+    pub radius: f32,
+    /// How many steps in a row they have to stay that close before converting.
+    pub steps: u32,
+    /// The chance of converting on each step after that.
+    pub chance: f32,
+}
+
+impl Default for Reaction {
+    fn default() -> Self {
+        Self {
+            a: 0,
+            b: 0,
+            result: 0,
+            radius: 10.0,
+            steps: 30,
+            chance: 0.1,
+        }
+    }
+}
+
+impl Reaction {
+    fn is_valid(&self, classes: usize) -> bool {
+        self.a < classes
+            && self.b < classes
+            && self.result < classes
+            && (0.0..=MAX_REACTION_RADIUS).contains(&self.radius)
+            && self.steps <= MAX_REACTION_STEPS
+            && (0.0..=1.0).contains(&self.chance)
+    }
+}
+
+/// A fixed point that pulls every particle within its radius towards it, or pushes them
+/// away when its strength is negative.
+///
+/// ```
+/// use egui::{Rgba, Vec2};
+/// use smarticles::{Attractor, Simulation};
+///
+/// let mut sim = Simulation::new(400.0, 400.0, [("a", Rgba::RED)]);
+/// sim.params[0].count = 50;
+/// sim.spawn_with(&mut rand::thread_rng());
+/// let center = Vec2::new(200.0, 200.0);
+/// let spread = |sim: &Simulation<1>| {
+///     sim.dots[0].iter().map(|d| (d.pos - center).length()).sum::<f32>()
+/// };
+/// let before = spread(&sim);
+///
+/// sim.attractors.push(Attractor {
+///     pos: center,
+///     strength: 500.0,
+///     radius: 400.0,
+/// });
+/// for _ in 0..30 {
+///     sim.step();
+/// }
+/// assert!(spread(&sim) < before);
+/// ```
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attractor {
+    pub pos: Vec2,
+    /// How hard particles are pulled in, on the same scale as one particle's power, but
+    /// repelled when negative.
+    pub strength: f32,
+    pub radius: f32,
+}
+
+impl Default for Attractor {
+    fn default() -> Self {
+        Self {
+            pos: Vec2::ZERO,
+            strength: 200.0,
+            radius: 100.0,
+        }
+    }
+}
+
+impl Attractor {
+    fn is_valid(&self) -> bool {
+        self.pos.x.is_finite()
+            && self.pos.y.is_finite()
+            && (-MAX_ATTRACTOR_STRENGTH..=MAX_ATTRACTOR_STRENGTH).contains(&self.strength)
+            && (0.0..=MAX_RADIUS).contains(&self.radius)
+    }
+
+    fn force(&self, pos: Vec2, physics: &Physics) -> Vec2 {
+        let d = pos - self.pos;
+        let r = d.length();
+        if r < self.radius && r > 0.0 {
+            d / r * self.strength * physics.force_scale / -100.0
+        } else {
+            Vec2::ZERO
+        }
+    }
+}
+
+/// The shape of a [`Flow`].
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlowKind {
+    /// The same push everywhere, in the direction of the angle.
+    Wind,
+    /// Turns around the center of the world like a wheel, fastest at the edges.
+    Vortex,
+    /// Pushes along the angle on one side of the center and against it on the other.
+    Shear,
+}
+
+impl FlowKind {
+    pub const ALL: [Self; 3] = [Self::Wind, Self::Vortex, Self::Shear];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Wind => "Wind",
+            Self::Vortex => "Vortex",
+            Self::Shear => "Shear",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Vortex,
+            2 => Self::Shear,
+            _ => Self::Wind,
+        }
+    }
+}
+
+/// A force on every particle that depends only on its position. A strength of 0 turns it off.
+///
+/// ```
+/// use egui::Vec2;
+/// use smarticles::{Flow, FlowKind};
+///
+/// let world = Vec2::new(800.0, 600.0);
+/// let wind = Flow {
+///     kind: FlowKind::Wind,
+///     angle: 90.0,
+///     strength: 100.0,
+/// };
+/// let f = wind.at(Vec2::new(10.0, 10.0), world);
+/// assert!(f.x.abs() < 1e-4 && (f.y - 100.0).abs() < 1e-4);
+///
+/// // a vortex stands still in the middle and spins the rest
+/// let vortex = Flow {
+///     kind: FlowKind::Vortex,
+///     ..wind
+/// };
+/// assert_eq!(vortex.at(world / 2.0, world), Vec2::ZERO);
+/// assert!(vortex.at(Vec2::new(700.0, 300.0), world).y > 0.0);
+/// ```
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flow {
+    pub kind: FlowKind,
+    /// The direction of wind and shear in degrees, clockwise from the right since y points
+    /// down.
+    pub angle: f32,
+    /// On the same scale as one particle's power. Negative turns the flow around.
+    pub strength: f32,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self {
+            kind: FlowKind::Wind,
+            angle: 0.0,
+            strength: 0.0,
+        }
+    }
+}
+
+impl Flow {
+    /// The push at a position in a world of the given size, before the force scale.
+    pub fn at(&self, pos: Vec2, world: Vec2) -> Vec2 {
+        let angle = self.angle.to_radians();
+        let dir = Vec2::new(angle.cos(), angle.sin());
+        let d = pos - world / 2.0;
+        let half = (world.x.min(world.y) / 2.0).max(1.0);
+        match self.kind {
+            FlowKind::Wind => dir * self.strength,
+            FlowKind::Vortex => Vec2::new(-d.y, d.x) / half * self.strength,
+            FlowKind::Shear => {
+                let side = Vec2::new(-dir.y, dir.x).dot(d) / half;
+                dir * side * self.strength
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        (0.0..=360.0).contains(&self.angle)
+            && (-MAX_FLOW_STRENGTH..=MAX_FLOW_STRENGTH).contains(&self.strength)
+    }
+}
+
+/// A spring between two particles, each given as a (type, index) pair, which pulls them
+/// back together when stretched and apart when squashed.
+///
+/// ```
+/// use egui::{Rgba, Vec2};
+/// use smarticles::{Dot, Simulation};
+///
+/// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED)]);
+/// sim.dots[0].push(Dot { pos: Vec2::new(400.0, 400.0), ..Default::default() });
+/// sim.dots[0].push(Dot { pos: Vec2::new(420.0, 400.0), ..Default::default() });
+/// assert!(sim.bond((0, 0), (0, 1), 0.2));
+/// // the same pair can't be bonded twice
+/// assert!(!sim.bond((0, 1), (0, 0), 0.2));
+///
+/// sim.dots[0][1].pos.x = 440.0;
+/// for _ in 0..200 {
+///     sim.step();
+/// }
+/// let dist = (sim.dots[0][1].pos - sim.dots[0][0].pos).length();
+/// assert!((dist - 20.0).abs() < 1.0);
+/// ```
+#[derive(Clone, Copy, PartialEq)]
+pub struct Bond {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+    /// The length the spring settles at.
+    pub rest: f32,
+    /// How hard the spring pulls for every unit it's stretched.
+    pub stiffness: f32,
+}
+
+impl Bond {
+    fn joins(&self, dot: (usize, usize)) -> bool {
+        self.a == dot || self.b == dot
+    }
+
+    fn ends(&mut self) -> [&mut (usize, usize); 2] {
+        [&mut self.a, &mut self.b]
+    }
+}
+
+/// The forces that don't come from particles in range.
+struct External<'a> {
+    attractors: &'a [Attractor],
+    flow: Flow,
+    world: Vec2,
+    gravity: Option<Gravity>,
+}
+
+impl External<'_> {
+    // adding nothing leaves the forces exactly as they were, so this costs nothing when unused
+    fn add(&self, f: Vec2, pos: Vec2, physics: &Physics) -> Vec2 {
+        let mut f = (self.attractors.iter()).fold(f, |f, a| f + a.force(pos, physics));
+        if let Some(gravity) = &self.gravity {
+            f += gravity.at(pos) * physics.force_scale;
+        }
+        if self.flow.strength == 0.0 {
+            return f;
+        }
+        f + self.flow.at(pos, self.world) * physics.force_scale / 100.0
+    }
+}
+
+/// Where the particles are on a coarse grid, so pulling every particle towards all the
+/// others only costs one pass over the cells.
+struct Gravity {
+    /// The center of each cell with particles in it, and its share of all the particles.
+    cells: Vec<(Vec2, f32)>,
+    strength: f32,
+    /// Keeps the pull from a particle's own cell from blowing up as it gets close.
+    softening_sq: f32,
+}
+
+impl Gravity {
+    /// Sorts the particles into cells, or returns `None` if gravity is off.
+    fn new<const N: usize>(dots: &[Vec<Dot>; N], world: Vec2, strength: f32) -> Option<Self> {
+        let total = dots.iter().map(Vec::len).sum::<usize>();
+        if strength == 0.0 || total == 0 {
+            return None;
+        }
+        let cell = (world.x.max(world.y) / GRAVITY_CELLS as f32).max(1.0);
+        let cols = ((world.x / cell).ceil() as usize).max(1);
+        let rows = ((world.y / cell).ceil() as usize).max(1);
+        let mut sums = vec![(Vec2::ZERO, 0); cols * rows];
+        for dot in dots.iter().flatten() {
+            // particles just outside the border count towards the cells along it
+            let x = ((dot.pos.x / cell) as usize).min(cols - 1);
+            let y = ((dot.pos.y / cell) as usize).min(rows - 1);
+            let (sum, count) = &mut sums[y * cols + x];
+            *sum += dot.pos;
+            *count += 1;
+        }
+        let cells = (sums.into_iter())
+            .filter(|&(_, count)| count > 0)
+            .map(|(sum, count)| (sum / count as f32, count as f32 / total as f32))
+            .collect();
+        Some(Self {
+            cells,
+            strength,
+            softening_sq: cell * cell,
+        })
+    }
+
+    // the pull falls off with distance rather than its square, which is how gravity works
+    // in two dimensions, and keeps it noticeable across the world
+    fn at(&self, pos: Vec2) -> Vec2 {
+        let pull = self.cells.iter().fold(Vec2::ZERO, |f, &(center, share)| {
+            let d = center - pos;
+            f + d * (share / (d.length_sq() + self.softening_sq))
+        });
+        pull * self.strength
+    }
+}
+
+impl Physics {
+    fn modulated(mut self, modulation: &Modulation) -> Self {
+        self.force_scale *= modulation.force;
+        self.friction = (self.friction * modulation.friction).clamp(0.0, 1.0);
+        self
+    }
+
+    fn dt(&self) -> f32 {
+        1.0 / self.substeps as f32
+    }
+
+    // friction is given per full step, so substeps only lose their share of it
+    fn keep(&self) -> f32 {
+        (1.0 - self.friction).powf(self.dt())
+    }
+}
+
+impl<const N: usize> Simulation<N> {
+    /// Creates an empty world with a name and color for each particle type.
+    pub fn new<S>(world_w: f32, world_h: f32, classes: [(S, Rgba); N]) -> Self
+    where
+        S: ToString,
+    {
+        Self {
+            world_w,
+            world_h,
+            spawn_radius: Vec2::new(world_w, world_h).length() / 2.0,
+            spawn_rings: false,
+            params: classes.map(|(name, color)| Params {
+                name: name.to_string(),
+                color,
+                count: 0,
+                power: [0.0; N],
+                radius: [MIN_RADIUS; N],
+                spin: [0.0; N],
+                enabled: [true; N],
+                lifetime: 0.0,
+                death: Death::Respawn,
+            }),
+            dots: std::array::from_fn(|_| Vec::new()),
+            count_scale: 1.0,
+            strides: [1; N],
+            border: BorderBehavior::Soft,
+            restitution: 1.0,
+            physics: Physics::default(),
+            integrator: Integrator::SemiImplicitEuler,
+            reactions: Vec::new(),
+            attractors: Vec::new(),
+            flow: Flow::default(),
+            settle_speed: 0.0,
+            settle_steps: 300,
+            modulation: Modulation::default(),
+            bonds: Vec::new(),
+            force_script: String::new(),
+            kernel: None,
+            script_error: None,
+            border_size: Vec2::new(world_w, world_h),
+            steps: 0,
+            forces: std::array::from_fn(|_| Vec::new()),
+            mid: std::array::from_fn(|_| Vec::new()),
+            stride_cache: std::array::from_fn(|_| Vec::new()),
+            cache_stale: true,
+            contact: Vec::new(),
+            rand: SmallRng::seed_from_u64(0),
+            motion: 0.0,
+            still_steps: 0,
+        }
+    }
+
+    /// Creates an empty world from a config, along with warnings for anything that didn't fit.
+    ///
+    /// ```
+    /// use smarticles::{config::Config, Simulation};
+    ///
+    /// let (config, _) = Config::from_ron("(world_w: 300.0, world_h: 200.0)").unwrap();
+    /// let (sim, _) = Simulation::<4>::from_config(config);
+    /// assert_eq!((sim.world_w, sim.world_h), (300.0, 200.0));
+    /// ```
+    pub fn from_config(config: Config) -> (Self, Vec<String>) {
+        let white = Rgba::from_rgb(1.0, 1.0, 1.0);
+        let mut sim = Self::new(INIT_SIZE, INIT_SIZE, std::array::from_fn(|i| (i, white)));
+        let warnings = config.apply(&mut sim);
+        (sim, warnings)
+    }
+
+    /// The force expression, or an empty string for the builtin kernel.
+    pub fn force_script(&self) -> &str {
+        &self.force_script
+    }
+
+    /// Why the force expression couldn't be used, if it couldn't.
+    pub fn script_error(&self) -> Option<&str> {
+        self.script_error.as_deref()
+    }
+
+    /// How hard a particle of type `j` at distance `r` pulls on one of type `i`, which is
+    /// negative when it pushes away. The spin isn't included.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use smarticles::Simulation;
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].power[1] = 40.0;
+    /// sim.params[0].radius[1] = 80.0;
+    /// assert!(sim.pull(0, 1, 50.0) > 0.0);
+    /// assert_eq!(sim.pull(0, 1, 90.0), 0.0);
+    ///
+    /// sim.set_force_script("force * (1 - r / radius)").unwrap();
+    /// assert!(sim.pull(0, 1, 20.0) > sim.pull(0, 1, 60.0));
+    /// ```
+    pub fn pull(&self, i: usize, j: usize, r: f32) -> f32 {
+        let p = &self.params[i];
+        if !p.enabled[j] || r <= 0.0 || r >= p.radius[j] {
+            return 0.0;
+        }
+        let w = match &self.kernel {
+            Some(kernel) => kernel.eval(r, p.radius[j], p.power[j]),
+            None => p.power[j],
+        };
+        match w.is_finite() {
+            true => w * self.physics.force_scale / 100.0,
+            false => 0.0,
+        }
+    }
+
+    /// How hard the particles of type `j` pull on each particle of type `i` in total, which
+    /// is negative when they push it away on balance.
+    pub fn pulls(&self, i: usize, j: usize) -> Vec<f32> {
+        (self.dots[i].par_iter())
+            .map(|p1| {
+                let others = self.dots[j].iter();
+                others
+                    .map(|p2| self.pull(i, j, (p1.pos - p2.pos).length()))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Works out the force on every particle where they are now, the same way a step does,
+    /// into `forces`. The buffers are resized to fit, so passing the same ones each time
+    /// doesn't allocate.
+    ///
+    /// ```
+    /// use egui::{Rgba, Vec2};
+    /// use smarticles::{Dot, Simulation};
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].power[1] = 40.0;
+    /// sim.params[0].radius[1] = 80.0;
+    /// sim.dots[0].push(Dot { pos: Vec2::new(400.0, 400.0), ..Default::default() });
+    /// sim.dots[1].push(Dot { pos: Vec2::new(450.0, 400.0), ..Default::default() });
+    ///
+    /// let mut forces = Default::default();
+    /// sim.forces(&mut forces);
+    /// // a is pulled towards b, and b doesn't care about a
+    /// assert!(forces[0][0].x > 0.0 && forces[0][0].y == 0.0);
+    /// assert_eq!(forces[1][0], Vec2::ZERO);
+    /// ```
+    pub fn forces(&self, forces: &mut [Vec<Vec2>; N]) {
+        let external = External {
+            attractors: &self.attractors,
+            flow: self.flow,
+            world: Vec2::new(self.world_w, self.world_h),
+            gravity: Gravity::new(&self.dots, self.border_size, self.physics.gravity),
+        };
+        compute_forces(
+            &self.params,
+            &self.physics.modulated(&self.modulation),
+            self.kernel.as_ref(),
+            &external,
+            &self.dots,
+            forces,
+            None,
+        );
+        add_bond_forces(&self.bonds, &self.dots, forces);
+    }
+
+    /// Replaces the builtin force kernel with an [`Expr`], which takes effect on the next step.
+    ///
+    /// An empty script restores the builtin kernel, which is also used as long as the script
+    /// doesn't parse. The script is kept either way, so it can be fixed and shared, unless
+    /// it's longer than [`expr::MAX_LEN`] and could never parse.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use smarticles::{expr, Simulation};
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED)]);
+    /// assert!(sim.set_force_script("force / (1 + r)").is_ok());
+    /// assert!(sim.set_force_script("force /").is_err());
+    /// assert_eq!(sim.force_script(), "force /");
+    ///
+    /// assert!(sim.set_force_script(&"é".repeat(expr::MAX_LEN)).is_err());
+    /// assert_eq!(sim.force_script(), "");
+    /// assert!(sim.script_error().is_some());
+    /// ```
+    pub fn set_force_script(&mut self, script: &str) -> Result<(), String> {
+        if script.len() > expr::MAX_LEN {
+            let err = format!("the script is longer than {} bytes", expr::MAX_LEN);
+            self.force_script.clear();
+            self.kernel = None;
+            self.script_error = Some(err.clone());
+            return Err(err);
+        }
+        self.force_script = script.to_string();
+        let result = match script.trim() {
+            "" => Ok(None),
+            script => Expr::parse(script).map(Some),
+        };
+        match result {
+            Ok(kernel) => {
+                self.kernel = kernel;
+                self.script_error = None;
+                Ok(())
+            }
+            Err(err) => {
+                self.kernel = None;
+                self.script_error = Some(err.clone());
+                Err(err)
+            }
+        }
+    }
+
+    /// Removes every particle.
+    pub fn clear(&mut self) {
+        for i in 0..N {
+            self.dots[i].clear();
+        }
+        self.bonds.clear();
+        self.cache_stale = true;
+    }
+
+    /// Replaces the particles with freshly spawned ones, matching the counts in `params`.
+    pub fn spawn_with(&mut self, rand: &mut impl Rng) {
+        self.border_size = Vec2::new(self.world_w, self.world_h);
+        self.steps = 0;
+        self.cache_stale = true;
+        let bounds = self.bounds();
+
+        for i in 0..N {
+            self.dots[i].clear();
+            for _ in 0..self.spawn_count(i) {
+                self.dots[i].push(Dot::new(spawn_pos(rand, &bounds, Some(i))));
+            }
+        }
+        self.contact.clear();
+        self.bonds.clear();
+        self.rand = SmallRng::seed_from_u64(rand.gen());
+        self.motion = 0.0;
+        self.still_steps = 0;
+
+        // spread out the ages, or a whole type would die at once
+        for i in 0..N {
+            if let Some(lifetime) = self.lifetime_steps(i) {
+                for dot in &mut self.dots[i] {
+                    dot.age = self.rand.gen_range(0..lifetime);
+                }
+            }
+        }
+    }
+
+    /// How many particles of a type are spawned, after applying the `count_scale`.
+    pub fn spawn_count(&self, class: usize) -> usize {
+        (self.params[class].count as f32 * self.count_scale.clamp(0.0, 1.0)) as usize
+    }
+
+    /// Matches the particles to the counts in `params` without disturbing the rest, dropping
+    /// extras and spawning fresh ones to make up a shortfall.
+    pub fn fit_counts(&mut self, rand: &mut impl Rng) {
+        self.cache_stale = true;
+        let bounds = self.bounds();
+
+        for i in 0..N {
+            let count = self.spawn_count(i);
+            self.dots[i].truncate(count);
+            while self.dots[i].len() < count {
+                self.dots[i].push(Dot::new(spawn_pos(rand, &bounds, Some(i))));
+            }
+        }
+        self.drop_loose_bonds();
+    }
+
+    /// Drops bonds to particles that are gone, since `dots` can be changed directly.
+    fn drop_loose_bonds(&mut self) {
+        let dots = &self.dots;
+        let exists = |(class, index): (usize, usize)| class < N && index < dots[class].len();
+        self.bonds.retain(|bond| exists(bond.a) && exists(bond.b));
+    }
+
+    /// A random seed for [`Simulation::spawn_with`] derived from the settings, so a seed
+    /// code always spawns, and then steps, the same way on every machine.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::{BorderBehavior, Simulation};
+    ///
+    /// let run = || {
+    ///     let mut sim = Simulation::new(300.0, 300.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    ///     sim.border = BorderBehavior::Absorb;
+    ///     sim.params[0].count = 100;
+    ///     sim.params[1].count = 100;
+    ///     sim.params[0].power = [-30.0, 40.0];
+    ///     sim.params[0].radius = [60.0, 90.0];
+    ///     sim.params[1].power = [15.0, -60.0];
+    ///     sim.params[1].radius = [120.0, 50.0];
+    ///     sim.spawn_with(&mut SmallRng::seed_from_u64(sim.spawn_seed()));
+    ///     for _ in 0..500 {
+    ///         sim.step();
+    ///     }
+    ///     assert_eq!(sim.steps(), 500);
+    ///     sim.dots
+    /// };
+    /// let (a, b) = (run(), run());
+    /// let bits = |dots: &[Vec<smarticles::Dot>; 2]| -> Vec<(u32, u32)> {
+    ///     let dots = dots.iter().flatten();
+    ///     dots.map(|d| (d.pos.x.to_bits(), d.pos.y.to_bits())).collect()
+    /// };
+    /// assert_eq!(bits(&a), bits(&b));
+    /// ```
+    pub fn spawn_seed(&self) -> u64 {
+        // FNV-1a, which unlike `DefaultHasher` is guaranteed to stay the same across Rust versions
+        self.export()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    /// How many particle pairs a step compares with `total` particles, which is what the
+    /// step time grows with.
+    pub fn interactions_per_step(&self, total: usize) -> u64 {
+        let total = total as u64;
+        let evals = match self.integrator {
+            Integrator::Midpoint => 2,
+            Integrator::SemiImplicitEuler | Integrator::Verlet => 1,
+        };
+        total * total * evals * self.physics.substeps as u64
+    }
+
+    /// How many steps have been taken since the particles were spawned.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// How much simulated time has passed since the particles were spawned, in seconds. Every
+    /// step is a `1 / STEPS_PER_SEC` of a second however long it took to work out, so this
+    /// only moves when the simulation steps, and the same seed always gets to the same time.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use smarticles::Simulation;
+    ///
+    /// let mut sim = Simulation::new(300.0, 300.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(0));
+    /// for _ in 0..90 {
+    ///     sim.step();
+    /// }
+    /// assert_eq!(sim.time(), 1.5);
+    ///
+    /// sim.spawn_with(&mut SmallRng::seed_from_u64(0));
+    /// assert_eq!(sim.time(), 0.0);
+    /// ```
+    pub fn time(&self) -> f32 {
+        self.steps as f32 / STEPS_PER_SEC
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    /// Links two particles with a spring as long as they are apart now. Returns false if
+    /// either doesn't exist, they're already bonded, or there are `MAX_BONDS` already.
+    pub fn bond(&mut self, a: (usize, usize), b: (usize, usize), stiffness: f32) -> bool {
+        let (Some(p1), Some(p2)) = (self.dot(a), self.dot(b)) else {
+            return false;
+        };
+        let rest = (p1.pos - p2.pos).length();
+        let bonded = (self.bonds.iter()).any(|bond| bond.joins(a) && bond.joins(b));
+        if a == b || bonded || self.bonds.len() >= MAX_BONDS {
+            return false;
+        }
+        self.bonds.push(Bond {
+            a,
+            b,
+            rest,
+            stiffness: stiffness.clamp(0.0, MAX_STIFFNESS),
+        });
+        true
+    }
+
+    /// Bonds every pair of `dots` closer than `radius`, returning how many new bonds there are.
+    pub fn bond_within(&mut self, dots: &[(usize, usize)], radius: f32, stiffness: f32) -> usize {
+        let mut count = 0;
+        for (k, &a) in dots.iter().enumerate() {
+            for &b in &dots[k + 1..] {
+                let close = match (self.dot(a), self.dot(b)) {
+                    (Some(p1), Some(p2)) => (p1.pos - p2.pos).length() < radius,
+                    _ => false,
+                };
+                if close && self.bond(a, b, stiffness) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Removes the bond at `index` in [`Simulation::bonds`] and returns it, or returns `None`
+    /// if there's no such bond.
+    ///
+    /// ```
+    /// use egui::{Rgba, Vec2};
+    /// use smarticles::{Dot, Simulation};
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED)]);
+    /// sim.dots[0].push(Dot { pos: Vec2::new(400.0, 400.0), ..Default::default() });
+    /// sim.dots[0].push(Dot { pos: Vec2::new(420.0, 400.0), ..Default::default() });
+    /// sim.bond((0, 0), (0, 1), 0.2);
+    /// assert!(sim.unbond(5).is_none());
+    /// assert_eq!(sim.unbond(0).map(|bond| bond.b), Some((0, 1)));
+    /// assert!(sim.bonds().is_empty());
+    /// ```
+    pub fn unbond(&mut self, index: usize) -> Option<Bond> {
+        (index < self.bonds.len()).then(|| self.bonds.remove(index))
+    }
+
+    pub fn clear_bonds(&mut self) {
+        self.bonds.clear();
+    }
+
+    fn dot(&self, (class, index): (usize, usize)) -> Option<&Dot> {
+        self.dots.get(class).and_then(|dots| dots.get(index))
+    }
+
+    /// How far the particles moved on average in the last step.
+    pub fn motion(&self) -> f32 {
+        self.motion
+    }
+
+    /// Whether the particles have hardly moved for `settle_steps` steps, see `settle_speed`.
+    pub fn settled(&self) -> bool {
+        self.settle_speed > 0.0 && self.still_steps >= self.settle_steps
+    }
+
+    /// The spawn radius that covers the whole world.
+    pub fn max_spawn_radius(&self) -> f32 {
+        Vec2::new(self.world_w, self.world_h).length() / 2.0
+    }
+
+    /// The size the border is at right now. After the world is resized, the border eases
+    /// towards the new size as the simulation steps, so particles aren't flung around.
+    pub fn border_size(&self) -> Vec2 {
+        self.border_size
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            world_w: self.border_size.x,
+            world_h: self.border_size.y,
+            spawn_radius: self.spawn_radius,
+            spawn_rings: self.spawn_rings.then_some(N),
+            behavior: self.border,
+            restitution: self.restitution,
+        }
+    }
+
+    /// Advances the particles by one step.
+    pub fn step(&mut self) {
+        let size = Vec2::new(self.world_w, self.world_h) * self.modulation.world;
+        let remaining = size - self.border_size;
+        self.border_size = if remaining.length() < 0.5 {
+            size
+        } else {
+            self.border_size + remaining * BORDER_EASE
+        };
+
+        let due = self.due_classes();
+        if !self.bonds.is_empty() {
+            self.drop_loose_bonds();
+        }
+        let start = (self.physics.remove_drift)
+            .then(|| self.centroid())
+            .flatten();
+        for _ in 0..self.physics.substeps {
+            self.simulate(due);
+        }
+        if let Some(start) = start {
+            self.remove_drift(start);
+        }
+        if !self.bonds.is_empty() {
+            self.snap_bonds();
+        }
+        self.age();
+        if !self.reactions.is_empty() {
+            self.react();
+        }
+        self.update_motion();
+        self.steps += 1;
+    }
+
+    fn snap_bonds(&mut self) {
+        let dots = &self.dots;
+        self.bonds.retain(|bond| {
+            let (p1, p2) = (&dots[bond.a.0][bond.a.1], &dots[bond.b.0][bond.b.1]);
+            (p1.pos - p2.pos).length() <= bond.rest + BOND_SNAP
+        });
+    }
+
+    // velocities are in distance per step, so they stand in for how far each particle moved,
+    // without wrapping around the border making it look like a big jump
+    fn update_motion(&mut self) {
+        let count = self.dots.iter().map(Vec::len).sum::<usize>();
+        let total = self
+            .dots
+            .iter()
+            .flatten()
+            .map(|dot| dot.vel.length())
+            .sum::<f32>();
+        self.motion = match count {
+            0 => 0.0,
+            _ => total / count as f32,
+        };
+        if self.motion < self.settle_speed {
+            self.still_steps = self.still_steps.saturating_add(1);
+        } else {
+            self.still_steps = 0;
+        }
+    }
+
+    fn centroid(&self) -> Option<Vec2> {
+        let count = self.dots.iter().map(Vec::len).sum::<usize>();
+        let sum = self
+            .dots
+            .iter()
+            .flatten()
+            .fold(Vec2::ZERO, |sum, dot| sum + dot.pos);
+        (count > 0).then(|| sum / count as f32)
+    }
+
+    /// Takes away the average velocity and moves the centroid back to where it started.
+    fn remove_drift(&mut self, start: Vec2) {
+        let Some(end) = self.centroid() else {
+            return;
+        };
+        let count = self.dots.iter().map(Vec::len).sum::<usize>() as f32;
+        let drift = self
+            .dots
+            .iter()
+            .flatten()
+            .fold(Vec2::ZERO, |sum, dot| sum + dot.vel)
+            / count;
+        let mut shift = start - end;
+        if self.physics.recenter {
+            let center = Vec2::new(self.world_w, self.world_h) / 2.0;
+            shift += (center - start) * RECENTER_RATE;
+        }
+        self.dots.par_iter_mut().flatten().for_each(|dot| {
+            dot.vel -= drift;
+            dot.pos += shift;
+        });
+    }
+
+    /// How many steps particles of a type live for, if they don't live forever.
+    fn lifetime_steps(&self, class: usize) -> Option<u32> {
+        let lifetime = self.params[class].lifetime;
+        (lifetime > 0.0).then(|| ((lifetime * STEPS_PER_SEC).round() as u32).max(1))
+    }
+
+    /// The share of its lifetime a particle has left, or `None` if its type lives forever.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use smarticles::{Death, Simulation};
+    ///
+    /// let mut sim = Simulation::new(100.0, 100.0, [("a", Rgba::RED), ("b", Rgba::BLUE)]);
+    /// sim.params[0].count = 20;
+    /// sim.params[1].count = 20;
+    /// sim.params[0].lifetime = 1.0;
+    /// sim.params[0].death = Death::Vanish;
+    /// sim.spawn_with(&mut rand::thread_rng());
+    /// assert!(sim.life_left(0, &sim.dots[0][0]).is_some());
+    /// assert!(sim.life_left(1, &sim.dots[1][0]).is_none());
+    ///
+    /// // a second at 60 steps per second is enough for all of them to run out
+    /// for _ in 0..60 {
+    ///     sim.step();
+    /// }
+    /// assert_eq!((sim.dots[0].len(), sim.dots[1].len()), (0, 20));
+    /// ```
+    pub fn life_left(&self, class: usize, dot: &Dot) -> Option<f32> {
+        let lifetime = self.lifetime_steps(class)?;
+        Some(1.0 - (dot.age as f32 / lifetime as f32).min(1.0))
+    }
+
+    fn age(&mut self) {
+        let bounds = self.bounds();
+        for i in 0..N {
+            for dot in &mut self.dots[i] {
+                dot.age = dot.age.saturating_add(1);
+            }
+            let Some(lifetime) = self.lifetime_steps(i) else {
+                continue;
+            };
+            match self.params[i].death {
+                Death::Respawn => {
+                    for k in 0..self.dots[i].len() {
+                        if self.dots[i][k].age >= lifetime {
+                            let pos = spawn_pos(&mut self.rand, &bounds, Some(i));
+                            self.dots[i][k] = Dot::new(pos);
+                            self.bonds.retain(|bond| !bond.joins((i, k)));
+                            self.cache_stale = true;
+                        }
+                    }
+                }
+                Death::Vanish => {
+                    for k in (0..self.dots[i].len()).rev() {
+                        if self.dots[i][k].age >= lifetime {
+                            self.remove_dot(i, k, None);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes a particle, keeping the reaction counters and bonds lined up with the ones
+    /// left. Its bonds go with it to `moved_to` if it's about to be put there, and are
+    /// dropped otherwise.
+    fn remove_dot(&mut self, class: usize, index: usize, moved_to: Option<(usize, usize)>) -> Dot {
+        for (rule, contact) in self.reactions.iter().zip(&mut self.contact) {
+            if rule.a == class && index < contact.len() {
+                contact.swap_remove(index);
+            }
+        }
+        if !self.bonds.is_empty() {
+            let removed = (class, index);
+            let last = (class, self.dots[class].len() - 1);
+            if moved_to.is_none() {
+                self.bonds.retain(|bond| !bond.joins(removed));
+            }
+            for bond in &mut self.bonds {
+                for end in bond.ends() {
+                    if *end == removed {
+                        *end = moved_to.unwrap_or(removed);
+                    } else if *end == last {
+                        *end = removed;
+                    }
+                }
+            }
+        }
+        self.cache_stale = true;
+        self.dots[class].swap_remove(index)
+    }
+
+    fn react(&mut self) {
+        // keep a counter for every particle, so converting one can shift the rest along
+        self.contact.resize(self.reactions.len(), Vec::new());
+        for (rule, contact) in self.reactions.iter().zip(&mut self.contact) {
+            contact.resize(self.dots.get(rule.a).map_or(0, Vec::len), 0);
+        }
+
+        for r in 0..self.reactions.len() {
+            let rule = self.reactions[r];
+            // rules can be edited as it runs, so skip any that don't make sense
+            if !rule.is_valid(N) {
+                continue;
+            }
+            let dots = &self.dots;
+            let radius_sq = rule.radius * rule.radius;
+            (self.contact[r].par_iter_mut())
+                .zip(&dots[rule.a])
+                .for_each(|(steps, p1)| {
+                    let touching = dots[rule.b].iter().any(|p2| {
+                        let dist_sq = (p1.pos - p2.pos).length_sq();
+                        dist_sq > 0.0 && dist_sq < radius_sq
+                    });
+                    *steps = if touching { *steps + 1 } else { 0 };
+                });
+            if rule.a == rule.result {
+                continue;
+            }
+
+            // going from the back means the particle swapped into a removed one's place
+            // has already had its turn
+            let ready: Vec<usize> = (0..self.contact[r].len())
+                .rev()
+                .filter(|&k| self.contact[r][k] > rule.steps)
+                .collect();
+            for k in ready {
+                if self.dots[rule.result].len() >= MAX_COUNT {
+                    break;
+                }
+                if self.rand.gen::<f32>() >= rule.chance {
+                    continue;
+                }
+                let moved_to = (rule.result, self.dots[rule.result].len());
+                let dot = self.remove_dot(rule.a, k, Some(moved_to));
+                self.dots[rule.result].push(dot);
+                for (other, contact) in self.reactions.iter().zip(&mut self.contact) {
+                    if other.a == rule.result {
+                        contact.push(0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Which types' forces are worked out this step, or `None` if they all are and nothing
+    /// needs caching.
+    fn due_classes(&mut self) -> Option<[bool; N]> {
+        if self.strides.iter().all(|&stride| stride <= 1) {
+            self.cache_stale = true;
+            return None;
+        }
+        // particles that were added or moved since the cache was filled need every force
+        let stale = self.cache_stale
+            || (self.stride_cache.iter().zip(&self.dots)).any(|(c, d)| c.len() != d.len());
+        self.cache_stale = false;
+        Some(std::array::from_fn(|j| {
+            stale || self.steps.is_multiple_of(self.strides[j].max(1) as u64)
+        }))
+    }
+
+    fn simulate(&mut self, due: Option<[bool; N]>) {
+        let bounds = self.bounds();
+        let physics = self.physics.modulated(&self.modulation);
+        let dt = physics.dt();
+        let keep = physics.keep();
+        let kernel = self.kernel.as_ref();
+        // gravity is too gentle for the particles moving within a step to matter
+        let external = External {
+            attractors: &self.attractors,
+            flow: self.flow,
+            world: Vec2::new(self.world_w, self.world_h),
+            gravity: Gravity::new(&self.dots, self.border_size, physics.gravity),
+        };
+
+        // every force is gathered from the positions at the start of the step
+        // before anything moves, so the order the classes are visited in is irrelevant
+        match self.integrator {
+            Integrator::SemiImplicitEuler => {
+                compute_forces(
+                    &self.params,
+                    &physics,
+                    kernel,
+                    &external,
+                    &self.dots,
+                    &mut self.forces,
+                    due.map(|due| (&mut self.stride_cache, due)),
+                );
+                add_bond_forces(&self.bonds, &self.dots, &mut self.forces);
+                for_each_dot(&mut self.dots, &self.forces, |class, p, &f| {
+                    p.vel = (p.vel + f * dt) * keep;
+                    p.pos += p.vel * dt;
+                    p.acc = f;
+                    apply_border(p, class, &bounds, &physics);
+                });
+            }
+            Integrator::Verlet => {
+                // velocity verlet: move with the previous step's forces, then
+                // average them with the forces at the new positions
+                self.dots.par_iter_mut().flatten().for_each(|p| {
+                    p.pos += (p.vel + p.acc * 0.5 * dt) * dt;
+                });
+                compute_forces(
+                    &self.params,
+                    &physics,
+                    kernel,
+                    &external,
+                    &self.dots,
+                    &mut self.forces,
+                    due.map(|due| (&mut self.stride_cache, due)),
+                );
+                add_bond_forces(&self.bonds, &self.dots, &mut self.forces);
+                for_each_dot(&mut self.dots, &self.forces, |class, p, &f| {
+                    p.vel = (p.vel + (p.acc + f) * 0.5 * dt) * keep;
+                    p.acc = f;
+                    apply_border(p, class, &bounds, &physics);
+                });
+            }
+            Integrator::Midpoint => {
+                // evaluate the forces again half a step ahead and use those
+                compute_forces(
+                    &self.params,
+                    &physics,
+                    kernel,
+                    &external,
+                    &self.dots,
+                    &mut self.forces,
+                    due.map(|due| (&mut self.stride_cache, due)),
+                );
+                add_bond_forces(&self.bonds, &self.dots, &mut self.forces);
+                for (mid, dots) in self.mid.iter_mut().zip(&self.dots) {
+                    mid.clone_from(dots);
+                }
+                for_each_dot(&mut self.mid, &self.forces, |_, m, &f| {
+                    m.pos += m.vel * 0.5 * dt;
+                    m.vel += f * 0.5 * dt;
+                });
+                compute_forces(
+                    &self.params,
+                    &physics,
+                    kernel,
+                    &external,
+                    &self.mid,
+                    &mut self.forces,
+                    due.map(|due| (&mut self.stride_cache, due)),
+                );
+                add_bond_forces(&self.bonds, &self.mid, &mut self.forces);
+                for_each_dot(&mut self.mid, &self.forces, |_, m, &f| m.acc = f);
+                for_each_dot(&mut self.dots, &self.mid, |class, p, m| {
+                    p.pos += m.vel * dt;
+                    p.vel = (p.vel + m.acc * dt) * keep;
+                    p.acc = m.acc;
+                    apply_border(p, class, &bounds, &physics);
+                });
+            }
+        }
+    }
+
+    /// Encodes the settings, but not the particles, as a shareable `@` seed code.
+    ///
+    /// ```
+    /// use egui::Rgba;
+    /// use smarticles::Simulation;
+    ///
+    /// let mut sim = Simulation::new(800.0, 800.0, [("a", Rgba::RED)]);
+    /// sim.params[0].power[0] = 12.5;
+    /// sim.params[0].enabled[0] = false;
+    /// let code = sim.export();
+    ///
+    /// let mut copy = Simulation::new(800.0, 800.0, [("b", Rgba::BLUE)]);
+    /// let bytes = base64::decode(code.strip_prefix('@').unwrap()).unwrap();
+    /// copy.import(&bytes).unwrap();
+    /// assert_eq!(copy.params[0].power[0], 12.5);
+    /// assert_eq!(copy.params[0].name, "a");
+    /// assert!(!copy.params[0].enabled[0]);
+    /// ```
+    pub fn export(&self) -> String {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.write_u16::<LE>(SEED_MAGIC).unwrap();
+        bytes.write_u8(SEED_VERSION).unwrap();
+        bytes.write_f32::<LE>(self.world_w).unwrap();
+        bytes.write_f32::<LE>(self.world_h).unwrap();
+        bytes.write_f32::<LE>(self.spawn_radius).unwrap();
+        bytes.write_u8(self.border as u8).unwrap();
+        bytes.write_f32::<LE>(self.restitution).unwrap();
+        bytes.write_f32::<LE>(self.physics.force_scale).unwrap();
+        bytes.write_f32::<LE>(self.physics.friction).unwrap();
+        bytes.write_f32::<LE>(self.physics.border_margin).unwrap();
+        bytes.write_u8(self.integrator as u8).unwrap();
+        bytes.write_u8(self.physics.substeps as u8).unwrap();
+        for p in &self.params {
+            bytes.write_u8((p.color.r() * 255.0) as u8).unwrap();
+            bytes.write_u8((p.color.g() * 255.0) as u8).unwrap();
+            bytes.write_u8((p.color.b() * 255.0) as u8).unwrap();
+            bytes.write_u16::<LE>(p.count as u16).unwrap();
+            for &v in p.power.iter().chain(&p.radius).chain(&p.spin) {
+                bytes.write_f32::<LE>(v).unwrap();
+            }
+        }
+        for p in &self.params {
+            let name = &p.name.as_bytes()[..p.name.len().min(u8::MAX as usize)];
+            bytes.write_u8(name.len() as u8).unwrap();
+            bytes.extend_from_slice(name);
+        }
+        // `set_force_script` turns away scripts too long to fit
+        let script = self.force_script.as_bytes();
+        bytes.write_u16::<LE>(script.len() as u16).unwrap();
+        bytes.extend_from_slice(script);
+        // one bit per pair, set when it's disabled
+        let mut disabled = vec![0; (N * N).div_ceil(8)];
+        for (i, p) in self.params.iter().enumerate() {
+            for j in (0..N).filter(|&j| !p.enabled[j]) {
+                disabled[(i * N + j) / 8] |= 1 << ((i * N + j) % 8);
+            }
+        }
+        bytes.extend_from_slice(&disabled);
+        let reactions = &self.reactions[..self.reactions.len().min(MAX_REACTIONS)];
+        bytes.write_u8(reactions.len() as u8).unwrap();
+        for r in reactions {
+            bytes.write_u8(r.a as u8).unwrap();
+            bytes.write_u8(r.b as u8).unwrap();
+            bytes.write_u8(r.result as u8).unwrap();
+            bytes.write_f32::<LE>(r.radius).unwrap();
+            bytes.write_u16::<LE>(r.steps as u16).unwrap();
+            bytes.write_f32::<LE>(r.chance).unwrap();
+        }
+        for p in &self.params {
+            bytes.write_f32::<LE>(p.lifetime).unwrap();
+            bytes.write_u8(p.death as u8).unwrap();
+        }
+        let attractors = &self.attractors[..self.attractors.len().min(MAX_ATTRACTORS)];
+        bytes.write_u8(attractors.len() as u8).unwrap();
+        for a in attractors {
+            for v in [a.pos.x, a.pos.y, a.strength, a.radius] {
+                bytes.write_f32::<LE>(v).unwrap();
+            }
+        }
+        bytes.write_u8(self.flow.kind as u8).unwrap();
+        bytes.write_f32::<LE>(self.flow.angle).unwrap();
+        bytes.write_f32::<LE>(self.flow.strength).unwrap();
+        let drift = self.physics.remove_drift as u8 | (self.physics.recenter as u8) << 1;
+        bytes.write_u8(drift).unwrap();
+        bytes.write_f32::<LE>(self.physics.gravity).unwrap();
+        bytes.write_u8(self.spawn_rings as u8).unwrap();
+        format!("@{}", base64::encode(bytes))
+    }
+
+    /// Decodes the base64-decoded bytes of a seed code, leaving everything untouched if any of
+    /// it is invalid.
+    pub fn import(&mut self, mut bytes: &[u8]) -> Result<(), SeedError> {
+        fn truncated(_: std::io::Error) -> SeedError {
+            SeedError::Truncated
+        }
+        fn check(valid: bool, what: &'static str) -> Result<(), SeedError> {
+            valid.then_some(()).ok_or(SeedError::OutOfRange(what))
+        }
+
+        // seeds from before the format was versioned start directly with the world size
+        let version = if bytes.len() >= 3 && bytes[..2] == SEED_MAGIC.to_le_bytes() {
+            let version = bytes[2];
+            bytes = &bytes[3..];
+            version
+        } else {
+            1
+        };
+        if version > SEED_VERSION {
+            return Err(SeedError::NewerVersion(version));
+        }
+
+        // everything up to version 9 was quantized to whole numbers
+        let precise = version >= 9;
+        let read_size = |bytes: &mut &[u8]| {
+            if precise {
+                bytes.read_f32::<LE>()
+            } else {
+                bytes.read_u16::<LE>().map(f32::from)
+            }
+        };
+        let world_w = read_size(&mut bytes).map_err(truncated)?;
+        let world_h = read_size(&mut bytes).map_err(truncated)?;
+        let max_spawn_radius = Vec2::new(world_w, world_h).length() / 2.0;
+        let spawn_radius = if version >= 5 {
+            read_size(&mut bytes).map_err(truncated)?
+        } else {
+            max_spawn_radius
+        };
+        let (border, restitution) = if version >= 3 {
+            let border = BorderBehavior::from_u8(bytes.read_u8().map_err(truncated)?);
+            let restitution = if precise {
+                bytes.read_f32::<LE>().map_err(truncated)?
+            } else {
+                bytes.read_u8().map_err(truncated)? as f32 / 255.0
+            };
+            (border, restitution)
+        } else {
+            (BorderBehavior::Soft, 1.0)
+        };
+        let mut physics = Physics::default();
+        if version >= 4 {
+            physics.force_scale = bytes.read_f32::<LE>().map_err(truncated)?;
+            physics.friction = bytes.read_f32::<LE>().map_err(truncated)?;
+            physics.border_margin = bytes.read_f32::<LE>().map_err(truncated)?;
+        }
+        let integrator = if version >= 6 {
+            Integrator::from_u8(bytes.read_u8().map_err(truncated)?)
+        } else {
+            Integrator::SemiImplicitEuler
+        };
+        if version >= 7 {
+            physics.substeps = bytes.read_u8().map_err(truncated)? as u32;
+        }
+
+        let mut params = self.params.clone();
+        for p in &mut params {
+            let r = bytes.read_u8().map_err(truncated)? as f32 / 255.0;
+            let g = bytes.read_u8().map_err(truncated)? as f32 / 255.0;
+            let b = bytes.read_u8().map_err(truncated)? as f32 / 255.0;
+            p.color = Rgba::from_rgb(r, g, b);
+            p.count = bytes.read_u16::<LE>().map_err(truncated)? as usize;
+            if precise {
+                for v in p.power.iter_mut().chain(&mut p.radius).chain(&mut p.spin) {
+                    *v = bytes.read_f32::<LE>().map_err(truncated)?;
+                }
+                continue;
+            }
+            for p in &mut p.power {
+                *p = bytes.read_i8().map_err(truncated)? as f32;
+            }
+            for r in &mut p.radius {
+                *r = bytes.read_u16::<LE>().map_err(truncated)? as f32;
+            }
+            for s in &mut p.spin {
+                *s = if version >= 2 {
+                    bytes.read_i8().map_err(truncated)? as f32
+                } else {
+                    0.0
+                };
+            }
+        }
+        if version >= 8 {
+            for p in &mut params {
+                let mut name = vec![0; bytes.read_u8().map_err(truncated)? as usize];
+                bytes.read_exact(&mut name).map_err(truncated)?;
+                if !name.is_empty() {
+                    p.name = String::from_utf8_lossy(&name).into_owned();
+                }
+            }
+        }
+        let script = if version >= 10 {
+            let mut script = vec![0; bytes.read_u16::<LE>().map_err(truncated)? as usize];
+            bytes.read_exact(&mut script).map_err(truncated)?;
+            String::from_utf8_lossy(&script).into_owned()
+        } else {
+            String::new()
+        };
+        if version >= 11 {
+            let mut disabled = vec![0; (N * N).div_ceil(8)];
+            bytes.read_exact(&mut disabled).map_err(truncated)?;
+            for (i, p) in params.iter_mut().enumerate() {
+                for j in 0..N {
+                    p.enabled[j] = disabled[(i * N + j) / 8] & (1 << ((i * N + j) % 8)) == 0;
+                }
+            }
+        } else {
+            for p in &mut params {
+                p.enabled = [true; N];
+            }
+        }
+        let mut reactions = Vec::new();
+        if version >= 12 {
+            for _ in 0..bytes.read_u8().map_err(truncated)? {
+                reactions.push(Reaction {
+                    a: bytes.read_u8().map_err(truncated)? as usize,
+                    b: bytes.read_u8().map_err(truncated)? as usize,
+                    result: bytes.read_u8().map_err(truncated)? as usize,
+                    radius: bytes.read_f32::<LE>().map_err(truncated)?,
+                    steps: bytes.read_u16::<LE>().map_err(truncated)? as u32,
+                    chance: bytes.read_f32::<LE>().map_err(truncated)?,
+                });
+            }
+        }
+        for p in &mut params {
+            if version >= 13 {
+                p.lifetime = bytes.read_f32::<LE>().map_err(truncated)?;
+                p.death = Death::from_u8(bytes.read_u8().map_err(truncated)?);
+            } else {
+                p.lifetime = 0.0;
+                p.death = Death::Respawn;
+            }
+        }
+        let mut attractors = Vec::new();
+        if version >= 14 {
+            for _ in 0..bytes.read_u8().map_err(truncated)? {
+                let mut v = [0.0; 4];
+                for v in &mut v {
+                    *v = bytes.read_f32::<LE>().map_err(truncated)?;
+                }
+                attractors.push(Attractor {
+                    pos: Vec2::new(v[0], v[1]),
+                    strength: v[2],
+                    radius: v[3],
+                });
+            }
+        }
+        let flow = if version >= 15 {
+            Flow {
+                kind: FlowKind::from_u8(bytes.read_u8().map_err(truncated)?),
+                angle: bytes.read_f32::<LE>().map_err(truncated)?,
+                strength: bytes.read_f32::<LE>().map_err(truncated)?,
+            }
+        } else {
+            Flow::default()
+        };
+        if version >= 16 {
+            let drift = bytes.read_u8().map_err(truncated)?;
+            physics.remove_drift = drift & 1 != 0;
+            physics.recenter = drift & 2 != 0;
+        }
+        physics.gravity = if version >= 17 {
+            bytes.read_f32::<LE>().map_err(truncated)?
+        } else {
+            0.0
+        };
+        let spawn_rings = version >= 18 && bytes.read_u8().map_err(truncated)? != 0;
+
+        // range checks also turn away NaN and infinities
+        let size = 1.0..=u16::MAX as f32;
+        check(
+            size.contains(&world_w) && size.contains(&world_h),
+            "world size",
+        )?;
+        check(
+            (0.0..=max_spawn_radius + 1.0).contains(&spawn_radius),
+            "spawn radius",
+        )?;
+        check((0.0..=1.0).contains(&restitution), "restitution")?;
+        check(
+            (0.0..=MAX_FORCE_SCALE).contains(&physics.force_scale),
+            "force scale",
+        )?;
+        check((0.0..=1.0).contains(&physics.friction), "friction")?;
+        check(
+            (0.0..=MAX_BORDER_MARGIN).contains(&physics.border_margin),
+            "border margin",
+        )?;
+        check(
+            (1..=MAX_SUBSTEPS).contains(&physics.substeps),
+            "substep count",
+        )?;
+        check((0.0..=MAX_GRAVITY).contains(&physics.gravity), "gravity")?;
+        for p in &params {
+            check(p.count <= MAX_COUNT, "particle count")?;
+            let power = MIN_POWER..=MAX_POWER;
+            check(p.power.iter().all(|v| power.contains(v)), "power")?;
+            let radius = MIN_RADIUS..=MAX_RADIUS;
+            check(p.radius.iter().all(|v| radius.contains(v)), "radius")?;
+            let spin = MIN_SPIN..=MAX_SPIN;
+            check(p.spin.iter().all(|v| spin.contains(v)), "spin")?;
+            check((0.0..=MAX_LIFETIME).contains(&p.lifetime), "lifetime")?;
+        }
+        check(reactions.len() <= MAX_REACTIONS, "reaction count")?;
+        check(reactions.iter().all(|r| r.is_valid(N)), "reaction")?;
+        check(attractors.len() <= MAX_ATTRACTORS, "attractor count")?;
+        check(attractors.iter().all(Attractor::is_valid), "attractor")?;
+        check(flow.is_valid(), "flow")?;
+
+        self.world_w = world_w;
+        self.world_h = world_h;
+        self.spawn_radius = spawn_radius;
+        self.spawn_rings = spawn_rings;
+        self.border = border;
+        self.restitution = restitution;
+        self.physics = physics;
+        self.integrator = integrator;
+        self.params = params;
+        self.reactions = reactions;
+        self.attractors = attractors;
+        self.flow = flow;
+        self.cache_stale = true;
+        // a script that doesn't parse falls back to the builtin kernel and shows up in
+        // `script_error`, rather than turning away the rest of the seed
+        let _ = self.set_force_script(&script);
+        Ok(())
+    }
+}
+
+/// A random position in the spawn circle, or in the ring for `class` if types spawn in rings.
+fn spawn_pos<R: Rng>(rand: &mut R, bounds: &Bounds, class: Option<usize>) -> Vec2 {
+    if let (Some(class), Some(rings)) = (class, bounds.spawn_rings) {
+        return ring_pos(rand, bounds, class, rings);
+    }
+    // only sample the part of the world that overlaps the spawn circle's bounding box
+    let center = Vec2::new(bounds.world_w, bounds.world_h) / 2.0;
+    let min_x = (center.x - bounds.spawn_radius).max(0.0);
+    let max_x = (center.x + bounds.spawn_radius).min(bounds.world_w);
+    let min_y = (center.y - bounds.spawn_radius).max(0.0);
+    let max_y = (center.y + bounds.spawn_radius).min(bounds.world_h);
+    loop {
+        let pos = Vec2::new(
+            min_x + (max_x - min_x) * rand.sample::<f32, _>(OpenClosed01),
+            min_y + (max_y - min_y) * rand.sample::<f32, _>(OpenClosed01),
+        );
+        if (pos - center).length() <= bounds.spawn_radius {
+            return pos;
+        }
+    }
+}
+
+fn ring_pos<R: Rng>(rand: &mut R, bounds: &Bounds, ring: usize, rings: usize) -> Vec2 {
+    let center = Vec2::new(bounds.world_w, bounds.world_h) / 2.0;
+    // past the corners, the outer rings could miss the world entirely
+    let radius = bounds.spawn_radius.min(center.length());
+    let inner = ring as f32 / rings as f32;
+    let outer = (ring + 1) as f32 / rings as f32;
+    loop {
+        // picking the square of the distance spreads them evenly over the ring's area
+        let t = rand.sample::<f32, _>(OpenClosed01);
+        let dist = (inner * inner + (outer * outer - inner * inner) * t).sqrt() * radius;
+        let angle = rand.gen::<f32>() * std::f32::consts::TAU;
+        let pos = center + Vec2::angled(angle) * dist;
+        if (0.0..=bounds.world_w).contains(&pos.x) && (0.0..=bounds.world_h).contains(&pos.y) {
+            return pos;
+        }
+    }
+}
+
+fn pair_force(
+    pos: Vec2,
+    group: &[Dot],
+    g: f32,
+    radius: f32,
+    spin: f32,
+    physics: &Physics,
+    kernel: Option<&Expr>,
+) -> Vec2 {
+    let mut f = Vec2::ZERO;
+    let mut scripted = Vec2::ZERO;
+    for p2 in group {
+        let d = pos - p2.pos;
+        let r = d.length();
+        if r < radius && r > 0.0 {
+            f += d / r;
+            // a script that divides by zero somewhere would otherwise wipe out every particle
+            if let Some(w) = kernel.map(|kernel| kernel.eval(r, radius, g)) {
+                if w.is_finite() {
+                    scripted += d / r * w;
+                }
+            }
+        }
+    }
+
+    // the tangential component is the radial one rotated a quarter turn
+    let t = Vec2::new(-f.y, f.x);
+    let radial = if kernel.is_some() { scripted } else { f * g };
+
+    (radial + t * spin) * physics.force_scale / -100.0
+}
+
+/// The force from each type on every particle of each type.
+type StrideCache<const N: usize> = [Vec<[Vec2; N]>; N];
+
+fn compute_forces<const N: usize>(
+    params: &[Params<N>; N],
+    physics: &Physics,
+    kernel: Option<&Expr>,
+    external: &External,
+    dots: &[Vec<Dot>; N],
+    forces: &mut [Vec<Vec2>; N],
+    cache: Option<(&mut StrideCache<N>, [bool; N])>,
+) {
+    let attract = |f: Vec2, p1: &Dot| external.add(f, p1.pos, physics);
+
+    let force = |p: &Params<N>, p1: &Dot, j: usize| {
+        if !p.enabled[j] {
+            return Vec2::ZERO;
+        }
+        pair_force(
+            p1.pos,
+            &dots[j],
+            p.power[j],
+            p.radius[j],
+            p.spin[j],
+            physics,
+            kernel,
+        )
+    };
+
+    // the buffers only ever grow, so a steady simulation doesn't allocate
+    let Some((cache, due)) = cache else {
+        forces.par_iter_mut().enumerate().for_each(|(i, forces_i)| {
+            let p = &params[i];
+            forces_i.resize(dots[i].len(), Vec2::ZERO);
+            forces_i.par_iter_mut().zip(&dots[i]).for_each(|(f, p1)| {
+                *f = attract((0..N).fold(Vec2::ZERO, |f, j| f + force(p, p1, j)), p1);
+            });
+        });
+        return;
+    };
+
+    // adding up in the same order as above means a cache where every type is due gives the
+    // same forces to the bit
+    (forces.par_iter_mut().zip(cache.par_iter_mut()))
+        .enumerate()
+        .for_each(|(i, (forces_i, cache_i))| {
+            let p = &params[i];
+            forces_i.resize(dots[i].len(), Vec2::ZERO);
+            cache_i.resize(dots[i].len(), [Vec2::ZERO; N]);
+            (forces_i.par_iter_mut().zip(cache_i.par_iter_mut()))
+                .zip(&dots[i])
+                .for_each(|((f, cached), p1)| {
+                    let pairs = (0..N).fold(Vec2::ZERO, |f, j| {
+                        if due[j] {
+                            cached[j] = force(p, p1, j);
+                        }
+                        f + cached[j]
+                    });
+                    *f = attract(pairs, p1);
+                });
+        });
+}
+
+/// Adds the pull of each bond to both of its ends, in opposite directions.
+fn add_bond_forces<const N: usize>(
+    bonds: &[Bond],
+    dots: &[Vec<Dot>; N],
+    forces: &mut [Vec<Vec2>; N],
+) {
+    for bond in bonds {
+        // `dots` may have been changed since the bonds were last checked
+        let (Some(p1), Some(p2)) = (dots[bond.a.0].get(bond.a.1), dots[bond.b.0].get(bond.b.1))
+        else {
+            continue;
+        };
+        let d = p2.pos - p1.pos;
+        let r = d.length();
+        if r > 0.0 {
+            let f = d / r * (r - bond.rest) * bond.stiffness;
+            forces[bond.a.0][bond.a.1] += f;
+            forces[bond.b.0][bond.b.1] -= f;
+        }
+    }
+}
+
+fn for_each_dot<const N: usize, T, F>(dots: &mut [Vec<Dot>; N], values: &[Vec<T>; N], f: F)
+where
+    T: Sync,
+    F: Fn(usize, &mut Dot, &T) + Sync,
+{
+    dots.par_iter_mut()
+        .zip(values)
+        .enumerate()
+        .for_each(|(i, (dots_i, values_i))| {
+            dots_i
+                .par_iter_mut()
+                .zip(values_i)
+                .for_each(|(p, value)| f(i, p, value));
+        });
+}
+
+fn apply_border(p1: &mut Dot, class: usize, bounds: &Bounds, physics: &Physics) {
+    let (world_w, world_h) = (bounds.world_w, bounds.world_h);
+    let margin = physics.border_margin;
+    match bounds.behavior {
+        BorderBehavior::Soft => {
+            if (p1.pos.x < margin && p1.vel.x < 0.0)
+                || (p1.pos.x > world_w - margin && p1.vel.x > 0.0)
+            {
+                p1.vel.x *= -1.0;
+            }
+            if (p1.pos.y < margin && p1.vel.y < 0.0)
+                || (p1.pos.y > world_h - margin && p1.vel.y > 0.0)
+            {
+                p1.vel.y *= -1.0;
+            }
+        }
+        BorderBehavior::Bounce => {
+            // clamp back onto the edge so fast particles can't tunnel out
+            if p1.pos.x < 0.0 {
+                p1.pos.x = 0.0;
+                p1.vel.x = p1.vel.x.abs() * bounds.restitution;
+            } else if p1.pos.x > world_w {
+                p1.pos.x = world_w;
+                p1.vel.x = -p1.vel.x.abs() * bounds.restitution;
+            }
+            if p1.pos.y < 0.0 {
+                p1.pos.y = 0.0;
+                p1.vel.y = p1.vel.y.abs() * bounds.restitution;
+            } else if p1.pos.y > world_h {
+                p1.pos.y = world_h;
+                p1.vel.y = -p1.vel.y.abs() * bounds.restitution;
+            }
+        }
+        BorderBehavior::Absorb => {
+            if p1.pos.x < 0.0 || p1.pos.x > world_w || p1.pos.y < 0.0 || p1.pos.y > world_h {
+                // seeded from where it left, so runs stay reproducible
+                let seed = (p1.pos.x.to_bits() as u64) << 32 | p1.pos.y.to_bits() as u64;
+                let rand = &mut SmallRng::seed_from_u64(seed);
+                p1.pos = spawn_pos(rand, bounds, Some(class));
+                p1.vel = Vec2::ZERO;
+                p1.acc = Vec2::ZERO;
+            }
+        }
+    }
+
+    // alternative: wrap
+    /*if p1.pos.x < 0.0 {
+        p1.pos.x += world_w;
+    } else if p1.pos.x >= world_w {
+        p1.pos.x -= world_w;
+    }
+    if p1.pos.y < 0.0 {
+        p1.pos.y += world_h;
+    } else if p1.pos.y >= world_h {
+        p1.pos.y -= world_h;
+    }*/
+}
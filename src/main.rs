@@ -1,453 +1,5791 @@
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use eframe::epaint::Color32;
-use eframe::{App, Frame, NativeOptions};
-use egui::{CentralPanel, Context, Rgba, Sense, SidePanel, Slider, Vec2};
+mod audio;
+mod control;
+mod recording;
+mod stream;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use audio::{AudioInput, Feature, Levels};
+use control::{Command, ControlServer};
+use eframe::epaint::{Color32, Mesh, Shape, Stroke};
+#[cfg(not(target_arch = "wasm32"))]
+use eframe::NativeOptions;
+use eframe::{App, Frame, Storage};
+use egui::panel::Side;
+use egui::plot::{Bar, BarChart, Legend, Line, Plot, PlotPoint, PlotPoints, VLine};
+use egui::{
+    Align2, Area, Button, CentralPanel, CollapsingHeader, ComboBox, Context, CursorIcon,
+    DroppedFile, Event, FontDefinitions, FontId, Grid, Key, Painter, Pos2, ProgressBar, Rect,
+    Response, Rgba, RichText, Sense, SidePanel, Slider, TextEdit, Ui, Vec2, Window,
+};
+use image::{Rgba as Pixel, RgbaImage};
+use instant::{Duration, Instant, SystemTime};
 use rand::distributions::OpenClosed01;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
-use rayon::prelude::*;
+use recording::{FrameWriter, GifWriter, Player, Recorder, ReplayBuffer};
+use smarticles::config::Config;
+use smarticles::expr;
+use smarticles::matrix;
+use smarticles::preset::{self, Preset};
+use smarticles::{
+    Attractor, BorderBehavior, Death, Dot, Flow, FlowKind, Integrator, Modulation, Params, Physics,
+    Reaction, SeedError, Simulation, INIT_SIZE, MAX_ATTRACTORS, MAX_ATTRACTOR_STRENGTH,
+    MAX_BORDER_MARGIN, MAX_COUNT, MAX_FLOW_STRENGTH, MAX_FORCE_SCALE, MAX_GRAVITY, MAX_LIFETIME,
+    MAX_POWER, MAX_RADIUS, MAX_REACTIONS, MAX_REACTION_RADIUS, MAX_REACTION_STEPS,
+    MAX_SETTLE_SPEED, MAX_SETTLE_STEPS, MAX_SPIN, MAX_STIFFNESS, MAX_SUBSTEPS, MIN_COUNT,
+    MIN_POWER, MIN_RADIUS, MIN_SPAWN_RADIUS, MIN_SPIN, STEPS_PER_SEC,
+};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use stream::Streamer;
+
+const MIN_MUTATION: f32 = 1.0;
+const MAX_MUTATION: f32 = 50.0;
+const MAX_UNDO: usize = 100;
+/// Seeds from `Randomize` end in a number below this, on top of the two words.
+const SEED_NUMBERS: u32 = 100;
+/// The furthest a setting can be pushed by a sound at full level, either way.
+const MAX_DEPTH: f32 = 2.0;
+const LOCK_TINT: Color32 = Color32::from_rgb(150, 110, 20);
 
-const INIT_SIZE: f32 = 800.0;
-const MIN_COUNT: usize = 0;
-const MAX_COUNT: usize = 1000;
-const MIN_POWER: f32 = -100.0;
-const MAX_POWER: f32 = 100.0;
-const MIN_RADIUS: f32 = 0.0;
-const MAX_RADIUS: f32 = 500.0;
+const STATS_HISTORY: usize = 600;
+const CLUSTER_INTERVAL: u32 = 30;
+/// How many steps each auto-tuning try runs for, the first half of which is spent settling in.
+const TUNE_STEPS: u32 = 300;
+const MIN_TUNE_STEPS: u32 = 60;
+const MAX_TUNE_STEPS: u32 = 1800;
+/// How often an auto-tuning try is measured, once it has settled in.
+const TUNE_SAMPLE_INTERVAL: u32 = 10;
+const MAX_TUNE_SPEED: f32 = 5.0;
+const ACTIVITY_HISTORY: usize = 300;
+const ACTIVITY_INTERVAL: u32 = 6;
+const HISTOGRAM_BINS: usize = 64;
+const HISTOGRAM_INTERVAL: Duration = Duration::from_secs(1);
+/// Bigger pairs of types are sampled, so the histogram never holds up a frame for long.
+const MAX_HISTOGRAM_PAIRS: usize = 250_000;
+/// How many flow arrows are drawn across the longer side of the world.
+const FLOW_ARROWS: usize = 12;
+const CLUSTER_DISTANCE: f32 = 20.0;
+const MIN_CLUSTER_DISTANCE: f32 = 2.0;
+const MAX_CLUSTER_DISTANCE: f32 = 100.0;
+/// Smaller groups are strays rather than clusters.
+const MIN_CLUSTER_SIZE: usize = 5;
+const DENSITY_CELL_SIZE: f32 = 20.0;
+/// How many cells the density field has along the longer side of the world.
+const FIELD_CELLS: usize = 128;
+const MAX_TRAIL_LEN: usize = 60;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 20.0;
+const ZOOM_SPEED: f32 = 0.002;
+const ZOOM_STEP: f32 = 1.25;
+const BOOKMARK_SLOTS: usize = 5;
+const BOOKMARK_KEYS: [Key; BOOKMARK_SLOTS] =
+    [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5];
+/// How long the view takes to glide somewhere new, in seconds.
+const VIEW_GLIDE_TIME: f32 = 0.3;
+/// How much of the panel the world takes up when it's fit to the view.
+const FIT_MARGIN: f32 = 0.95;
+const MINIMAP_SIZE: f32 = 160.0;
+const MINIMAP_STRIDE: usize = 16;
+const GRID_STEP: f32 = 100.0;
+const GRID_SUBSTEP: f32 = 25.0;
+const SCALE_BAR_LEN: f32 = 120.0;
+const MAX_HISTORY_LEN: usize = 50;
+const IMAGE_SIZES: [u32; 3] = [1920, 2560, 3840];
+/// Widths for the replay buffer's frames, which are kept small since there are a lot of them.
+const REPLAY_SIZES: [u32; 3] = [320, 480, 640];
+/// The replay buffer keeps one frame every few steps, which divides evenly into the
+/// hundredths of a second GIF frame delays are counted in.
+const REPLAY_FPS: u32 = 20;
+const MAX_REPLAY_SECS: u32 = 30;
+const MAX_NAME_LEN: usize = 32;
+const MAX_STRIDE: u32 = 8;
+const PICK_RADIUS: f32 = 8.0;
+/// How close to the edge of the force curve a drag grabs the radius rather than the power.
+const CURVE_GRAB: f32 = 6.0;
+const TOAST_TIME: f32 = 4.0;
+/// How long the mark where the selected particle was before a respawn takes to fade.
+const RESPAWN_MARK_TIME: f32 = 1.5;
+/// Particles fade out over this last share of their lifetime.
+const AGE_FADE: f32 = 0.25;
+/// Step and frame rates are counted over this long.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+/// Steps slower than the budget by this factor make auto-limit shrink the counts.
+const BUDGET_SLACK: f32 = 1.25;
+const MIN_STEP_INTERVAL: f32 = 10.0;
+const MAX_STEP_INTERVAL: f32 = 100.0;
+const MIN_STEP_BUDGET: f32 = 10.0;
+const MAX_STEP_BUDGET: f32 = 200.0;
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+const MIN_DOT_SIZE: f32 = 0.5;
+const MAX_DOT_SIZE: f32 = 6.0;
+/// The largest particles get when they're scaled with the zoom.
+const MAX_DOT_RADIUS: f32 = 24.0;
+/// The stats overlay's text size is given for a view this tall, and scales with the real one.
+const HUD_REFERENCE_HEIGHT: f32 = 720.0;
+const MIN_HUD_SIZE: f32 = 8.0;
+const MAX_HUD_SIZE: f32 = 48.0;
+/// Smaller text than this is hard to read, however small the view.
+const MIN_HUD_TEXT: f32 = 9.0;
+/// Custom seed codes are cut down to this many characters in the overlay.
+const HUD_SEED_LEN: usize = 24;
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    let mut options = NativeOptions::default();
-    options.initial_window_size = Some(Vec2::new(1600.0, 900.0));
-    //options.fullscreen = true;
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(2);
+    });
+
+    let mut app = default_app();
+    if let Some(classes) = args
+        .classes
+        .filter(|&classes| classes != app.sim.params.len())
+    {
+        eprintln!(
+            "error: --classes {} isn't supported, the number of types is fixed at {}",
+            classes,
+            app.sim.params.len()
+        );
+        std::process::exit(2);
+    }
+    // check the seed before opening a window, so a typo doesn't open a broken simulation
+    if let Some(seed) = &args.seed {
+        app.seed = seed.clone();
+        app.apply_seed();
+        if let Some(err) = &app.seed_error {
+            eprintln!("error: invalid --seed: {}", err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(steps) = args.bench {
+        if args.seed.is_none() {
+            app.seed = "bench".to_string();
+            app.apply_seed();
+        }
+        bench(&mut app, steps);
+        return;
+    }
+
+    let options = NativeOptions {
+        initial_window_size: Some(args.window.size.unwrap_or(Vec2::new(1600.0, 900.0))),
+        fullscreen: args.window.fullscreen.unwrap_or(false),
+        ..Default::default()
+    };
     eframe::run_native(
         "Smarticles",
         options,
-        Box::new(|_cc| {
-            Box::new(Smarticles::new(
-                INIT_SIZE,
-                INIT_SIZE,
-                [
-                    ("α", Rgba::from_rgb(1.0, 0.0, 0.0)),
-                    ("β", Rgba::from_rgb(0.0, 1.0, 0.0)),
-                    ("γ", Rgba::from_rgb(1.0, 1.0, 1.0)),
-                    ("δ", Rgba::from_rgb(0.0, 0.0, 1.0)),
-                ],
-            ))
+        Box::new(move |cc| {
+            if let Some(storage) = cc.storage {
+                app.load(storage);
+            }
+            // the command line wins over whatever the last session left behind
+            if let Some(seed) = args.seed {
+                app.seed = seed;
+                app.apply_seed();
+                app.spawn();
+                app.update_history();
+            }
+            if args.play {
+                app.play();
+            }
+            if let Some(addr) = args.control {
+                app.control_addr = addr;
+                app.start_control(&cc.egui_ctx);
+                if let Some(err) = &app.control_error {
+                    eprintln!("error: couldn't listen on --control address: {}", err);
+                }
+            }
+            app.window_args = Some(args.window);
+            Box::new(app)
         }),
     );
 }
 
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    // otherwise panics only show up as "unreachable" in the browser console
+    console_error_panic_hook::set_once();
+
+    let mut app = default_app();
+    eframe::start_web(
+        "smarticles_canvas",
+        eframe::WebOptions::default(),
+        Box::new(move |cc| {
+            if let Some(storage) = cc.storage {
+                app.load(storage);
+            }
+            Box::new(app)
+        }),
+    )
+    .expect("failed to start the app");
+}
+
+/// The types are the same on every platform, so seeds can be shared between them.
+fn default_app() -> Smarticles<4> {
+    Smarticles::new(
+        INIT_SIZE,
+        INIT_SIZE,
+        [
+            ("α", Rgba::from_rgb(1.0, 0.0, 0.0)),
+            ("β", Rgba::from_rgb(0.0, 1.0, 0.0)),
+            ("γ", Rgba::from_rgb(1.0, 1.0, 1.0)),
+            ("δ", Rgba::from_rgb(0.0, 0.0, 1.0)),
+        ],
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Runs the simulation without a window and prints timings, one `name value` pair per line.
+fn bench<const N: usize>(app: &mut Smarticles<N>, steps: usize) {
+    // a fixed spawn makes the checksum comparable between runs
+    app.spawn_with(&mut SmallRng::seed_from_u64(0));
+
+    let mut times: Vec<f64> = (0..steps)
+        .map(|_| {
+            let time = Instant::now();
+            app.sim.step();
+            time.elapsed().as_secs_f64() * 1000.0
+        })
+        .collect();
+    times.sort_by(f64::total_cmp);
+
+    // FNV-1a, which unlike `DefaultHasher` is guaranteed to stay the same across Rust versions
+    let checksum = app
+        .sim
+        .dots
+        .iter()
+        .flatten()
+        .flat_map(|dot| [dot.pos.x.to_bits(), dot.pos.y.to_bits()])
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, bits| {
+            (hash ^ bits as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    println!("seed {}", app.seed);
+    println!("steps {}", steps);
+    println!(
+        "particles {}",
+        app.sim.dots.iter().map(Vec::len).sum::<usize>()
+    );
+    println!("min_ms {:.4}", times[0]);
+    println!("mean_ms {:.4}", times.iter().sum::<f64>() / steps as f64);
+    println!("p95_ms {:.4}", times[(steps - 1) * 95 / 100]);
+    println!("max_ms {:.4}", times[steps - 1]);
+    println!("checksum {:016x}", checksum);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Options given on the command line.
+#[derive(Default)]
+struct Args {
+    window: WindowArgs,
+    seed: Option<String>,
+    classes: Option<usize>,
+    play: bool,
+    bench: Option<usize>,
+    control: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Window options, which are applied once the window exists.
+#[derive(Clone, Copy, Default)]
+struct WindowArgs {
+    fullscreen: Option<bool>,
+    size: Option<Vec2>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut result = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--windowed" => result.window.fullscreen = Some(false),
+                "--fullscreen" => result.window.fullscreen = Some(true),
+                "--size" => {
+                    let size = args.next().and_then(|size| {
+                        let (w, h) = size.split_once('x')?;
+                        Some(Vec2::new(w.parse().ok()?, h.parse().ok()?))
+                    });
+                    let size = size.ok_or("--size expects WIDTHxHEIGHT, e.g. --size 1280x720")?;
+                    result.window.size = Some(size);
+                }
+                "--seed" => {
+                    let seed = args
+                        .next()
+                        .ok_or("--seed expects a word seed or an @ code")?;
+                    result.seed = Some(seed);
+                }
+                "--classes" => {
+                    let classes = args.next().and_then(|n| n.parse().ok());
+                    result.classes = Some(classes.ok_or("--classes expects a number")?);
+                }
+                "--play" | "--autostart" => result.play = true,
+                "--bench" => {
+                    let steps = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                    result.bench = Some(steps.ok_or("--bench expects a number of steps")?);
+                }
+                "--control" => {
+                    let addr = args.next().ok_or("--control expects HOST:PORT")?;
+                    result.control = Some(addr);
+                }
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+        Ok(result)
+    }
+}
+
 struct Smarticles<const N: usize> {
-    world_w: f32,
-    world_h: f32,
-    params: [Params<N>; N],
-    dots: [Vec<Dot>; N],
+    sim: Simulation<N>,
+    locks: [Locks<N>; N],
+    appearance: Appearance,
     play: bool,
     prev_time: Instant,
+    step_time: Duration,
+    /// How long to wait between steps while playing, in milliseconds.
+    step_interval: f32,
+    /// When the recent steps and frames happened, for measuring their rates.
+    step_times: VecDeque<Instant>,
+    frame_times: VecDeque<Instant>,
+    step_held_since: Option<Instant>,
+    selected_particle: (usize, usize),
+    /// The type whose settings are shown, or every type's.
+    class_tab: Option<usize>,
+    /// Pairs with less power than this either way are left out of the type settings.
+    force_filter: f32,
+    /// Respawning selects the particle of the same type nearest to where the selected one was,
+    /// rather than whichever now has its index.
+    track_selected: bool,
+    /// Where the selected particle was before the last respawn, and when that was.
+    respawn_mark: Option<(Vec2, Instant)>,
+    /// Particles picked with a box, as (type, index) pairs.
+    group: Vec<(usize, usize)>,
+    /// The counts when the group was picked, since the indices don't survive them changing.
+    group_counts: [usize; N],
+    /// Where a shift-drag to pick a group started, on screen.
+    group_box: Option<Pos2>,
+    /// Whether clicks in the view bond particles instead of selecting them.
+    bond_tool: bool,
+    /// The particle clicked first with the bond tool, and the counts when it was, since
+    /// its index doesn't survive them changing.
+    bond_from: Option<((usize, usize), [usize; N])>,
+    bond_stiffness: f32,
+    /// How close particles in the group have to be for "Bond Group" to bond them.
+    bond_radius: f32,
+    selected_param: (usize, usize),
+    inspector_open: bool,
+    reveal_inspector: bool,
+    view: View,
+    bookmarks: [Option<View>; BOOKMARK_SLOTS],
+    smooth_bookmarks: bool,
+    /// The view being glided away from and towards, and when that started.
+    glide: Option<(View, View, Instant)>,
+    /// Where the view was before it started following something, to go back to after.
+    manual_view: Option<View>,
+    follow: Follow,
+    follow_class: usize,
+    prev_follow_pos: Option<Vec2>,
+    show_minimap: bool,
+    show_panel: bool,
+    panel_side: Side,
+    show_grid: bool,
+    show_clusters: bool,
+    show_flow: bool,
+    settle_action: SettleAction,
+    tuner: Option<Tuner<N>>,
+    tune_goal: TuneGoal,
+    tune_speed: f32,
+    tune_steps: u32,
+    /// Particles closer than this are in the same cluster.
+    cluster_distance: f32,
+    show_radius: bool,
+    smooth_motion: bool,
+    keep_simulating: bool,
+    /// Counts that would make steps slower than this many milliseconds are warned about.
+    step_budget: f32,
+    auto_limit: bool,
+    /// A running average of how long each interaction takes, for estimating step times.
+    ns_per_interaction: Option<f64>,
+    /// Where the particles were before the last step, for drawing them in between steps.
+    prev_positions: [Vec<Vec2>; N],
+    render_mode: RenderMode,
+    field: DensityField,
+    trail_len: usize,
+    trails: VecDeque<[Vec<Vec2>; N]>,
+    collect_stats: bool,
+    stats: VecDeque<SimStats>,
+    clusters: Vec<Cluster>,
+    cluster_frame: u32,
+    /// The simulated time of each sample, and the mean speed of each type.
+    activity: VecDeque<(f32, [f32; N])>,
+    activity_frame: u32,
+    distances: Option<DistanceHistogram>,
+    /// Also plot the force the other way around the selected pair.
+    curve_reverse: bool,
+    /// Plot the forces on the selected type from every type, rather than just the pair.
+    curve_all: bool,
+    curve_drag: Option<CurveDrag>,
     seed: String,
-    words: Vec<String>,
+    seed_error: Option<String>,
+    history: VecDeque<HistoryEntry>,
+    presets: Vec<Preset>,
+    gallery: Vec<Preset>,
+    preset_name: String,
+    mutation_strength: f32,
+    undo: VecDeque<String>,
+    redo: Vec<String>,
+    committed: String,
+    words: Vec<&'static str>,
+    #[cfg(not(target_arch = "wasm32"))]
+    window_args: Option<WindowArgs>,
+    view_size: Vec2,
+    image_size: u32,
+    image_whole_world: bool,
+    output_dir: String,
+    export_status: Option<Result<String, String>>,
+    recorder: Option<Recorder>,
+    record_status: Option<Result<String, String>>,
+    player: Option<Player>,
+    frame_writer: Option<FrameWriter>,
+    /// The last few seconds of frames, while buffering is on.
+    replay: Option<ReplayBuffer>,
+    replay_secs: u32,
+    replay_size: u32,
+    gif_writer: Option<GifWriter>,
+    config_path: String,
+    config_warnings: Vec<String>,
+    matrix_path: String,
+    playback_path: String,
+    playback_error: Option<String>,
+    toast: Option<(Result<String, String>, Instant)>,
+    recovered: Option<Session>,
+    closing: bool,
+    streamer: Option<Streamer>,
+    stream_target: String,
+    stream_rate: f32,
+    stream_stride: usize,
+    stream_error: Option<String>,
+    control: Option<ControlServer>,
+    control_addr: String,
+    control_error: Option<String>,
+    audio: Option<AudioInput>,
+    /// The input to listen to, or `None` for the default one.
+    audio_device: Option<String>,
+    /// The devices to pick from, which are only looked up once the list is opened.
+    audio_devices: Option<Vec<String>>,
+    audio_error: Option<String>,
+    levels: Levels,
+    mappings: [Mapping; ModTarget::ALL.len()],
 }
 
-struct Params<const N: usize> {
-    name: String,
-    heading: String,
-    color: Rgba,
-    count: usize,
-    power: [f32; N],
-    radius: [f32; N],
+/// Parameters the user pinned so randomizing and mutating leave them alone.
+#[derive(Clone, Copy)]
+struct Locks<const N: usize> {
+    count: bool,
+    power: [bool; N],
+    radius: [bool; N],
+    spin: [bool; N],
 }
 
-#[derive(Clone)]
-struct Dot {
-    pos: Vec2,
-    vel: Vec2,
+impl<const N: usize> Default for Locks<N> {
+    fn default() -> Self {
+        Self {
+            count: false,
+            power: [false; N],
+            radius: [false; N],
+            spin: [false; N],
+        }
+    }
 }
 
-impl<const N: usize> Smarticles<N> {
-    fn new<S>(world_w: f32, world_h: f32, colors: [(S, Rgba); N]) -> Self
-    where
-        S: ToString,
-    {
-        let words = include_str!("words.txt");
-        let words = words
-            .par_lines()
-            .filter_map(|w| {
-                if w.len() > 8 {
-                    return None;
-                }
-                for chr in w.chars() {
-                    if !chr.is_ascii_alphabetic() || chr.is_ascii_uppercase() {
-                        return None;
-                    }
-                }
-                Some(w.to_string())
-            })
-            .collect();
+/// A setup that was applied, with the full code so going back to it doesn't depend on how a
+/// word seed happens to roll.
+struct HistoryEntry {
+    /// The word seed, or when an edited setup was recorded.
+    label: String,
+    code: String,
+}
 
-        Self {
-            world_w,
-            world_h,
-            params: colors.map(|(name, color)| Params {
-                name: name.to_string(),
-                heading: "Type ".to_string() + &name.to_string(),
-                color: color.into(),
-                count: 0,
-                power: [0.0; N],
-                radius: [MIN_RADIUS; N],
+impl HistoryEntry {
+    // stored one per line like presets, but lines from before that are just the seed
+    fn parse(line: &str) -> Option<Self> {
+        let (mut label, mut code) = (None, None);
+        for field in line.split('\t') {
+            match field.split_once('=') {
+                Some(("label", value)) => label = Some(value.to_string()),
+                Some(("code", value)) => code = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        match code {
+            Some(code) => Some(Self {
+                label: label.unwrap_or_else(|| code.clone()),
+                code,
             }),
-            dots: std::array::from_fn(|_| Vec::new()),
-            play: false,
-            prev_time: Instant::now(),
-            seed: String::new(),
-            words,
+            None if !line.is_empty() => Some(Self {
+                label: line.to_string(),
+                code: line.to_string(),
+            }),
+            None => None,
         }
     }
 
-    fn play(&mut self) {
-        self.play = true;
+    fn encode(&self) -> String {
+        format!("label={}\tcode={}", self.label, self.code)
     }
+}
 
-    fn stop(&mut self) {
-        self.play = false;
-    }
+/// How the world view is drawn, which is saved with the rest of the UI state.
+#[derive(Clone)]
+struct Appearance {
+    background: Color32,
+    boundary: Color32,
+    boundary_width: f32,
+    /// How each particle type is drawn, by index. Types without one use the default.
+    dots: Vec<DotStyle>,
+    scale_with_zoom: bool,
+    /// Whether particles fade out towards the end of their lifetime.
+    fade_with_age: bool,
+    /// Stats drawn over a corner of the world, which exported images get too.
+    hud: bool,
+    hud_corner: Corner,
+    /// The overlay's text size in a view `HUD_REFERENCE_HEIGHT` tall.
+    hud_size: f32,
+    /// Which fields the overlay shows, by `HudField` index.
+    hud_fields: [bool; HudField::ALL.len()],
+}
 
-    fn restart(&mut self) {
-        self.world_w = INIT_SIZE;
-        self.world_h = INIT_SIZE;
-        for p in &mut self.params {
-            p.count = 0;
-            p.radius.iter_mut().for_each(|r| *r = 0.0);
-            p.power.iter_mut().for_each(|p| *p = 0.0);
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            background: egui::Visuals::dark().window_fill(),
+            boundary: Color32::from_gray(200),
+            boundary_width: 0.0,
+            dots: Vec::new(),
+            scale_with_zoom: false,
+            fade_with_age: true,
+            hud: false,
+            hud_corner: Corner::TopLeft,
+            hud_size: 16.0,
+            hud_fields: [true; HudField::ALL.len()],
         }
     }
+}
 
-    fn clear(&mut self) {
-        for i in 0..N {
-            self.dots[i].clear();
+#[derive(Clone, Copy)]
+struct DotStyle {
+    size: f32,
+    shape: DotShape,
+}
+
+impl Default for DotStyle {
+    fn default() -> Self {
+        Self {
+            size: 2.0,
+            shape: DotShape::Circle,
         }
     }
+}
 
-    fn spawn(&mut self) {
-        self.clear();
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DotShape {
+    Circle,
+    Square,
+}
 
-        let mut rand = SmallRng::from_entropy();
+impl DotShape {
+    const ALL: [Self; 2] = [Self::Circle, Self::Square];
 
-        for i in 0..N {
-            self.dots[i].clear();
-            for _ in 0..self.params[i].count {
-                self.dots[i].push(Dot {
-                    pos: Vec2::new(
-                        self.world_w * rand.sample::<f32, _>(OpenClosed01),
-                        self.world_h * rand.sample::<f32, _>(OpenClosed01),
-                    ),
-                    vel: Vec2::ZERO,
-                });
-            }
+    fn name(self) -> &'static str {
+        match self {
+            Self::Circle => "Circle",
+            Self::Square => "Square",
         }
     }
+}
 
-    fn apply_seed(&mut self) {
-        self.clear();
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
 
-        let mut rand = if self.seed.is_empty() {
-            SmallRng::from_entropy()
-        } else {
-            if self.seed.starts_with('@') {
-                if let Ok(bytes) = base64::decode(&self.seed[1..]) {
-                    self.import(&bytes);
-                    return;
-                }
-            }
-            let mut hasher = DefaultHasher::new();
-            self.seed.hash(&mut hasher);
-            SmallRng::seed_from_u64(hasher.finish())
-        };
-        let mut rand = |min: f32, max: f32| min + (max - min) * rand.sample::<f32, _>(OpenClosed01);
+impl Corner {
+    const ALL: [Self; 4] = [
+        Self::TopLeft,
+        Self::TopRight,
+        Self::BottomLeft,
+        Self::BottomRight,
+    ];
 
-        const POW_F: f32 = 1.25;
-        const RAD_F: f32 = 1.1;
+    fn name(self) -> &'static str {
+        match self {
+            Self::TopLeft => "Top Left",
+            Self::TopRight => "Top Right",
+            Self::BottomLeft => "Bottom Left",
+            Self::BottomRight => "Bottom Right",
+        }
+    }
 
-        for i in 0..N {
-            self.params[i].count = rand(MIN_COUNT as f32, MAX_COUNT as f32) as usize;
-            for j in 0..N {
-                let pow = rand(MIN_POWER, MAX_POWER);
-                self.params[i].power[j] = if pow >= 0.0 {
-                    pow.powf(1.0 / POW_F)
-                } else {
-                    -pow.abs().powf(1.0 / POW_F)
-                };
-                //self.params[i].power[j] = rand(MIN_POWER, MAX_POWER);
-                self.params[i].radius[j] = rand(MIN_RADIUS, MAX_RADIUS).powf(1.0 / RAD_F);
-            }
+    fn key(self) -> &'static str {
+        match self {
+            Self::TopLeft => "top_left",
+            Self::TopRight => "top_right",
+            Self::BottomLeft => "bottom_left",
+            Self::BottomRight => "bottom_right",
         }
     }
 
-    fn simulate(&mut self) {
-        let mut dots: [Vec<Dot>; N] = std::array::from_fn(|i| self.dots[i].clone());
-        dots.par_iter_mut().enumerate().for_each(|(i, dots_i)| {
-            for j in 0..N {
-                interaction(
-                    dots_i,
-                    &self.dots[j],
-                    self.params[i].power[j],
-                    self.params[i].radius[j],
-                    self.world_w,
-                    self.world_h,
-                );
-            }
-        });
-        self.dots = dots;
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|corner| corner.key() == key)
     }
 
-    fn export(&self) -> String {
-        let mut bytes: Vec<u8> = Vec::new();
-        bytes.write_u16::<LE>(self.world_w as u16).unwrap();
-        bytes.write_u16::<LE>(self.world_h as u16).unwrap();
-        for p in &self.params {
-            bytes.write_u8((p.color.r() * 255.0) as u8).unwrap();
-            bytes.write_u8((p.color.g() * 255.0) as u8).unwrap();
-            bytes.write_u8((p.color.b() * 255.0) as u8).unwrap();
-            bytes.write_u16::<LE>(p.count as u16).unwrap();
-            for &p in &p.power {
-                bytes.write_i8(p as i8).unwrap();
-            }
-            for &r in &p.radius {
-                bytes.write_u16::<LE>(r as u16).unwrap();
-            }
+    fn align(self) -> Align2 {
+        match self {
+            Self::TopLeft => Align2::LEFT_TOP,
+            Self::TopRight => Align2::RIGHT_TOP,
+            Self::BottomLeft => Align2::LEFT_BOTTOM,
+            Self::BottomRight => Align2::RIGHT_BOTTOM,
         }
-        format!("@{}", base64::encode(bytes))
     }
+}
 
-    fn import(&mut self, mut bytes: &[u8]) {
-        self.world_w = bytes.read_u16::<LE>().unwrap_or(1000) as f32;
-        self.world_h = bytes.read_u16::<LE>().unwrap_or(1000) as f32;
-        for p in &mut self.params {
-            let r = (bytes.read_u8().unwrap_or((p.color.r() * 255.0) as u8) as f32) / 255.0;
-            let g = (bytes.read_u8().unwrap_or((p.color.g() * 255.0) as u8) as f32) / 255.0;
-            let b = (bytes.read_u8().unwrap_or((p.color.b() * 255.0) as u8) as f32) / 255.0;
-            p.color = Rgba::from_rgb(r, g, b);
-            p.count = bytes.read_u16::<LE>().unwrap_or(0) as usize;
-            for p in &mut p.power {
-                *p = bytes.read_i8().unwrap_or(0) as f32;
-            }
-            for r in &mut p.radius {
-                *r = bytes.read_u16::<LE>().unwrap_or(0) as f32;
-            }
+/// Something the stats overlay can show.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HudField {
+    Seed,
+    Particles,
+    StepRate,
+    /// The frame number and how long that is at the usual step rate.
+    SimTime,
+}
+
+impl HudField {
+    const ALL: [Self; 4] = [Self::Seed, Self::Particles, Self::StepRate, Self::SimTime];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Seed => "Seed",
+            Self::Particles => "Particles",
+            Self::StepRate => "Steps/s",
+            Self::SimTime => "Time",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Seed => "seed",
+            Self::Particles => "particles",
+            Self::StepRate => "step_rate",
+            Self::SimTime => "sim_time",
         }
     }
 }
 
-fn interaction(
-    group1: &mut [Dot],
-    group2: &[Dot],
-    g: f32,
-    radius: f32,
-    world_w: f32,
-    world_h: f32,
-) {
-    let g = g / -100.0;
-    group1.par_iter_mut().for_each(|p1| {
-        let mut f = Vec2::ZERO;
-        for p2 in group2 {
-            let d = p1.pos - p2.pos;
-            let r = d.length();
-            if r < radius && r > 0.0 {
-                f += d / r;
-            }
+impl Appearance {
+    fn load(storage: &dyn Storage) -> Self {
+        let color = |key| {
+            let hex = storage.get_string(key)?;
+            let rgb = u32::from_str_radix(&hex, 16).ok()?.to_be_bytes();
+            Some(Color32::from_rgb(rgb[1], rgb[2], rgb[3]))
+        };
+        let default = Self::default();
+        Self {
+            background: color("background").unwrap_or(default.background),
+            boundary: color("boundary").unwrap_or(default.boundary),
+            boundary_width: storage
+                .get_string("boundary_width")
+                .and_then(|width| width.parse().ok())
+                .unwrap_or(default.boundary_width),
+            // one `size shape` pair per type, separated by commas
+            dots: storage
+                .get_string("dot_styles")
+                .map(|styles| {
+                    styles
+                        .split(',')
+                        .map(|style| {
+                            let (size, shape) = style.split_once(' ').unwrap_or((style, ""));
+                            DotStyle {
+                                size: size
+                                    .parse::<f32>()
+                                    .map_or(2.0, |size| size.clamp(MIN_DOT_SIZE, MAX_DOT_SIZE)),
+                                shape: DotShape::ALL
+                                    .into_iter()
+                                    .find(|s| s.name() == shape)
+                                    .unwrap_or(DotShape::Circle),
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            scale_with_zoom: storage.get_string("scale_with_zoom").as_deref() == Some("true"),
+            fade_with_age: storage.get_string("fade_with_age").as_deref() != Some("false"),
+            hud: storage.get_string("hud").as_deref() == Some("true"),
+            hud_corner: storage
+                .get_string("hud_corner")
+                .and_then(|key| Corner::from_key(&key))
+                .unwrap_or(default.hud_corner),
+            hud_size: storage
+                .get_string("hud_size")
+                .and_then(|size| size.parse::<f32>().ok())
+                .map_or(default.hud_size, |size| {
+                    size.clamp(MIN_HUD_SIZE, MAX_HUD_SIZE)
+                }),
+            // the keys of the fields that are shown, separated by commas
+            hud_fields: storage
+                .get_string("hud_fields")
+                .map_or(default.hud_fields, |keys| {
+                    HudField::ALL.map(|field| keys.split(',').any(|key| key == field.key()))
+                }),
         }
+    }
+
+    fn save(&self, storage: &mut dyn Storage) {
+        let hex = |col: Color32| format!("{:02x}{:02x}{:02x}", col.r(), col.g(), col.b());
+        storage.set_string("background", hex(self.background));
+        storage.set_string("boundary", hex(self.boundary));
+        storage.set_string("boundary_width", self.boundary_width.to_string());
+        let styles: Vec<_> = self
+            .dots
+            .iter()
+            .map(|style| format!("{} {}", style.size, style.shape.name()))
+            .collect();
+        storage.set_string("dot_styles", styles.join(","));
+        storage.set_string("scale_with_zoom", self.scale_with_zoom.to_string());
+        storage.set_string("fade_with_age", self.fade_with_age.to_string());
+        storage.set_string("hud", self.hud.to_string());
+        storage.set_string("hud_corner", self.hud_corner.key().to_string());
+        storage.set_string("hud_size", self.hud_size.to_string());
+        let fields: Vec<_> = (HudField::ALL.into_iter())
+            .filter(|&field| self.hud_fields[field as usize])
+            .map(HudField::key)
+            .collect();
+        storage.set_string("hud_fields", fields.join(","));
+    }
+
+    /// How big the overlay's text is drawn in a view or image this tall.
+    fn hud_text_size(&self, height: f32) -> f32 {
+        (self.hud_size * height / HUD_REFERENCE_HEIGHT).max(MIN_HUD_TEXT)
+    }
+
+    fn dot_style(&self, class: usize) -> DotStyle {
+        self.dots.get(class).copied().unwrap_or_default()
+    }
 
-        p1.vel = (p1.vel + f * g) * 0.5;
-        p1.pos += p1.vel;
+    fn dot_style_mut(&mut self, class: usize) -> &mut DotStyle {
+        if self.dots.len() <= class {
+            self.dots.resize(class + 1, DotStyle::default());
+        }
+        &mut self.dots[class]
+    }
 
-        if (p1.pos.x < 10.0 && p1.vel.x < 0.0) || (p1.pos.x > world_w - 10.0 && p1.vel.x > 0.0) {
-            p1.vel.x *= -1.0;
+    /// How big particles of a type are drawn at a zoom level.
+    fn dot_radius(&self, class: usize, zoom: f32) -> f32 {
+        let size = self.dot_style(class).size;
+        if self.scale_with_zoom {
+            (size * zoom).min(MAX_DOT_RADIUS)
+        } else {
+            size
         }
-        if (p1.pos.y < 10.0 && p1.vel.y < 0.0) || (p1.pos.y > world_h - 10.0 && p1.vel.y > 0.0) {
-            p1.vel.y *= -1.0;
+    }
+}
+
+/// What's restored from the last session, which is autosaved in case the app crashes.
+struct Session {
+    seed: Option<String>,
+    state: Option<String>,
+    view: Option<View>,
+}
+
+impl Session {
+    fn load(storage: &dyn Storage) -> Self {
+        Self {
+            seed: storage.get_string("seed"),
+            state: storage.get_string("state"),
+            view: storage
+                .get_string("view")
+                .and_then(|view| View::parse(&view)),
         }
+    }
 
-        // alternative: wrap
-        /*if p1.pos.x < 0.0 {
-            p1.pos.x += world_w;
-        } else if p1.pos.x >= world_w {
-            p1.pos.x -= world_w;
+    fn save(&self, storage: &mut dyn Storage) {
+        if let Some(seed) = &self.seed {
+            storage.set_string("seed", seed.clone());
         }
-        if p1.pos.y < 0.0 {
-            p1.pos.y += world_h;
-        } else if p1.pos.y >= world_h {
-            p1.pos.y -= world_h;
-        }*/
-    });
+        if let Some(state) = &self.state {
+            storage.set_string("state", state.clone());
+        }
+        if let Some(view) = self.view {
+            storage.set_string("view", view.encode());
+        }
+    }
 }
 
-impl<const N: usize> App for Smarticles<N> {
-    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
-        if self.play {
-            let time = Instant::now();
-            let delta = time - self.prev_time;
-            if delta > Duration::from_secs_f32(1.0 / 60.0) {
-                self.prev_time = time;
-                self.simulate();
-            }
-            ctx.request_repaint();
+/// The world point shown at the center of the panel, and how many pixels a world unit takes up.
+#[derive(Clone, Copy)]
+struct View {
+    pos: Vec2,
+    zoom: f32,
+}
+
+impl View {
+    fn new(world_w: f32, world_h: f32) -> Self {
+        Self {
+            pos: Vec2::new(world_w, world_h) / 2.0,
+            zoom: 1.0,
         }
+    }
 
-        SidePanel::left("settings").show(&ctx, |ui| {
-            ui.heading("Settings");
-            ui.separator();
-            ui.horizontal(|ui| {
-                if ui.button("Respawn").clicked() {
-                    self.spawn();
-                }
-                if self.play {
-                    if ui.button("Pause").clicked() {
-                        self.stop();
-                    }
-                } else {
-                    if ui.button("Play").clicked() {
-                        self.play();
-                    }
-                }
+    fn to_screen(self, center: Pos2, pos: Vec2) -> Pos2 {
+        center + (pos - self.pos) * self.zoom
+    }
 
-                if ui.button("Randomize").clicked() {
-                    let w1 = rand::random::<usize>() % self.words.len();
-                    let w2 = rand::random::<usize>() % self.words.len();
-                    self.seed = format!("{}_{}", self.words[w1], self.words[w2]);
+    /// Blends towards another view, zooming at an even pace whatever the zoom levels.
+    fn lerp(self, to: Self, t: f32) -> Self {
+        Self {
+            pos: self.pos + (to.pos - self.pos) * t,
+            zoom: self.zoom * (to.zoom / self.zoom).powf(t),
+        }
+    }
 
-                    self.apply_seed();
-                    self.spawn();
-                }
+    fn encode(self) -> String {
+        format!("{} {} {}", self.pos.x, self.pos.y, self.zoom)
+    }
 
-                if ui.button("Reset").clicked() {
-                    self.restart();
-                }
+    fn parse(text: &str) -> Option<Self> {
+        let mut values = text.split(' ').map(|v| v.parse::<f32>().ok());
+        let (x, y, zoom) = (values.next()??, values.next()??, values.next()??);
+        Some(Self {
+            pos: Vec2::new(x, y),
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        })
+    }
 
-                if ui.button("Quit").clicked() {
-                    frame.close();
-                }
-            });
-            ui.horizontal(|ui| {
-                ui.label("Seed:");
-                if ui.text_edit_singleline(&mut self.seed).changed() {
-                    self.apply_seed();
-                    self.spawn();
-                    self.stop();
-                }
-            });
+    fn to_world(self, center: Pos2, pos: Pos2) -> Vec2 {
+        self.pos + (pos - center) / self.zoom
+    }
+}
 
-            ui.horizontal(|ui| {
-                ui.label("World Width:");
-                if ui
-                    .add(Slider::new(&mut self.world_w, 100.0..=1000.0))
-                    .changed()
-                {
-                    self.seed = self.export();
-                    self.spawn();
-                }
-            });
-            ui.horizontal(|ui| {
-                ui.label("World Height:");
-                if ui
-                    .add(Slider::new(&mut self.world_h, 100.0..=1000.0))
-                    .changed()
-                {
-                    self.seed = self.export();
-                    self.spawn();
-                }
-            });
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RenderMode {
+    ClassColor,
+    SpeedHeatmap,
+    DensityHeatmap,
+    /// A blurred grid of the particles instead of the particles themselves.
+    DensityField,
+    /// How hard the selected pair's other type pulls on each particle of its first type.
+    PairForce,
+}
 
-            for i in 0..N {
-                ui.add_space(10.0);
-                ui.colored_label(self.params[i].color, &self.params[i].heading);
-                ui.separator();
+impl RenderMode {
+    const ALL: [Self; 5] = [
+        Self::ClassColor,
+        Self::SpeedHeatmap,
+        Self::DensityHeatmap,
+        Self::DensityField,
+        Self::PairForce,
+    ];
 
-                ui.horizontal(|ui| {
-                    ui.label("Color:");
-                    let mut rgb = [
-                        self.params[i].color.r(),
-                        self.params[i].color.g(),
-                        self.params[i].color.b(),
-                    ];
-                    if ui.color_edit_button_rgb(&mut rgb).changed() {
-                        self.params[i].color = Rgba::from_rgb(rgb[0], rgb[1], rgb[2]);
-                        self.seed = self.export();
-                    }
-                });
+    fn name(self) -> &'static str {
+        match self {
+            Self::ClassColor => "Type Color",
+            Self::SpeedHeatmap => "Speed",
+            Self::DensityHeatmap => "Density",
+            Self::DensityField => "Density Field",
+            Self::PairForce => "Selected Pair Force",
+        }
+    }
+}
 
-                ui.horizontal(|ui| {
-                    ui.label("Count:");
-                    if ui
-                        .add(Slider::new(
-                            &mut self.params[i].count,
-                            MIN_COUNT..=MAX_COUNT,
-                        ))
-                        .changed()
-                    {
-                        self.seed = self.export();
-                    }
-                });
+/// A coarse grid of where the particles are, which is kept around so drawing it doesn't
+/// allocate every frame.
+#[derive(Default)]
+struct DensityField {
+    cols: usize,
+    rows: usize,
+    cell_size: f32,
+    /// The summed colors of the particles in each cell, with their count last.
+    cells: Vec<[f32; 4]>,
+    blurred: Vec<[f32; 4]>,
+}
 
-                ui.horizontal(|ui| {
-                    ui.vertical(|ui| {
-                        for j in 0..N {
-                            ui.horizontal(|ui| {
-                                ui.label("Power (");
-                                ui.colored_label(self.params[j].color, &self.params[j].name);
-                                ui.label(")");
-                                if ui
-                                    .add(Slider::new(
-                                        &mut self.params[i].power[j],
-                                        MIN_POWER..=MAX_POWER,
-                                    ))
-                                    .changed()
-                                {
-                                    self.seed = self.export();
-                                }
-                            });
-                        }
-                    });
-                    ui.vertical(|ui| {
-                        for j in 0..N {
-                            ui.horizontal(|ui| {
-                                ui.label("Radius (");
-                                ui.colored_label(self.params[j].color, &self.params[j].name);
-                                ui.label(")");
-                                if ui
-                                    .add(Slider::new(
-                                        &mut self.params[i].radius[j],
-                                        MIN_RADIUS..=MAX_RADIUS,
-                                    ))
-                                    .changed()
-                                {
-                                    self.seed = self.export();
-                                }
-                            });
+impl DensityField {
+    fn update<const N: usize>(&mut self, sim: &Simulation<N>) {
+        let size = sim.border_size();
+        self.cell_size = size.x.max(size.y) / FIELD_CELLS as f32;
+        self.cols = (size.x / self.cell_size).ceil().max(1.0) as usize;
+        self.rows = (size.y / self.cell_size).ceil().max(1.0) as usize;
+        let (cols, rows) = (self.cols, self.rows);
+
+        self.cells.clear();
+        self.cells.resize(cols * rows, [0.0; 4]);
+        for (p, dots) in sim.params.iter().zip(&sim.dots) {
+            let (r, g, b) = (p.color.r(), p.color.g(), p.color.b());
+            for dot in dots {
+                let cell = &mut self.cells[grid_cell(dot.pos, cols, rows, self.cell_size)];
+                cell[0] += r;
+                cell[1] += g;
+                cell[2] += b;
+                cell[3] += 1.0;
+            }
+        }
+
+        // a 1-2-1 blur in both directions softens the edges between cells
+        self.blurred.clear();
+        self.blurred.resize(cols * rows, [0.0; 4]);
+        for y in 0..rows {
+            for x in 0..cols {
+                let mut sum = [0.0; 4];
+                for ny in y.saturating_sub(1)..=(y + 1).min(rows - 1) {
+                    for nx in x.saturating_sub(1)..=(x + 1).min(cols - 1) {
+                        let weight = ((2 - x.abs_diff(nx)) * (2 - y.abs_diff(ny))) as f32;
+                        let cell = self.cells[ny * cols + nx];
+                        for (sum, value) in sum.iter_mut().zip(cell) {
+                            *sum += value * weight / 16.0;
                         }
-                    });
-                });
+                    }
+                }
+                self.blurred[y * cols + x] = sum;
             }
-        });
+        }
+    }
 
-        CentralPanel::default().show(&ctx, |ui| {
-            let (resp, paint) =
-                ui.allocate_painter(ui.available_size_before_wrap(), Sense::hover());
+    /// Colors each cell by the types in it, brighter where there are more particles.
+    fn mesh(&self, view: View, center: Pos2) -> Mesh {
+        let max = self.blurred.iter().map(|cell| cell[3]).fold(0.0, f32::max);
+        let mut mesh = Mesh::default();
+        for (k, &[r, g, b, count]) in self.blurred.iter().enumerate() {
+            if count <= 0.0 {
+                continue;
+            }
+            let min = Vec2::new((k % self.cols) as f32, (k / self.cols) as f32) * self.cell_size;
+            let rect = Rect::from_min_max(
+                view.to_screen(center, min),
+                view.to_screen(center, min + Vec2::splat(self.cell_size)),
+            );
+            let col = Rgba::from_rgb(r / count, g / count, b / count) * (count / max).sqrt();
+            mesh.add_colored_rect(rect, col.into());
+        }
+        mesh
+    }
+}
 
-            let min = resp.rect.min
-                + Vec2::new(
-                    (resp.rect.width() - self.world_w) / 2.0,
-                    (resp.rect.height() - self.world_h) / 2.0,
-                );
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    Off,
+    SelectedParticle,
+    ClassCenterOfMass,
+    GlobalCenterOfMass,
+    GroupCenter,
+}
 
-            for i in 0..N {
-                let p = &self.params[i];
-                let col: Color32 = p.color.into();
-                for dot in &self.dots[i] {
-                    paint.circle_filled(min + dot.pos, 2.0, col);
-                }
-            }
+impl Follow {
+    const ALL: [Self; 5] = [
+        Self::Off,
+        Self::SelectedParticle,
+        Self::ClassCenterOfMass,
+        Self::GlobalCenterOfMass,
+        Self::GroupCenter,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::SelectedParticle => "Selected Particle",
+            Self::ClassCenterOfMass => "Type Center of Mass",
+            Self::GlobalCenterOfMass => "Center of Mass",
+            Self::GroupCenter => "Selected Group",
+        }
+    }
+}
+
+/// What auto-tuning pushes the simulation towards.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TuneGoal {
+    MostClusters,
+    /// A mean speed as close as possible to the target one.
+    TargetSpeed,
+}
+
+impl TuneGoal {
+    const ALL: [Self; 2] = [Self::MostClusters, Self::TargetSpeed];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::MostClusters => "Most Clusters",
+            Self::TargetSpeed => "Target Speed",
+        }
+    }
+
+    fn describe(self, score: f32) -> String {
+        match self {
+            Self::MostClusters => format!("{:.1} clusters", score),
+            Self::TargetSpeed => format!("speed off by {:.3}", -score),
+        }
+    }
+}
+
+/// Hill climbing on the forces and counts: each try mutates them, runs for a while and keeps
+/// the change if it scored better, or goes back to the best setup so far if it didn't.
+struct Tuner<const N: usize> {
+    goal: TuneGoal,
+    best: [Params<N>; N],
+    /// Higher is better, and it's `None` until the starting setup has been measured.
+    best_score: Option<f32>,
+    /// How far into the current try it is.
+    steps: u32,
+    score_sum: f32,
+    samples: u32,
+    tries: u32,
+    /// The try and score of each change that was kept.
+    accepted: Vec<(u32, f32)>,
+}
+
+/// What happens when the simulation settles down.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettleAction {
+    Nothing,
+    Pause,
+    /// Moves on to a random seed, which makes a screensaver out of it.
+    NextSeed,
+}
+
+impl SettleAction {
+    const ALL: [Self; 3] = [Self::Nothing, Self::Pause, Self::NextSeed];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Nothing => "Nothing",
+            Self::Pause => "Pause",
+            Self::NextSeed => "Next Seed",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Nothing => "nothing",
+            Self::Pause => "pause",
+            Self::NextSeed => "next_seed",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.key() == key)
+    }
+}
+
+/// A setting the audio input can modulate.
+#[derive(Clone, Copy)]
+enum ModTarget {
+    Force,
+    WorldSize,
+    Friction,
+}
+
+impl ModTarget {
+    const ALL: [Self; 3] = [Self::Force, Self::WorldSize, Self::Friction];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Force => "Force",
+            Self::WorldSize => "World Size",
+            Self::Friction => "Friction",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Force => "force",
+            Self::WorldSize => "world_size",
+            Self::Friction => "friction",
+        }
+    }
+}
+
+/// Which feature of the sound drives a setting, and how hard. The setting is multiplied by
+/// `1 + depth * level`, so a negative depth makes it shrink with the sound instead.
+#[derive(Clone, Copy)]
+struct Mapping {
+    feature: Option<Feature>,
+    depth: f32,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Self {
+            feature: None,
+            depth: 1.0,
+        }
+    }
+}
+
+impl Mapping {
+    fn encode(&self) -> String {
+        let feature = self.feature.map_or("off", Feature::key);
+        format!("{} {}", feature, self.depth)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let (feature, depth) = text.split_once(' ')?;
+        let feature = match feature {
+            "off" => None,
+            key => Some(Feature::from_key(key)?),
+        };
+        let depth: f32 = depth.parse().ok()?;
+        Some(Self {
+            feature,
+            depth: depth.clamp(-MAX_DEPTH, MAX_DEPTH),
+        })
+    }
+}
+
+/// Which part of the force plot is being dragged.
+#[derive(Clone, Copy)]
+enum CurveDrag {
+    /// The height of the curve, which is the power.
+    Power,
+    /// The edge of the curve, which is the radius. The plot's width is held at `x_max`
+    /// meanwhile, or it would rescale under the pointer.
+    Radius { x_max: f32 },
+}
+
+/// How far apart the particles of the selected pair of types are.
+struct DistanceHistogram {
+    pair: (usize, usize),
+    /// Pair counts for distances up to twice the max radius.
+    bins: Vec<u32>,
+    /// Only every this many particles of the first type were measured.
+    sample_every: usize,
+    time: Instant,
+}
+
+/// A group of particles that are each within the cluster distance of another member.
+struct Cluster {
+    center: Vec2,
+    /// The root mean square distance of the members from the center.
+    spread: f32,
+    count: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SimStats {
+    /// The simulated time they were taken at, in seconds.
+    time: f32,
+    mean_speed: f32,
+    max_speed: f32,
+    kinetic_energy: f32,
+    clusters: usize,
+}
+
+impl<const N: usize> Smarticles<N> {
+    fn new<S>(world_w: f32, world_h: f32, colors: [(S, Rgba); N]) -> Self
+    where
+        S: ToString,
+    {
+        // the list is filtered ahead of time, rather than on every launch, to lowercase words
+        // of 3 to 8 letters that have a vowel, no letter three times in a row, no more than
+        // three consonants in a row and no q without a u, so seeds are easy to say
+        let words = include_str!("words.txt").lines().collect();
+
+        Self {
+            sim: Simulation::new(world_w, world_h, colors),
+            locks: [Locks::default(); N],
+            appearance: Appearance::default(),
+            play: false,
+            prev_time: Instant::now(),
+            step_time: Duration::ZERO,
+            step_interval: 1000.0 / STEPS_PER_SEC,
+            step_times: VecDeque::new(),
+            frame_times: VecDeque::new(),
+            step_held_since: None,
+            selected_particle: (0, 0),
+            // a handful of types fit on one scroll, but more are easier a type at a time
+            class_tab: (N > 4).then_some(0),
+            force_filter: 0.0,
+            track_selected: false,
+            respawn_mark: None,
+            group: Vec::new(),
+            group_counts: [0; N],
+            group_box: None,
+            bond_tool: false,
+            bond_from: None,
+            bond_stiffness: 0.2,
+            bond_radius: 20.0,
+            selected_param: (0, 0),
+            inspector_open: false,
+            reveal_inspector: false,
+            view: View::new(world_w, world_h),
+            bookmarks: [None; BOOKMARK_SLOTS],
+            smooth_bookmarks: true,
+            glide: None,
+            manual_view: None,
+            follow: Follow::Off,
+            follow_class: 0,
+            prev_follow_pos: None,
+            show_minimap: true,
+            show_panel: true,
+            panel_side: Side::Left,
+            show_grid: false,
+            show_clusters: false,
+            show_flow: true,
+            settle_action: SettleAction::Nothing,
+            tuner: None,
+            tune_goal: TuneGoal::MostClusters,
+            tune_speed: 1.0,
+            tune_steps: TUNE_STEPS,
+            cluster_distance: CLUSTER_DISTANCE,
+            show_radius: false,
+            smooth_motion: true,
+            keep_simulating: false,
+            step_budget: 50.0,
+            auto_limit: false,
+            ns_per_interaction: None,
+            prev_positions: std::array::from_fn(|_| Vec::new()),
+            render_mode: RenderMode::ClassColor,
+            field: DensityField::default(),
+            trail_len: 0,
+            trails: VecDeque::new(),
+            collect_stats: false,
+            stats: VecDeque::with_capacity(STATS_HISTORY),
+            clusters: Vec::new(),
+            cluster_frame: 0,
+            activity: VecDeque::with_capacity(ACTIVITY_HISTORY),
+            activity_frame: 0,
+            distances: None,
+            curve_reverse: false,
+            curve_all: false,
+            curve_drag: None,
+            seed: String::new(),
+            seed_error: None,
+            history: VecDeque::new(),
+            presets: Vec::new(),
+            gallery: preset::gallery(),
+            preset_name: String::new(),
+            mutation_strength: 10.0,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            committed: String::new(),
+            words,
+            #[cfg(not(target_arch = "wasm32"))]
+            window_args: None,
+            view_size: Vec2::splat(INIT_SIZE),
+            image_size: IMAGE_SIZES[0],
+            image_whole_world: true,
+            output_dir: std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            export_status: None,
+            recorder: None,
+            record_status: None,
+            player: None,
+            frame_writer: None,
+            replay: None,
+            replay_secs: 10,
+            replay_size: 480,
+            gif_writer: None,
+            config_path: String::new(),
+            config_warnings: Vec::new(),
+            matrix_path: String::new(),
+            playback_path: String::new(),
+            playback_error: None,
+            toast: None,
+            recovered: None,
+            closing: false,
+            streamer: None,
+            stream_target: "127.0.0.1:9000".to_string(),
+            stream_rate: 30.0,
+            stream_stride: 1,
+            stream_error: None,
+            control: None,
+            control_addr: "127.0.0.1:7878".to_string(),
+            control_error: None,
+            audio: None,
+            audio_device: None,
+            audio_devices: None,
+            audio_error: None,
+            levels: Levels::default(),
+            mappings: [Mapping::default(); ModTarget::ALL.len()],
+        }
+    }
+
+    fn play(&mut self) {
+        self.play = true;
+    }
+
+    fn stop(&mut self) {
+        self.play = false;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn paste_seed(&mut self) -> Result<(), String> {
+        let text = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map_err(|_| "The clipboard doesn't contain any text.".to_string())?;
+        self.apply_seed_text(&text)
+    }
+
+    fn apply_seed_text(&mut self, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("There's no seed to apply.".to_string());
+        }
+
+        self.seed = text.to_string();
+        self.seed_error = None;
+        self.apply_seed();
+        if let Some(err) = self.seed_error.take() {
+            return Err(err);
+        }
+        self.spawn();
+        self.update_history();
+        Ok(())
+    }
+
+    fn update_history(&mut self) {
+        if self.seed.is_empty() {
+            return;
+        }
+        let label = if self.seed.starts_with('@') {
+            let secs = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs());
+            let (h, m, s) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+            format!("Edited {:02}:{:02}:{:02} UTC", h, m, s)
+        } else {
+            // tabs and newlines would break the line format
+            (self.seed.chars())
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect()
+        };
+        self.push_history(label);
+    }
+
+    fn push_history(&mut self, label: String) {
+        let code = self.sim.export();
+        if self.history.front().is_some_and(|e| e.code == code) {
+            return;
+        }
+        self.history.push_front(HistoryEntry { label, code });
+        self.history.truncate(MAX_HISTORY_LEN);
+    }
+
+    fn save_preset(&mut self) {
+        // tabs and newlines would break the line format
+        let name: String = self
+            .preset_name
+            .trim()
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        if name.is_empty() {
+            return;
+        }
+        let preset = Preset {
+            description: String::new(),
+            seed: self.sim.export(),
+            classes: self
+                .sim
+                .params
+                .iter()
+                .map(|p| p.name.replace(',', " "))
+                .collect(),
+            name,
+        };
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    fn load_preset(&mut self, preset: &Preset) {
+        self.seed = preset.seed.clone();
+        if preset.classes.len() == N {
+            for (p, name) in self.sim.params.iter_mut().zip(&preset.classes) {
+                p.name = name.clone();
+            }
+        }
+        self.seed_error = None;
+        self.apply_seed();
+        self.spawn();
+        self.update_history();
+    }
+
+    fn load(&mut self, storage: &dyn Storage) {
+        // browsers close tabs without telling the app, so only native builds can spot a crash
+        let crashed = !cfg!(target_arch = "wasm32")
+            && storage.get_string("clean_exit").as_deref() == Some("false");
+        let session = Session::load(storage);
+        if crashed {
+            self.recovered = Some(session);
+        } else {
+            self.restore_session(session);
+        }
+
+        if let Some(history) = storage.get_string("history") {
+            self.history = history
+                .lines()
+                .filter_map(HistoryEntry::parse)
+                .take(MAX_HISTORY_LEN)
+                .collect();
+        }
+        if let Some(presets) = storage.get_string("presets") {
+            self.presets = presets.lines().filter_map(Preset::parse).collect();
+        }
+        self.appearance = Appearance::load(storage);
+        self.keep_simulating = storage.get_string("keep_simulating").as_deref() == Some("true");
+        if let Some(budget) = storage
+            .get_string("step_budget")
+            .and_then(|b| b.parse().ok())
+        {
+            self.step_budget = f32::clamp(budget, MIN_STEP_BUDGET, MAX_STEP_BUDGET);
+        }
+        self.auto_limit = storage.get_string("auto_limit").as_deref() == Some("true");
+        if let Some(interval) = storage
+            .get_string("step_interval")
+            .and_then(|i| i.parse().ok())
+        {
+            self.step_interval = f32::clamp(interval, MIN_STEP_INTERVAL, MAX_STEP_INTERVAL);
+        }
+        if let Some(bookmarks) = storage.get_string("bookmarks") {
+            for (slot, line) in self.bookmarks.iter_mut().zip(bookmarks.lines()) {
+                *slot = View::parse(line);
+            }
+        }
+        self.smooth_bookmarks = storage.get_string("smooth_bookmarks").as_deref() != Some("false");
+        if storage.get_string("panel_side").as_deref() == Some("right") {
+            self.panel_side = Side::Right;
+        }
+        if let Some(secs) = storage
+            .get_string("replay_secs")
+            .and_then(|s| s.parse().ok())
+        {
+            self.replay_secs = u32::clamp(secs, 1, MAX_REPLAY_SECS);
+        }
+        if let Some(size) = storage
+            .get_string("replay_size")
+            .and_then(|s| s.parse().ok())
+            .filter(|size| REPLAY_SIZES.contains(size))
+        {
+            self.replay_size = size;
+        }
+        // there's nowhere to save the frames on the web
+        if !cfg!(target_arch = "wasm32") && storage.get_string("replay").as_deref() == Some("true")
+        {
+            self.replay = Some(ReplayBuffer::new(self.replay_len()));
+        }
+        if let Some(speed) = storage
+            .get_string("settle_speed")
+            .and_then(|s| s.parse().ok())
+        {
+            self.sim.settle_speed = f32::clamp(speed, 0.0, MAX_SETTLE_SPEED);
+        }
+        if let Some(steps) = storage
+            .get_string("settle_steps")
+            .and_then(|s| s.parse().ok())
+        {
+            self.sim.settle_steps = u32::clamp(steps, 1, MAX_SETTLE_STEPS);
+        }
+        if let Some(action) = storage
+            .get_string("settle_action")
+            .and_then(|a| SettleAction::from_key(&a))
+        {
+            self.settle_action = action;
+        }
+        self.audio_device = storage
+            .get_string("audio_device")
+            .filter(|device| !device.is_empty());
+        for (target, mapping) in ModTarget::ALL.into_iter().zip(&mut self.mappings) {
+            if let Some(saved) = storage
+                .get_string(&format!("modulate_{}", target.key()))
+                .and_then(|m| Mapping::parse(&m))
+            {
+                *mapping = saved;
+            }
+        }
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            seed: Some(self.seed.clone()),
+            state: Some(self.sim.export()),
+            view: Some(self.view),
+        }
+    }
+
+    /// Applies a saved session, respawning rather than restoring the particles.
+    fn restore_session(&mut self, session: Session) {
+        // a missing or unreadable state just leaves the defaults in place
+        let state = session.state;
+        if let Some(bytes) = state.and_then(|s| base64::decode(s.strip_prefix('@')?).ok()) {
+            if self.sim.import(&bytes).is_ok() {
+                self.spawn();
+            }
+        }
+        if let Some(seed) = session.seed {
+            self.seed = seed;
+        }
+        if let Some(view) = session.view {
+            self.view = view;
+        }
+    }
+
+    fn randomize(&mut self) {
+        let number = rand::thread_rng().gen_range(0..SEED_NUMBERS);
+        self.seed = format!(
+            "{}_{}_{:02}",
+            self.random_word(),
+            self.random_word(),
+            number
+        );
+
+        self.apply_seed();
+        self.spawn();
+        self.update_history();
+    }
+
+    fn random_word(&self) -> &'static str {
+        self.words[rand::thread_rng().gen_range(0..self.words.len())]
+    }
+
+    /// How many bits of randomness go into a seed from `Randomize`.
+    fn seed_bits(&self) -> f32 {
+        2.0 * (self.words.len() as f32).log2() + (SEED_NUMBERS as f32).log2()
+    }
+
+    /// Swaps the last word of a word seed for a random one, keeping the rest of it.
+    fn reroll_last_word(&mut self) {
+        let Some(last) = last_word(&self.seed) else {
+            return;
+        };
+        let mut parts: Vec<_> = self.seed.split('_').collect();
+        parts[last] = self.random_word();
+        self.seed = parts.join("_");
+
+        self.apply_seed();
+        self.spawn();
+        self.update_history();
+    }
+
+    fn reset_view(&mut self) {
+        self.view = View::new(self.sim.world_w, self.sim.world_h);
+        self.follow = Follow::Off;
+        self.manual_view = None;
+        self.glide = None;
+    }
+
+    /// Glides to a view that frames the whole world.
+    fn fit_view(&mut self) {
+        let world = Vec2::new(self.sim.world_w, self.sim.world_h);
+        let zoom = (self.view_size.x / world.x).min(self.view_size.y / world.y) * FIT_MARGIN;
+        self.follow = Follow::Off;
+        self.manual_view = None;
+        self.glide_to(View {
+            pos: world / 2.0,
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        });
+    }
+
+    fn glide_to(&mut self, view: View) {
+        self.glide = Some((self.view, view, Instant::now()));
+    }
+
+    /// Switches what the view follows, gliding back to where it was before when following
+    /// is turned off.
+    fn set_follow(&mut self, follow: Follow) {
+        if follow == Follow::Off {
+            if let Some(view) = self.manual_view.take() {
+                self.glide_to(view);
+            }
+        } else if self.follow == Follow::Off {
+            self.manual_view = Some(self.view);
+            self.glide = None;
+        }
+        self.follow = follow;
+        self.prev_follow_pos = None;
+    }
+
+    fn store_bookmark(&mut self, slot: usize) {
+        self.bookmarks[slot] = Some(self.view);
+        self.toast = Some((
+            Ok(format!("Saved the view as bookmark {}", slot + 1)),
+            Instant::now(),
+        ));
+    }
+
+    fn recall_bookmark(&mut self, slot: usize) {
+        let Some(view) = self.bookmarks[slot] else {
+            return;
+        };
+        // otherwise following would pull the view straight back
+        self.follow = Follow::Off;
+        self.manual_view = None;
+        self.prev_follow_pos = None;
+        if self.smooth_bookmarks {
+            self.glide_to(view);
+        } else {
+            self.view = view;
+        }
+    }
+
+    fn update_glide(&mut self, ctx: &Context) {
+        let Some((from, to, start)) = self.glide else {
+            return;
+        };
+        let t = start.elapsed().as_secs_f32() / VIEW_GLIDE_TIME;
+        if t >= 1.0 {
+            self.view = to;
+            self.glide = None;
+        } else {
+            // ease out, so it settles gently
+            let t = 1.0 - (1.0 - t) * (1.0 - t);
+            self.view = from.lerp(to, t);
+            ctx.request_repaint();
+        }
+    }
+
+    fn follow_pos(&self) -> Option<Vec2> {
+        fn centroid<'a>(dots: impl Iterator<Item = &'a Dot>) -> Option<Vec2> {
+            let (sum, count) = dots.fold((Vec2::ZERO, 0), |(sum, n), dot| (sum + dot.pos, n + 1));
+            (count > 0).then(|| sum / count as f32)
+        }
+        match self.follow {
+            Follow::Off => None,
+            Follow::SelectedParticle => {
+                let (class, index) = self.selected_particle;
+                self.sim.dots[class].get(index).map(|dot| dot.pos)
+            }
+            Follow::ClassCenterOfMass => centroid(self.sim.dots[self.follow_class].iter()),
+            Follow::GlobalCenterOfMass => centroid(self.sim.dots.iter().flatten()),
+            Follow::GroupCenter => centroid(self.group_dots()),
+        }
+    }
+
+    fn group_dots(&self) -> impl Iterator<Item = &Dot> + '_ {
+        (self.group.iter()).filter_map(|&(class, index)| self.sim.dots[class].get(index))
+    }
+
+    /// Picks every particle inside a box with corners `a` and `b` in the world.
+    fn select_group(&mut self, a: Vec2, b: Vec2) {
+        let rect = Rect::from_two_pos(a.to_pos2(), b.to_pos2());
+        self.group = (self.sim.dots.iter().enumerate())
+            .flat_map(|(i, dots)| {
+                let inside = dots
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, dot)| rect.contains(dot.pos.to_pos2()));
+                inside.map(move |(j, _)| (i, j))
+            })
+            .collect();
+        self.group_counts = self.sim.dots.each_ref().map(Vec::len);
+        if !self.group.is_empty() {
+            self.reveal_inspector = !self.inspector_open;
+        }
+    }
+
+    /// The particle nearest to `pos` in the world, if any are within `max_dist`.
+    fn particle_at(&self, pos: Vec2, max_dist: f32) -> Option<(usize, usize)> {
+        let nearest = (self.sim.dots.iter().enumerate())
+            .flat_map(|(i, dots)| {
+                dots.iter()
+                    .enumerate()
+                    .map(move |(j, dot)| ((i, j), (dot.pos - pos).length_sq()))
+            })
+            .filter(|&(_, dist)| dist <= max_dist * max_dist)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        nearest.map(|(dot, _)| dot)
+    }
+
+    /// The index of the bond nearest to `pos` in the world, if any are within `max_dist`.
+    fn bond_at(&self, pos: Vec2, max_dist: f32) -> Option<usize> {
+        let dot = |(class, index): (usize, usize)| self.sim.dots[class].get(index);
+        let nearest = (self.sim.bonds().iter().enumerate())
+            .filter_map(|(k, bond)| {
+                let (a, b) = (dot(bond.a)?.pos, dot(bond.b)?.pos);
+                let along = (b - a).length_sq().max(f32::EPSILON);
+                let t = ((pos - a).dot(b - a) / along).clamp(0.0, 1.0);
+                Some((k, (a + (b - a) * t - pos).length_sq()))
+            })
+            .filter(|&(_, dist)| dist <= max_dist * max_dist)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        nearest.map(|(k, _)| k)
+    }
+
+    /// Bonds the clicked particle to the one clicked before it, or removes the clicked bond.
+    fn bond_click(&mut self, pos: Vec2, max_dist: f32) {
+        let counts = self.sim.dots.each_ref().map(Vec::len);
+        match (self.particle_at(pos, max_dist), self.bond_from.take()) {
+            (Some(dot), Some((from, _))) if dot != from => {
+                self.sim.bond(from, dot, self.bond_stiffness);
+            }
+            // clicking the first particle again lets go of it
+            (Some(_), Some(_)) => {}
+            (Some(dot), None) => self.bond_from = Some((dot, counts)),
+            (None, _) => {
+                if let Some(k) = self.bond_at(pos, max_dist) {
+                    self.sim.unbond(k);
+                }
+            }
+        }
+    }
+
+    fn bonds_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.bond_tool, "Bond Tool")
+                .on_hover_text("Click two particles to bond them, or click a bond to remove it");
+            if self.bond_tool && self.bond_from.is_some() {
+                ui.label("Now click another particle");
+            }
+        });
+        if !self.bond_tool {
+            self.bond_from = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Stiffness:");
+            ui.add(Slider::new(&mut self.bond_stiffness, 0.0..=MAX_STIFFNESS))
+                .on_hover_text("Only changes new bonds");
+        });
+        ui.horizontal(|ui| {
+            let bond_group = ui
+                .add_enabled(!self.group.is_empty(), Button::new("Bond Group"))
+                .on_hover_text("Bonds every pair in the selected group closer than this")
+                .on_disabled_hover_text("Shift-drag to select a group of particles first");
+            ui.add(Slider::new(&mut self.bond_radius, 1.0..=MAX_RADIUS).logarithmic(true));
+            if bond_group.clicked() {
+                let count =
+                    (self.sim).bond_within(&self.group, self.bond_radius, self.bond_stiffness);
+                let msg = format!("Added {} bonds", count);
+                self.toast = Some((Ok(msg), Instant::now()));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(format!("{} bonds", self.sim.bonds().len()));
+            if ui.button("Clear Bonds").clicked() {
+                self.sim.clear_bonds();
+            }
+        });
+    }
+
+    fn replay_ui(&mut self, ui: &mut Ui) {
+        let mut buffering = self.replay.is_some();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut buffering, "Replay Buffer").on_hover_text(
+                "Keeps the last few seconds of frames, in case something happens that's \
+                 worth saving. It costs memory and a small render every few steps.",
+            );
+            if let Some(replay) = &self.replay {
+                let secs = replay.frames() as f32 / REPLAY_FPS as f32;
+                let mb = replay.bytes() as f32 / 1_000_000.0;
+                ui.label(format!("{:.1}s, {:.0} MB", secs, mb));
+            }
+        });
+        if buffering != self.replay.is_some() {
+            self.replay = buffering.then(|| ReplayBuffer::new(self.replay_len()));
+        }
+        ui.add_enabled_ui(buffering, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Length:");
+                let length = Slider::new(&mut self.replay_secs, 1..=MAX_REPLAY_SECS).suffix("s");
+                if ui.add(length).changed() {
+                    let len = self.replay_len();
+                    self.replay
+                        .iter_mut()
+                        .for_each(|replay| replay.set_len(len));
+                }
+                ComboBox::from_id_source("replay_size")
+                    .selected_text(format!("{}px", self.replay_size))
+                    .show_ui(ui, |ui| {
+                        for size in REPLAY_SIZES {
+                            let label = format!("{}px", size);
+                            ui.selectable_value(&mut self.replay_size, size, label);
+                        }
+                    });
+            });
+        });
+        ui.horizontal(|ui| {
+            let has_frames = self.replay.as_ref().is_some_and(|r| r.frames() > 0);
+            let label = format!("Save Last {}s as GIF", self.replay_secs);
+            if ui
+                .add_enabled(has_frames && self.gif_writer.is_none(), Button::new(label))
+                .clicked()
+            {
+                self.save_replay();
+            }
+            if let Some(writer) = &self.gif_writer {
+                ui.add(ProgressBar::new(writer.progress()).show_percentage());
+            }
+        });
+    }
+
+    /// Sums up the particles picked with a box.
+    fn group_ui(&mut self, ui: &mut Ui) {
+        if self.group.is_empty() {
+            ui.label("Shift-drag to select a group of particles.");
+            return;
+        }
+        let mut counts = [0; N];
+        let (mut sum, mut speed) = (Vec2::ZERO, 0.0);
+        // the counts may have changed since the group was last checked against them
+        for &(class, index) in &self.group {
+            let Some(dot) = self.sim.dots[class].get(index) else {
+                continue;
+            };
+            counts[class] += 1;
+            sum += dot.pos;
+            speed += dot.vel.length();
+        }
+        let total = counts.iter().sum::<usize>().max(1) as f32;
+        let centroid = sum / total;
+        let radius = (self.group_dots())
+            .map(|dot| (dot.pos - centroid).length())
+            .fold(0.0, f32::max);
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Group: {} particles", self.group.len()));
+            if ui.button("Clear").clicked() {
+                self.group.clear();
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            for (p, count) in self.sim.params.iter().zip(counts) {
+                if count > 0 {
+                    ui.colored_label(p.color, format!("{}: {}", p.name, count));
+                }
+            }
+        });
+        ui.label(format!("Center: ({:.1}, {:.1})", centroid.x, centroid.y));
+        ui.label(format!("Mean Speed: {:.2}", speed / total));
+        ui.label(format!("Radius: {:.1}", radius));
+        let mut following = self.follow == Follow::GroupCenter;
+        if ui.checkbox(&mut following, "Follow Group Center").changed() {
+            self.set_follow(if following {
+                Follow::GroupCenter
+            } else {
+                Follow::Off
+            });
+        }
+    }
+
+    /// Lets the selected pair's power and radius be dragged on the force plot. `edge` is where
+    /// the radius is on screen, and `delta` how far the pointer moved in plot units.
+    fn drag_curve(&mut self, ui: &Ui, resp: Response, (edge, delta): (f32, Vec2), x_max: f32) {
+        let (i, j) = self.selected_param;
+        let near_edge = |pos: Pos2| (pos.x - edge).abs() < CURVE_GRAB;
+        let editable = self.sim.params[i].enabled[j];
+        if let Some(pos) = resp.hover_pos().filter(|_| editable) {
+            ui.output().cursor_icon = match self.curve_drag {
+                Some(CurveDrag::Radius { .. }) => CursorIcon::ResizeHorizontal,
+                Some(CurveDrag::Power) => CursorIcon::ResizeVertical,
+                None if near_edge(pos) => CursorIcon::ResizeHorizontal,
+                None if pos.x < edge => CursorIcon::ResizeVertical,
+                None => CursorIcon::Default,
+            };
+        }
+
+        if resp.drag_started() && editable {
+            self.curve_drag = match resp.interact_pointer_pos() {
+                Some(pos) if near_edge(pos) => Some(CurveDrag::Radius { x_max }),
+                Some(pos) if pos.x < edge => Some(CurveDrag::Power),
+                _ => None,
+            };
+        }
+        let (p, locks) = (&mut self.sim.params[i], &self.locks[i]);
+        let scale = self.sim.physics.force_scale;
+        match self.curve_drag {
+            // the curve's height is the power scaled by the force scale
+            Some(CurveDrag::Power) if !locks.power[j] && scale > 0.0 => {
+                p.power[j] = (p.power[j] + delta.y * 100.0 / scale).clamp(MIN_POWER, MAX_POWER);
+            }
+            Some(CurveDrag::Radius { .. }) if !locks.radius[j] => {
+                p.radius[j] = (p.radius[j] + delta.x).clamp(MIN_RADIUS, MAX_RADIUS);
+            }
+            _ => {}
+        }
+        if resp.drag_released() && self.curve_drag.take().is_some() {
+            self.seed = self.sim.export();
+        }
+    }
+
+    /// Lists the attractors, which change the seed whenever they're edited.
+    fn attractors_ui(&mut self, ui: &mut Ui) {
+        let (world_w, world_h) = (self.sim.world_w, self.sim.world_h);
+        let mut changed = false;
+        let mut remove = None;
+        for (k, a) in self.sim.attractors.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("Attractor {}", k + 1));
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    remove = Some(k);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Strength:");
+                changed |= ui
+                    .add(Slider::new(
+                        &mut a.strength,
+                        -MAX_ATTRACTOR_STRENGTH..=MAX_ATTRACTOR_STRENGTH,
+                    ))
+                    .on_hover_text("Negative strengths push particles away")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Radius:");
+                changed |= ui
+                    .add(Slider::new(&mut a.radius, 0.0..=MAX_RADIUS))
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("X:");
+                changed |= ui.add(Slider::new(&mut a.pos.x, 0.0..=world_w)).changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Y:");
+                changed |= ui.add(Slider::new(&mut a.pos.y, 0.0..=world_h)).changed();
+            });
+            ui.separator();
+        }
+        if let Some(k) = remove {
+            self.sim.attractors.remove(k);
+            changed = true;
+        }
+        let can_add = self.sim.attractors.len() < MAX_ATTRACTORS;
+        if ui
+            .add_enabled(can_add, Button::new("Add at View Center"))
+            .clicked()
+        {
+            self.sim.attractors.push(Attractor {
+                pos: self.view.pos,
+                ..Attractor::default()
+            });
+            changed = true;
+        }
+        if changed {
+            self.seed = self.sim.export();
+        }
+    }
+
+    /// Lists the reaction rules, which change the seed whenever they're edited.
+    fn reactions_ui(&mut self, ui: &mut Ui) {
+        let names: Vec<_> = self.sim.params.iter().map(|p| p.name.clone()).collect();
+        let mut changed = false;
+        let mut remove = None;
+        for (r, rule) in self.sim.reactions.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= class_combo(ui, ("reaction_a", r), &names, &mut rule.a);
+                ui.label("near");
+                changed |= class_combo(ui, ("reaction_b", r), &names, &mut rule.b);
+                ui.label("becomes");
+                changed |= class_combo(ui, ("reaction_result", r), &names, &mut rule.result);
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    remove = Some(r);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Within:");
+                changed |= ui
+                    .add(Slider::new(&mut rule.radius, 0.0..=MAX_REACTION_RADIUS))
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("For:");
+                changed |= ui
+                    .add(Slider::new(&mut rule.steps, 0..=MAX_REACTION_STEPS).suffix(" steps"))
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Chance:");
+                changed |= ui
+                    .add(Slider::new(&mut rule.chance, 0.0..=1.0))
+                    .on_hover_text("Chance of converting on each step after that")
+                    .changed();
+            });
+            ui.separator();
+        }
+        if let Some(r) = remove {
+            self.sim.reactions.remove(r);
+            changed = true;
+        }
+        let can_add = self.sim.reactions.len() < MAX_REACTIONS;
+        if ui
+            .add_enabled(can_add, Button::new("Add Reaction"))
+            .clicked()
+        {
+            self.sim.reactions.push(Reaction {
+                b: 1 % N,
+                result: 1 % N,
+                ..Reaction::default()
+            });
+            changed = true;
+        }
+        if changed {
+            self.seed = self.sim.export();
+        }
+    }
+
+    /// The basic controls, floating over the view while the settings are hidden.
+    fn panel_strip(&mut self, ctx: &Context) {
+        let (align, offset) = match self.panel_side {
+            Side::Left => (Align2::LEFT_TOP, Vec2::new(10.0, 10.0)),
+            Side::Right => (Align2::RIGHT_TOP, Vec2::new(-10.0, 10.0)),
+        };
+        Area::new("panel_strip")
+            .anchor(align, offset)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Settings").on_hover_text("Tab").clicked() {
+                            self.show_panel = true;
+                        }
+                        let play = if self.play { "Pause" } else { "Play" };
+                        if ui.button(play).clicked() {
+                            if self.play {
+                                self.stop();
+                            } else {
+                                self.play();
+                            }
+                        }
+                        if ui.button("Respawn").clicked() {
+                            self.spawn();
+                        }
+                    });
+                });
+            });
+    }
+
+    fn zoom_by(&mut self, factor: f32) {
+        self.view.zoom = (self.view.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.glide = None;
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        // don't steal keys that are meant for the seed box
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let input = ctx.input();
+        let zoom_in = input
+            .events
+            .iter()
+            .any(|e| matches!(e, Event::Text(t) if t == "+" || t == "="));
+        let zoom_out = input
+            .events
+            .iter()
+            .any(|e| matches!(e, Event::Text(t) if t == "-"));
+        let space = input.key_pressed(Key::Space);
+        let respawn = input.key_pressed(Key::R);
+        let randomize = input.key_pressed(Key::N);
+        let home = input.key_pressed(Key::Home);
+        let tab = input.key_pressed(Key::Tab);
+        let step = input.key_pressed(Key::ArrowRight);
+        let undo = input.modifiers.command && input.key_pressed(Key::Z);
+        let shift = input.modifiers.shift;
+        let command = input.modifiers.command;
+        let bookmark = BOOKMARK_KEYS.iter().position(|&key| input.key_pressed(key));
+        let paste = input.events.iter().find_map(|e| match e {
+            Event::Paste(text) => Some(text.clone()),
+            _ => None,
+        });
+        drop(input);
+
+        if let Some(text) = paste {
+            self.seed_error = self.apply_seed_text(&text).err();
+        }
+
+        if undo && shift {
+            self.redo();
+        } else if undo {
+            self.undo();
+        }
+
+        if space {
+            if self.play {
+                self.stop();
+            } else {
+                self.play();
+            }
+        }
+        if respawn {
+            self.spawn();
+        }
+        if step && !self.play {
+            self.step();
+        }
+        if randomize {
+            self.randomize();
+        }
+        if home {
+            self.reset_view();
+        }
+        if tab {
+            self.show_panel = !self.show_panel;
+        }
+        if zoom_in {
+            self.zoom_by(ZOOM_STEP);
+        }
+        if zoom_out {
+            self.zoom_by(1.0 / ZOOM_STEP);
+        }
+        match bookmark {
+            Some(slot) if command => self.store_bookmark(slot),
+            Some(slot) => self.recall_bookmark(slot),
+            None => {}
+        }
+    }
+
+    /// Records the parameters as an undo step once they've changed and the mouse is
+    /// released, so a slider drag only takes up a single step.
+    fn track_changes(&mut self, ctx: &Context) {
+        if ctx.input().pointer.any_down() {
+            return;
+        }
+        let current = self.sim.export();
+        if self.committed.is_empty() {
+            self.committed = current;
+        } else if current != self.committed {
+            if self.undo.len() == MAX_UNDO {
+                self.undo.pop_front();
+            }
+            self.undo
+                .push_back(std::mem::replace(&mut self.committed, current));
+            self.redo.clear();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(code) = self.undo.pop_back() {
+            let current = std::mem::replace(&mut self.committed, code.clone());
+            self.redo.push(current);
+            self.restore(&code);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(code) = self.redo.pop() {
+            let current = std::mem::replace(&mut self.committed, code.clone());
+            self.undo.push_back(current);
+            self.restore(&code);
+        }
+    }
+
+    fn restore(&mut self, code: &str) {
+        let counts: [usize; N] = std::array::from_fn(|i| self.sim.params[i].count);
+        // undo snapshots come from `export`, so they always decode
+        if let Ok(bytes) = base64::decode(&code[1..]) {
+            let _ = self.sim.import(&bytes);
+        }
+        self.seed = code.to_string();
+        // only respawn when the dots no longer match the counts
+        if self
+            .sim
+            .params
+            .iter()
+            .zip(counts)
+            .any(|(p, count)| p.count != count)
+        {
+            self.spawn();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.sim.world_w = INIT_SIZE;
+        self.sim.world_h = INIT_SIZE;
+        self.sim.spawn_radius = self.sim.max_spawn_radius();
+        self.sim.spawn_rings = false;
+        self.sim.border = BorderBehavior::Soft;
+        self.sim.restitution = 1.0;
+        self.sim.physics = Physics::default();
+        self.sim.integrator = Integrator::SemiImplicitEuler;
+        for p in &mut self.sim.params {
+            p.count = 0;
+            p.radius.iter_mut().for_each(|r| *r = 0.0);
+            p.power.iter_mut().for_each(|p| *p = 0.0);
+            p.spin.iter_mut().for_each(|s| *s = 0.0);
+            p.enabled = [true; N];
+            p.lifetime = 0.0;
+            p.death = Death::default();
+        }
+        // an empty script always parses, and goes back to the builtin force
+        let _ = self.sim.set_force_script("");
+        self.sim.reactions.clear();
+        self.sim.attractors.clear();
+        self.sim.flow = Flow::default();
+
+        // the counts are all zero, so this clears away the old particles and their stats
+        self.spawn();
+        self.seed = self.sim.export();
+        self.seed_error = None;
+        self.selected_particle = (0, 0);
+        self.selected_param = (0, 0);
+    }
+
+    fn spawn(&mut self) {
+        // derived from the seed, so sharing a seed and a frame number shares the exact picture
+        self.spawn_with(&mut SmallRng::seed_from_u64(self.sim.spawn_seed()));
+    }
+
+    fn spawn_with(&mut self, rand: &mut impl Rng) {
+        let (class, index) = self.selected_particle;
+        let last_pos = (self.sim.dots[class].get(index))
+            .filter(|_| self.track_selected)
+            .map(|dot| dot.pos);
+        self.sim.clear();
+        self.stats.clear();
+        self.clusters.clear();
+        self.cluster_frame = 0;
+        self.activity.clear();
+        self.activity_frame = 0;
+        self.distances = None;
+        self.group.clear();
+        self.bond_from = None;
+        self.trails.clear();
+        self.prev_positions.iter_mut().for_each(Vec::clear);
+        self.update_count_scale();
+        self.sim.spawn_with(rand);
+
+        if let Some(last_pos) = last_pos {
+            let nearest = (self.sim.dots[class].iter().enumerate())
+                .min_by(|(_, a), (_, b)| {
+                    let (a, b) = (
+                        (a.pos - last_pos).length_sq(),
+                        (b.pos - last_pos).length_sq(),
+                    );
+                    a.total_cmp(&b)
+                })
+                .map(|(i, _)| i);
+            if let Some(nearest) = nearest {
+                self.selected_particle = (class, nearest);
+            }
+            self.respawn_mark = Some((last_pos, Instant::now()));
+        }
+    }
+
+    fn requested_total(&self) -> usize {
+        self.sim.params.iter().map(|p| p.count).sum()
+    }
+
+    /// How long a step with the requested counts would take, going by the recent steps.
+    fn estimated_step_ms(&self) -> Option<f64> {
+        let interactions = self.sim.interactions_per_step(self.requested_total());
+        Some(self.ns_per_interaction? * interactions as f64 / 1e6)
+    }
+
+    /// How much auto-limit would scale the requested counts down to fit the step budget.
+    fn limited_scale(&self) -> f32 {
+        match (self.auto_limit, self.estimated_step_ms()) {
+            // interactions grow with the square of the counts
+            (true, Some(ms)) if ms > self.step_budget as f64 => {
+                (self.step_budget as f64 / ms).sqrt() as f32
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Applies the auto-limit, returning whether the particles need to be fitted to it.
+    fn update_count_scale(&mut self) -> bool {
+        let scale = self.limited_scale();
+        let changed = scale != self.sim.count_scale;
+        self.sim.count_scale = scale;
+        changed
+    }
+
+    fn apply_seed(&mut self) {
+        // a new seed takes over from whatever auto-tuning was working on
+        self.tuner = None;
+        self.sim.clear();
+
+        let rand = if self.seed.is_empty() {
+            SmallRng::from_entropy()
+        } else {
+            if let Some(code) = self.seed.strip_prefix('@') {
+                // keep the current setup rather than applying part of a broken code
+                let result = base64::decode(code)
+                    .map_err(|_| SeedError::InvalidBase64)
+                    .and_then(|bytes| self.sim.import(&bytes));
+                if let Err(err) = result {
+                    self.seed_error = Some(err.to_string());
+                }
+                return;
+            }
+            let mut hasher = DefaultHasher::new();
+            self.seed.hash(&mut hasher);
+            SmallRng::seed_from_u64(hasher.finish())
+        };
+        let mut rand = uniform(rand);
+
+        for i in 0..N {
+            self.roll_count(i, &mut rand);
+            self.roll_forces(i, &mut rand);
+        }
+
+        // spin is rolled last so older seeds keep their counts, powers and radii
+        self.roll_spins(&mut rand);
+    }
+
+    // locked values still draw from `rand`, so they don't shift the rest of a seed
+    fn roll_count(&mut self, i: usize, rand: &mut impl FnMut(f32, f32) -> f32) {
+        let count = rand(MIN_COUNT as f32, MAX_COUNT as f32) as usize;
+        if !self.locks[i].count {
+            self.sim.params[i].count = count;
+        }
+    }
+
+    fn roll_forces(&mut self, i: usize, rand: &mut impl FnMut(f32, f32) -> f32) {
+        for j in 0..N {
+            self.roll_force(i, j, rand);
+        }
+    }
+
+    fn roll_force(&mut self, i: usize, j: usize, rand: &mut impl FnMut(f32, f32) -> f32) {
+        const POW_F: f32 = 1.25;
+        const RAD_F: f32 = 1.1;
+
+        let locks = self.locks[i];
+        let pow = rand(MIN_POWER, MAX_POWER);
+        if !locks.power[j] {
+            self.sim.params[i].power[j] = if pow >= 0.0 {
+                pow.powf(1.0 / POW_F)
+            } else {
+                -pow.abs().powf(1.0 / POW_F)
+            };
+        }
+        //self.sim.params[i].power[j] = rand(MIN_POWER, MAX_POWER);
+        let radius = rand(MIN_RADIUS, MAX_RADIUS).powf(1.0 / RAD_F);
+        if !locks.radius[j] {
+            self.sim.params[i].radius[j] = radius;
+        }
+    }
+
+    fn roll_spins(&mut self, rand: &mut impl FnMut(f32, f32) -> f32) {
+        for i in 0..N {
+            for j in 0..N {
+                self.roll_spin(i, j, rand);
+            }
+        }
+    }
+
+    fn roll_spin(&mut self, i: usize, j: usize, rand: &mut impl FnMut(f32, f32) -> f32) {
+        const SPIN_F: f32 = 4.0;
+
+        let spin = rand(MIN_SPIN, MAX_SPIN) / SPIN_F;
+        if !self.locks[i].spin[j] {
+            self.sim.params[i].spin[j] = spin;
+        }
+    }
+
+    fn randomize_forces(&mut self) {
+        let mut rand = uniform(SmallRng::from_entropy());
+        for i in 0..N {
+            self.roll_forces(i, &mut rand);
+        }
+        self.roll_spins(&mut rand);
+        self.seed = self.sim.export();
+        self.update_history();
+    }
+
+    /// Rolls new forces between type `i` and every type, both ways, and a new count for it,
+    /// leaving the rest of the setup alone. Locked values stay as they are.
+    fn randomize_class(&mut self, i: usize) {
+        let mut rand = uniform(SmallRng::from_entropy());
+        let prev = self.sim.params[i].count;
+        for j in 0..N {
+            self.roll_force(i, j, &mut rand);
+            self.roll_spin(i, j, &mut rand);
+            if j != i {
+                self.roll_force(j, i, &mut rand);
+                self.roll_spin(j, i, &mut rand);
+            }
+        }
+        self.roll_count(i, &mut rand);
+        self.seed = self.sim.export();
+        self.update_history();
+        if self.sim.params[i].count != prev {
+            self.spawn();
+        }
+    }
+
+    fn mutate(&mut self) {
+        self.mutate_params(&mut SmallRng::from_entropy());
+        self.seed = self.sim.export();
+        self.spawn();
+        self.update_history();
+    }
+
+    fn mutate_params(&mut self, rand: &mut impl Rng) {
+        let strength = self.mutation_strength / 100.0;
+
+        // only touch about half of the cells so the result stays recognizable
+        for i in 0..N {
+            let p = &mut self.sim.params[i];
+            let locks = &self.locks[i];
+            for j in 0..N {
+                if rand.gen_bool(0.5) {
+                    let delta = rand.gen_range(-strength..=strength) * (MAX_POWER - MIN_POWER);
+                    if !locks.power[j] {
+                        p.power[j] = (p.power[j] + delta).clamp(MIN_POWER, MAX_POWER);
+                    }
+                    let delta = rand.gen_range(-strength..=strength) * (MAX_RADIUS - MIN_RADIUS);
+                    if !locks.radius[j] {
+                        p.radius[j] = (p.radius[j] + delta).clamp(MIN_RADIUS, MAX_RADIUS);
+                    }
+                }
+            }
+            let count = p.count as f32 * (1.0 + rand.gen_range(-strength..=strength));
+            if !locks.count {
+                p.count = (count.round() as usize).clamp(MIN_COUNT, MAX_COUNT);
+            }
+        }
+    }
+
+    fn start_tuning(&mut self) {
+        self.tuner = Some(Tuner {
+            goal: self.tune_goal,
+            best: self.sim.params.clone(),
+            best_score: None,
+            steps: 0,
+            score_sum: 0.0,
+            samples: 0,
+            tries: 0,
+            accepted: Vec::new(),
+        });
+        self.spawn();
+        self.play();
+    }
+
+    /// Stops auto-tuning and goes back to the best setup it found.
+    fn stop_tuning(&mut self) {
+        let Some(tuner) = self.tuner.take() else {
+            return;
+        };
+        self.sim.params = tuner.best;
+        self.seed = self.sim.export();
+        self.spawn();
+    }
+
+    /// How well the simulation is doing at the goal right now, where higher is better.
+    fn tune_score(&self, goal: TuneGoal) -> f32 {
+        match goal {
+            TuneGoal::MostClusters => {
+                let (w, h) = (self.sim.world_w, self.sim.world_h);
+                find_clusters(&self.sim.dots, self.cluster_distance, w, h).len() as f32
+            }
+            TuneGoal::TargetSpeed => -(self.sim.motion() - self.tune_speed).abs(),
+        }
+    }
+
+    /// Moves auto-tuning along by a step, starting the next try once this one is measured.
+    fn update_tuner(&mut self) {
+        let Some(mut tuner) = self.tuner.take() else {
+            return;
+        };
+        tuner.steps += 1;
+        let settle = self.tune_steps / 2;
+        if tuner.steps > settle && (tuner.steps - settle).is_multiple_of(TUNE_SAMPLE_INTERVAL) {
+            tuner.score_sum += self.tune_score(tuner.goal);
+            tuner.samples += 1;
+        }
+        if tuner.steps >= self.tune_steps {
+            let score = tuner.score_sum / tuner.samples.max(1) as f32;
+            match tuner.best_score {
+                Some(best) if score <= best => self.sim.params = tuner.best.clone(),
+                best => {
+                    if best.is_some() {
+                        tuner.accepted.push((tuner.tries, score));
+                        self.seed = self.sim.export();
+                        self.push_history(format!("Tuned, {}", tuner.goal.describe(score)));
+                    }
+                    tuner.best = self.sim.params.clone();
+                    tuner.best_score = Some(score);
+                }
+            }
+            tuner.tries += 1;
+            tuner.steps = 0;
+            tuner.score_sum = 0.0;
+            tuner.samples = 0;
+            self.mutate_params(&mut SmallRng::from_entropy());
+            // every try starts from the same kind of spawn, so they're scored fairly
+            self.spawn();
+        }
+        self.tuner = Some(tuner);
+    }
+
+    fn randomize_counts(&mut self) {
+        let mut rand = uniform(SmallRng::from_entropy());
+        let prev: [usize; N] = std::array::from_fn(|i| self.sim.params[i].count);
+        for i in 0..N {
+            self.roll_count(i, &mut rand);
+        }
+        self.seed = self.sim.export();
+        self.update_history();
+        if self
+            .sim
+            .params
+            .iter()
+            .zip(prev)
+            .any(|(p, count)| p.count != count)
+        {
+            self.spawn();
+        }
+    }
+
+    fn step(&mut self) {
+        for (prev, dots) in self.prev_positions.iter_mut().zip(&self.sim.dots) {
+            prev.clear();
+            prev.extend(dots.iter().map(|dot| dot.pos));
+        }
+        let time = Instant::now();
+        let was_settled = self.sim.settled();
+        self.sim.modulation = self.modulation();
+        self.sim.step();
+        self.step_time = time.elapsed();
+        push_time(&mut self.step_times, time);
+        if self.sim.settled() && !was_settled {
+            self.on_settled();
+        }
+
+        let total = self.sim.dots.iter().map(Vec::len).sum();
+        let interactions = self.sim.interactions_per_step(total);
+        // small simulations are mostly overhead, which would make big ones look cheap
+        if interactions >= 100_000 {
+            let sample = self.step_time.as_nanos() as f64 / interactions as f64;
+            self.ns_per_interaction = Some(match self.ns_per_interaction {
+                Some(avg) => avg * 0.9 + sample * 0.1,
+                None => sample,
+            });
+        }
+        // catches counts that were too big before there were any steps to estimate from,
+        // but only ever shrinks them, since the average may still be catching up
+        let slow = self.step_time.as_secs_f32() * 1000.0 > self.step_budget * BUDGET_SLACK;
+        let scale = self.limited_scale();
+        if slow && scale < self.sim.count_scale {
+            self.sim.count_scale = scale;
+            self.sim.fit_counts(&mut SmallRng::from_entropy());
+        }
+        if self.collect_stats || self.show_clusters {
+            self.update_clusters();
+        }
+        if self.collect_stats {
+            self.record_stats();
+        }
+        self.update_tuner();
+        self.record_activity();
+        self.record_trails();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push(&self.sim.dots);
+        }
+        if let Some(img) = self.frame_writer.is_some().then(|| self.render_image()) {
+            self.frame_writer.as_mut().unwrap().push(img);
+        }
+        let stride = (STEPS_PER_SEC as u32 / REPLAY_FPS) as u64;
+        let due = self.replay.is_some() && self.sim.steps().is_multiple_of(stride);
+        if let Some(img) = due.then(|| self.render_image_at(self.replay_size)) {
+            self.replay.as_mut().unwrap().push(img);
+        }
+        if let Some(streamer) = &mut self.streamer {
+            let world = Vec2::new(self.sim.world_w, self.sim.world_h);
+            streamer.push(&self.sim.dots, world, self.stream_rate, self.stream_stride);
+        }
+    }
+
+    fn on_settled(&mut self) {
+        match self.settle_action {
+            SettleAction::Nothing => {}
+            SettleAction::Pause => {
+                self.stop();
+                self.toast = Some((Ok("Settled, paused".to_string()), Instant::now()));
+            }
+            SettleAction::NextSeed => self.randomize(),
+        }
+    }
+
+    fn record_activity(&mut self) {
+        // keep one sample every few steps so the buffer spans about 30 seconds
+        self.activity_frame += 1;
+        if !self.activity_frame.is_multiple_of(ACTIVITY_INTERVAL) {
+            return;
+        }
+        let speeds = self.sim.dots.each_ref().map(|dots| {
+            let sum: f32 = dots.iter().map(|dot| dot.vel.length()).sum();
+            sum / dots.len().max(1) as f32
+        });
+        if self.activity.len() == ACTIVITY_HISTORY {
+            self.activity.pop_front();
+        }
+        self.activity.push_back((self.sim.time(), speeds));
+    }
+
+    fn record_trails(&mut self) {
+        if self.trail_len == 0 {
+            self.trails.clear();
+            return;
+        }
+
+        // recycle the oldest snapshot's buffers once the trail is full
+        let mut snapshot = if self.trails.len() >= self.trail_len {
+            self.trails.pop_front().unwrap()
+        } else {
+            std::array::from_fn(|_| Vec::new())
+        };
+        while self.trails.len() >= self.trail_len {
+            self.trails.pop_front();
+        }
+        for (trail, dots) in snapshot.iter_mut().zip(&self.sim.dots) {
+            trail.clear();
+            trail.extend(dots.iter().map(|dot| dot.pos));
+        }
+        self.trails.push_back(snapshot);
+    }
+
+    fn record_stats(&mut self) {
+        let mut stats = SimStats {
+            time: self.sim.time(),
+            ..SimStats::default()
+        };
+        let mut count = 0;
+        for dot in self.sim.dots.iter().flatten() {
+            let speed = dot.vel.length();
+            stats.mean_speed += speed;
+            stats.max_speed = stats.max_speed.max(speed);
+            stats.kinetic_energy += 0.5 * speed * speed;
+            count += 1;
+        }
+        if count > 0 {
+            stats.mean_speed /= count as f32;
+        }
+
+        stats.clusters = self.clusters.len();
+
+        if self.stats.len() == STATS_HISTORY {
+            self.stats.pop_front();
+        }
+        self.stats.push_back(stats);
+    }
+
+    fn update_clusters(&mut self) {
+        // the cluster pass is much slower than the rest, so only run it now and then
+        if self.cluster_frame.is_multiple_of(CLUSTER_INTERVAL) {
+            self.clusters = find_clusters(
+                &self.sim.dots,
+                self.cluster_distance,
+                self.sim.world_w,
+                self.sim.world_h,
+            );
+        }
+        self.cluster_frame += 1;
+    }
+
+    /// Returns how to color each particle in the current render mode.
+    fn dot_colors(&self) -> impl Fn(usize, usize, &Dot) -> Color32 + '_ {
+        let (cols, rows) = grid_size(self.sim.world_w, self.sim.world_h, DENSITY_CELL_SIZE);
+        let mut density = Vec::new();
+        let mut max_density = 1;
+        if self.render_mode == RenderMode::DensityHeatmap {
+            density.resize(cols * rows, 0u32);
+            for dot in self.sim.dots.iter().flatten() {
+                let cell = &mut density[grid_cell(dot.pos, cols, rows, DENSITY_CELL_SIZE)];
+                *cell += 1;
+                max_density = max_density.max(*cell);
+            }
+        }
+        let max_speed = if self.render_mode == RenderMode::SpeedHeatmap {
+            self.sim
+                .dots
+                .iter()
+                .flatten()
+                .map(|dot| dot.vel.length())
+                .fold(f32::EPSILON, f32::max)
+        } else {
+            1.0
+        };
+        // only worked out in this mode, since it's as slow as a step of that pair
+        let (pair_i, pair_j) = self.selected_param;
+        let pulls = match self.render_mode {
+            RenderMode::PairForce => self.sim.pulls(pair_i, pair_j),
+            _ => Vec::new(),
+        };
+        let max_pull = pulls.iter().map(|p| p.abs()).fold(f32::EPSILON, f32::max);
+
+        move |class, index, dot| {
+            let color = match self.render_mode {
+                // the density field is drawn by itself, so this only colors saved images
+                RenderMode::ClassColor | RenderMode::DensityField => {
+                    self.sim.params[class].color.into()
+                }
+                RenderMode::SpeedHeatmap => heat_color(dot.vel.length() / max_speed),
+                RenderMode::DensityHeatmap => {
+                    let cell = grid_cell(dot.pos, cols, rows, DENSITY_CELL_SIZE);
+                    heat_color(density[cell] as f32 / max_density as f32)
+                }
+                RenderMode::PairForce if class == pair_i => {
+                    let t = pulls.get(index).map_or(0.0, |pull| pull / max_pull);
+                    let strong = if t > 0.0 {
+                        Rgba::from(Color32::from_rgb(60, 220, 60))
+                    } else {
+                        Rgba::from(Color32::from_rgb(230, 50, 50))
+                    };
+                    // weak forces stay grey, so the ones that matter stand out
+                    let t = (t.abs() * 1.25 - 0.05).clamp(0.0, 1.0);
+                    (Rgba::from(Color32::from_gray(90)) * (1.0 - t) + strong * t).into()
+                }
+                RenderMode::PairForce => (self.sim.params[class].color * 0.25).into(),
+            };
+            match self.sim.life_left(class, dot) {
+                Some(left) if self.appearance.fade_with_age => {
+                    color.linear_multiply((left / AGE_FADE).min(1.0))
+                }
+                _ => color,
+            }
+        }
+    }
+
+    fn render_image(&self) -> RgbaImage {
+        self.render_image_at(self.image_size)
+    }
+
+    fn render_image_at(&self, width: u32) -> RgbaImage {
+        // either frame the whole world in a square, or match what's on screen
+        let (size, view) = if self.image_whole_world {
+            let size = Vec2::splat(width as f32);
+            let view = View {
+                pos: Vec2::new(self.sim.world_w, self.sim.world_h) / 2.0,
+                zoom: size.x / self.sim.world_w.max(self.sim.world_h),
+            };
+            (size, view)
+        } else {
+            let scale = width as f32 / self.view_size.x;
+            let view = View {
+                pos: self.view.pos,
+                zoom: self.view.zoom * scale,
+            };
+            (self.view_size * scale, view)
+        };
+        let center = (size / 2.0).to_pos2();
+        // fixed sizes are in screen points, so they grow with the image like the rest
+        let scale = match self.appearance.scale_with_zoom {
+            true => 1.0,
+            false => size.x / self.view_size.x.max(1.0),
+        };
+
+        let mut img = RgbaImage::from_pixel(
+            size.x as u32,
+            size.y.max(1.0) as u32,
+            Pixel(self.appearance.background.to_array()),
+        );
+        let color = self.dot_colors();
+        for (i, dots) in self.sim.dots.iter().enumerate() {
+            let radius = self.appearance.dot_radius(i, view.zoom) * scale;
+            let shape = self.appearance.dot_style(i).shape;
+            for (j, dot) in dots.iter().enumerate() {
+                fill_dot(
+                    &mut img,
+                    view.to_screen(center, dot.pos),
+                    radius,
+                    shape,
+                    color(i, j, dot),
+                );
+            }
+        }
+        if self.appearance.hud {
+            let size = self.appearance.hud_text_size(img.height() as f32);
+            fill_hud(
+                &mut img,
+                &self.hud_lines(),
+                size,
+                self.appearance.hud_corner,
+            );
+        }
+        img
+    }
+
+    /// The lines of the stats overlay, one for each field that's turned on.
+    fn hud_lines(&self) -> Vec<String> {
+        let fields = HudField::ALL.into_iter();
+        let fields = fields.filter(|&field| self.appearance.hud_fields[field as usize]);
+        fields
+            .map(|field| match field {
+                HudField::Seed if self.seed.chars().count() > HUD_SEED_LEN => {
+                    let start: String = self.seed.chars().take(HUD_SEED_LEN - 3).collect();
+                    format!("Seed: {}...", start)
+                }
+                HudField::Seed => format!("Seed: {}", self.seed),
+                HudField::Particles => {
+                    let total: usize = self.sim.dots.iter().map(Vec::len).sum();
+                    format!("Particles: {}", total)
+                }
+                HudField::StepRate => {
+                    let steps = self.step_times.iter();
+                    let steps = steps.filter(|t| t.elapsed() < RATE_WINDOW).count();
+                    format!("Steps/s: {}", steps)
+                }
+                HudField::SimTime => {
+                    let time = format_time(self.sim.time());
+                    format!("Frame: {} ({})", self.sim.steps(), time)
+                }
+            })
+            .collect()
+    }
+
+    fn save_image(&self) -> Result<PathBuf, String> {
+        let path = self.output_path(".png");
+        self.render_image()
+            .save(&path)
+            .map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+
+    fn save_csv(&self) -> Result<PathBuf, String> {
+        let path = self.output_path(".csv");
+        let mut csv = String::from("class_index,class_name,particle_index,x,y,vx,vy\n");
+        for (i, (p, dots)) in self.sim.params.iter().zip(&self.sim.dots).enumerate() {
+            // names can be edited, so quote them in case they contain commas or quotes
+            let name = format!("\"{}\"", p.name.replace('"', "\"\""));
+            for (j, dot) in dots.iter().enumerate() {
+                csv += &format!(
+                    "{},{},{},{},{},{},{}\n",
+                    i, name, j, dot.pos.x, dot.pos.y, dot.vel.x, dot.vel.y
+                );
+            }
+        }
+        std::fs::write(&path, csv).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+
+    fn save_config(&mut self) -> Result<PathBuf, String> {
+        let path = self.output_path(".ron");
+        let text = Config::from_sim(&self.sim).to_ron()?;
+        std::fs::write(&path, text).map_err(|err| err.to_string())?;
+        self.config_path = path.display().to_string();
+        Ok(path)
+    }
+
+    fn load_config(&mut self) -> Result<(), String> {
+        let text = std::fs::read_to_string(&self.config_path).map_err(|err| err.to_string())?;
+        self.apply_config(&text)
+    }
+
+    fn apply_config(&mut self, text: &str) -> Result<(), String> {
+        let (config, mut warnings) = Config::from_ron(text)?;
+        warnings.extend(config.apply(&mut self.sim));
+        self.config_warnings = warnings;
+        self.seed = self.sim.export();
+        self.seed_error = None;
+        self.spawn();
+        self.update_history();
+        Ok(())
+    }
+
+    fn save_matrix(&mut self) -> Result<PathBuf, String> {
+        let path = self.output_path("_matrix.csv");
+        std::fs::write(&path, matrix::to_csv(&self.sim)).map_err(|err| err.to_string())?;
+        self.matrix_path = path.display().to_string();
+        Ok(path)
+    }
+
+    fn load_matrix(&mut self) -> Result<(), String> {
+        let text = std::fs::read_to_string(&self.matrix_path).map_err(|err| err.to_string())?;
+        self.apply_matrix(&text)
+    }
+
+    /// Replaces the force matrices while the simulation keeps running, without a respawn.
+    fn apply_matrix(&mut self, text: &str) -> Result<(), String> {
+        matrix::apply_csv(&mut self.sim, text)?;
+        self.seed = self.sim.export();
+        self.seed_error = None;
+        self.update_history();
+        Ok(())
+    }
+
+    /// Loads a config, matrix or seed file dropped onto the window, returning what happened.
+    fn load_dropped(&mut self, file: &DroppedFile) -> Result<String, String> {
+        // native backends only give a path, and the web backend only the contents
+        let (name, bytes) = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => (file.name.clone(), bytes.to_vec()),
+            (None, Some(path)) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let bytes = std::fs::read(path)
+                    .map_err(|err| format!("Couldn't read {}: {}", name, err))?;
+                (name.into_owned(), bytes)
+            }
+            (None, None) => return Err("The dropped file is empty.".to_string()),
+        };
+        let not_supported = || format!("{} isn't a config, matrix or seed file.", name);
+        let text = String::from_utf8(bytes).map_err(|_| not_supported())?;
+        let text = text.trim();
+
+        if text.starts_with('@') {
+            self.apply_seed_text(text)?;
+            return Ok(format!("Applied the seed from {}", name));
+        }
+        if name.ends_with(".ron") || text.starts_with('(') {
+            self.config_warnings.clear();
+            self.apply_config(text)?;
+            if let Some(path) = &file.path {
+                self.config_path = path.display().to_string();
+            }
+            return Ok(match self.config_warnings.len() {
+                0 => format!("Loaded {}", name),
+                n => format!("Loaded {} with {} warnings, see Export", name, n),
+            });
+        }
+        if name.ends_with(".csv") {
+            self.apply_matrix(text)
+                .map_err(|err| format!("Couldn't load {}: {}", name, err))?;
+            if let Some(path) = &file.path {
+                self.matrix_path = path.display().to_string();
+            }
+            return Ok(format!("Loaded the matrices from {}", name));
+        }
+        Err(not_supported())
+    }
+
+    fn output_path(&self, suffix: &str) -> PathBuf {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        PathBuf::from(&self.output_dir).join(format!("smarticles_{}{}", secs, suffix))
+    }
+
+    fn replay_len(&self) -> usize {
+        (self.replay_secs * REPLAY_FPS) as usize
+    }
+
+    fn save_replay(&mut self) {
+        let path = self.output_path(".gif");
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        match replay.save_gif(&path, REPLAY_FPS) {
+            Ok(writer) => {
+                self.gif_writer = Some(writer);
+                self.export_status = Some(Ok(format!("Saving {}", path.display())));
+            }
+            Err(err) => self.export_status = Some(Err(err.to_string())),
+        }
+    }
+
+    fn start_recording(&mut self) {
+        let path = self.output_path(".smrec");
+        let classes: Vec<_> = self
+            .sim
+            .params
+            .iter()
+            .map(|p| (p.name.as_str(), p.color.into()))
+            .collect();
+        match Recorder::start(&path, self.sim.world_w, self.sim.world_h, &classes) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                self.playback_path = path.display().to_string();
+                self.record_status = Some(Ok(format!("Recording to {}", path.display())));
+            }
+            Err(err) => self.record_status = Some(Err(err.to_string())),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            self.record_status = Some(
+                recorder
+                    .finish()
+                    .map(|frames| format!("Saved {} frames", frames))
+                    .map_err(|err| err.to_string()),
+            );
+        }
+    }
+
+    fn start_frames(&mut self) {
+        let dir = self.output_path("_frames");
+        match FrameWriter::start(&dir) {
+            Ok(writer) => {
+                self.frame_writer = Some(writer);
+                self.export_status = Some(Ok(format!("Rendering frames to {}", dir.display())));
+            }
+            Err(err) => self.export_status = Some(Err(err.to_string())),
+        }
+    }
+
+    fn stop_frames(&mut self) {
+        if let Some(writer) = self.frame_writer.take() {
+            self.export_status = Some(
+                writer
+                    .finish()
+                    .map(|frames| format!("Saved {} frames", frames)),
+            );
+        }
+    }
+
+    fn load_recording(&mut self) {
+        // the live simulation stays paused underneath while a recording plays
+        self.stop();
+        match Player::open(Path::new(&self.playback_path)) {
+            Ok(player) => {
+                self.player = Some(player);
+                self.playback_error = None;
+            }
+            Err(err) => self.playback_error = Some(err.to_string()),
+        }
+    }
+
+    fn seek_recording(&mut self, frame: usize) {
+        if let Some(player) = &mut self.player {
+            if let Err(err) = player.seek(frame) {
+                self.playback_error = Some(err.to_string());
+                self.player = None;
+            }
+        }
+    }
+
+    fn start_control(&mut self, ctx: &Context) {
+        match ControlServer::start(self.control_addr.trim(), ctx.clone()) {
+            Ok(control) => {
+                self.control = Some(control);
+                self.control_error = None;
+            }
+            Err(err) => self.control_error = Some(err.to_string()),
+        }
+    }
+
+    /// Runs a remote command, returning what to report back besides `ok`.
+    fn run_command(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Play => self.play(),
+            Command::Pause => self.stop(),
+            Command::Respawn => self.spawn(),
+            Command::Randomize => {
+                self.randomize();
+                return Ok(self.seed.clone());
+            }
+            Command::Seed(seed) => {
+                self.apply_seed_text(&seed)?;
+                return Ok(self.seed.clone());
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn start_audio(&mut self, ctx: &Context) {
+        match AudioInput::start(self.audio_device.as_deref(), ctx.clone()) {
+            Ok(audio) => {
+                self.audio = Some(audio);
+                self.audio_error = None;
+            }
+            Err(err) => self.audio_error = Some(err),
+        }
+    }
+
+    /// The multipliers the audio input puts on the settings, which leave them alone while
+    /// it's off.
+    fn modulation(&self) -> Modulation {
+        let mut modulation = Modulation::default();
+        if self
+            .audio
+            .as_ref()
+            .is_none_or(|audio| audio.error().is_some())
+        {
+            return modulation;
+        }
+        for (target, mapping) in ModTarget::ALL.into_iter().zip(&self.mappings) {
+            let Some(feature) = mapping.feature else {
+                continue;
+            };
+            let scale = (1.0 + mapping.depth * self.levels.get(feature)).max(0.0);
+            match target {
+                ModTarget::Force => modulation.force = scale,
+                ModTarget::WorldSize => modulation.world = scale,
+                ModTarget::Friction => modulation.friction = scale,
+            }
+        }
+        modulation
+    }
+
+    fn modulation_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mut listening = self.audio.is_some();
+            if ui.toggle_value(&mut listening, "Listen").changed() {
+                if listening {
+                    self.start_audio(ui.ctx());
+                } else {
+                    self.audio = None;
+                    self.levels = Levels::default();
+                }
+            }
+            ui.add_enabled_ui(self.audio.is_none(), |ui| {
+                let selected = self.audio_device.as_deref().unwrap_or("Default Input");
+                let combo = ComboBox::from_id_source("audio_device")
+                    .selected_text(selected)
+                    .width(160.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.audio_device, None, "Default Input");
+                        let devices = self.audio_devices.get_or_insert_with(AudioInput::devices);
+                        for device in devices.iter() {
+                            let name = Some(device.clone());
+                            ui.selectable_value(&mut self.audio_device, name, device);
+                        }
+                    });
+                // devices come and go, so look them up again next time it's opened
+                if combo.inner.is_none() {
+                    self.audio_devices = None;
+                }
+            });
+        });
+        match (&self.audio, &self.audio_error) {
+            (Some(audio), _) => match audio.error() {
+                Some(err) => {
+                    ui.colored_label(Color32::RED, format!("● {}", err));
+                }
+                None => {
+                    let listening = format!("● Listening to {}", audio.device());
+                    ui.colored_label(Color32::GREEN, listening);
+                }
+            },
+            (None, Some(err)) => {
+                ui.colored_label(Color32::RED, err);
+            }
+            (None, None) => {}
+        }
+
+        Grid::new("levels").num_columns(2).show(ui, |ui| {
+            for feature in Feature::ALL {
+                ui.label(format!("{}:", feature.name()));
+                let level = self.levels.get(feature);
+                ui.add(ProgressBar::new(level).desired_width(160.0));
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        Grid::new("mappings").num_columns(3).show(ui, |ui| {
+            for (target, mapping) in ModTarget::ALL.into_iter().zip(&mut self.mappings) {
+                ui.label(format!("{}:", target.name()));
+                ComboBox::from_id_source(("modulate", target.key()))
+                    .selected_text(mapping.feature.map_or("Off", Feature::name))
+                    .width(90.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut mapping.feature, None, "Off");
+                        for feature in Feature::ALL {
+                            ui.selectable_value(
+                                &mut mapping.feature,
+                                Some(feature),
+                                feature.name(),
+                            );
+                        }
+                    });
+                ui.add_enabled(
+                    mapping.feature.is_some(),
+                    Slider::new(&mut mapping.depth, -MAX_DEPTH..=MAX_DEPTH).text("Depth"),
+                );
+                ui.end_row();
+            }
+        });
+        ui.label("Settings are scaled by 1 + depth × level, and go back to normal when off.");
+    }
+
+    fn start_stream(&mut self) {
+        match Streamer::start(self.stream_target.trim()) {
+            Ok(streamer) => {
+                self.streamer = Some(streamer);
+                self.stream_error = None;
+            }
+            Err(err) => self.stream_error = Some(err.to_string()),
+        }
+    }
+}
+
+/// Which `_`-separated part of a word seed is its last word, if it has one.
+fn last_word(seed: &str) -> Option<usize> {
+    if seed.starts_with('@') {
+        return None;
+    }
+    seed.split('_')
+        .enumerate()
+        .filter(|(_, part)| !part.is_empty() && part.chars().all(char::is_alphabetic))
+        .last()
+        .map(|(i, _)| i)
+}
+
+fn grid_size(world_w: f32, world_h: f32, cell_size: f32) -> (usize, usize) {
+    let cols = (world_w / cell_size).ceil() as usize + 1;
+    let rows = (world_h / cell_size).ceil() as usize + 1;
+    (cols, rows)
+}
+
+fn grid_cell(pos: Vec2, cols: usize, rows: usize, cell_size: f32) -> usize {
+    let x = (pos.x / cell_size).clamp(0.0, (cols - 1) as f32) as usize;
+    let y = (pos.y / cell_size).clamp(0.0, (rows - 1) as f32) as usize;
+    y * cols + x
+}
+
+fn distance_histogram(a: &[Dot], b: &[Dot], same: bool) -> (Vec<u32>, usize) {
+    let sample_every = (a.len() * b.len()).div_ceil(MAX_HISTOGRAM_PAIRS).max(1);
+    let max = MAX_RADIUS * 2.0;
+    let mut bins = vec![0; HISTOGRAM_BINS];
+    for (k, p1) in a.iter().enumerate().step_by(sample_every) {
+        for (l, p2) in b.iter().enumerate() {
+            // a particle's distance to itself says nothing
+            if same && k == l {
+                continue;
+            }
+            let r = (p1.pos - p2.pos).length();
+            if r < max {
+                bins[((r / max * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+            }
+        }
+    }
+    (bins, sample_every)
+}
+
+fn find_clusters(dots: &[Vec<Dot>], distance: f32, world_w: f32, world_h: f32) -> Vec<Cluster> {
+    fn root(parent: &mut [usize], mut k: usize) -> usize {
+        while parent[k] != k {
+            parent[k] = parent[parent[k]];
+            k = parent[k];
+        }
+        k
+    }
+
+    // bucket the particles into cells as wide as the distance, so each one only needs
+    // comparing with the particles in its own and the neighboring cells
+    let positions: Vec<Vec2> = dots.iter().flatten().map(|dot| dot.pos).collect();
+    let (cols, rows) = grid_size(world_w, world_h, distance);
+    let mut cells = vec![Vec::new(); cols * rows];
+    for (k, &pos) in positions.iter().enumerate() {
+        cells[grid_cell(pos, cols, rows, distance)].push(k);
+    }
+
+    let mut parent: Vec<usize> = (0..positions.len()).collect();
+    for (cell, members) in cells.iter().enumerate() {
+        let (x, y) = (cell % cols, cell / cols);
+        for ny in y.saturating_sub(1)..=(y + 1).min(rows - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(cols - 1) {
+                for &a in members {
+                    for &b in &cells[ny * cols + nx] {
+                        if a < b && (positions[a] - positions[b]).length_sq() < distance * distance
+                        {
+                            let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+                            parent[a] = b;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..positions.len()).map(|k| root(&mut parent, k)).collect();
+    let mut sums = vec![(Vec2::ZERO, 0); positions.len()];
+    for (&pos, &root) in positions.iter().zip(&roots) {
+        sums[root].0 += pos;
+        sums[root].1 += 1;
+    }
+    // measured from the centers rather than summing squares, which would lose precision
+    let mut spreads = vec![0.0; positions.len()];
+    for (&pos, &root) in positions.iter().zip(&roots) {
+        let (sum, count) = sums[root];
+        spreads[root] += (pos - sum / count as f32).length_sq();
+    }
+
+    sums.iter()
+        .zip(&spreads)
+        .filter(|((_, count), _)| *count >= MIN_CLUSTER_SIZE)
+        .map(|(&(sum, count), spread)| Cluster {
+            center: sum / count as f32,
+            spread: (spread / count as f32).sqrt(),
+            count,
+        })
+        .collect()
+}
+
+fn locked_slider(ui: &mut Ui, locked: &mut bool, slider: Slider) -> Response {
+    ui.toggle_value(locked, "🔒")
+        .on_hover_text("Keep this value when randomizing or mutating");
+    ui.scope(|ui| {
+        if *locked {
+            let widgets = &mut ui.visuals_mut().widgets;
+            widgets.inactive.bg_fill = LOCK_TINT;
+            widgets.hovered.bg_fill = LOCK_TINT;
+            widgets.active.bg_fill = LOCK_TINT;
+        }
+        ui.add(slider)
+    })
+    .inner
+}
+
+/// Draws an anti-aliased filled circle, blending it over what's already there.
+fn fill_dot(img: &mut RgbaImage, pos: Pos2, radius: f32, shape: DotShape, col: Color32) {
+    let min_x = (pos.x - radius - 1.0).max(0.0) as u32;
+    let min_y = (pos.y - radius - 1.0).max(0.0) as u32;
+    let max_x = ((pos.x + radius + 1.0).max(0.0) as u32).min(img.width());
+    let max_y = ((pos.y + radius + 1.0).max(0.0) as u32).min(img.height());
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let offset = Vec2::new(x as f32 + 0.5 - pos.x, y as f32 + 0.5 - pos.y);
+            let dist = match shape {
+                DotShape::Circle => offset.length(),
+                DotShape::Square => offset.x.abs().max(offset.y.abs()),
+            };
+            let coverage = (radius + 0.5 - dist).clamp(0.0, 1.0) * col.a() as f32 / 255.0;
+            if coverage <= 0.0 {
+                continue;
+            }
+            let Pixel(px) = img.get_pixel_mut(x, y);
+            for (dst, src) in px.iter_mut().zip(col.to_array()).take(3) {
+                *dst = (*dst as f32 + (src as f32 - *dst as f32) * coverage) as u8;
+            }
+        }
+    }
+}
+
+/// Draws the stats overlay in a corner of `rect`, on a dark backing so it reads over anything.
+fn draw_hud(paint: &Painter, rect: Rect, lines: &[String], size: f32, corner: Corner) {
+    let galley = paint.layout_no_wrap(lines.join("\n"), FontId::monospace(size), Color32::WHITE);
+    let margin = size * 0.5;
+    let text = corner
+        .align()
+        .align_size_within_rect(galley.size(), rect.shrink(margin * 2.0));
+    paint.rect_filled(
+        text.expand(margin),
+        margin * 0.5,
+        Color32::from_black_alpha(140),
+    );
+    paint.galley(text.min, galley);
+}
+
+/// Draws the stats overlay into an image, like `draw_hud` does on screen.
+fn fill_hud(img: &mut RgbaImage, lines: &[String], size: f32, corner: Corner) {
+    // the same font the screen uses, which egui comes with
+    let fonts = FontDefinitions::default();
+    let Some(data) = fonts.font_data.get("Hack") else {
+        return;
+    };
+    let Ok(font) = FontRef::try_from_slice_and_index(&data.font, data.index) else {
+        return;
+    };
+    let font = font.as_scaled(PxScale::from(size));
+    let line_height = font.height() + font.line_gap();
+    let width = |line: &str| line.chars().map(|c| font.h_advance(font.glyph_id(c))).sum();
+    let text_size = Vec2::new(
+        lines.iter().map(|line| width(line)).fold(0.0, f32::max),
+        line_height * lines.len() as f32,
+    );
+    let margin = size * 0.5;
+    let bounds = Rect::from_min_size(
+        Pos2::ZERO,
+        Vec2::new(img.width() as f32, img.height() as f32),
+    );
+    let text = corner
+        .align()
+        .align_size_within_rect(text_size, bounds.shrink(margin * 2.0));
+
+    let mut blend = |x: i32, y: i32, col: Color32, coverage: f32| {
+        if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+            return;
+        }
+        let Pixel(px) = img.get_pixel_mut(x as u32, y as u32);
+        let coverage = coverage * col.a() as f32 / 255.0;
+        for (dst, src) in px.iter_mut().zip(col.to_array()).take(3) {
+            *dst = (*dst as f32 + (src as f32 - *dst as f32) * coverage) as u8;
+        }
+    };
+    let backing = text.expand(margin);
+    for y in backing.min.y as i32..backing.max.y as i32 {
+        for x in backing.min.x as i32..backing.max.x as i32 {
+            // straight black, since the image's pixels aren't premultiplied
+            blend(x, y, Color32::from_rgba_unmultiplied(0, 0, 0, 140), 1.0);
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let mut caret = text.min + Vec2::new(0.0, line_height * i as f32 + font.ascent());
+        for c in line.chars() {
+            let mut glyph = font.scaled_glyph(c);
+            let advance = font.h_advance(glyph.id);
+            glyph.position = ab_glyph::point(caret.x, caret.y);
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let min = outline.px_bounds().min;
+                outline.draw(|x, y, coverage| {
+                    let (x, y) = (min.x as i32 + x as i32, min.y as i32 + y as i32);
+                    blend(x, y, Color32::WHITE, coverage);
+                });
+            }
+            caret.x += advance;
+        }
+    }
+}
+
+/// Records when something happened, forgetting what's older than the rate window.
+fn push_time(times: &mut VecDeque<Instant>, time: Instant) {
+    times.push_back(time);
+    while times.front().is_some_and(|t| time - *t > RATE_WINDOW) {
+        times.pop_front();
+    }
+}
+
+/// Formats seconds as `mm:ss.t`.
+fn format_time(secs: f32) -> String {
+    let tenths = (secs * 10.0) as u64;
+    format!(
+        "{:02}:{:02}.{}",
+        tenths / 600,
+        tenths / 10 % 60,
+        tenths % 10
+    )
+}
+
+/// Shortens big numbers to something like `1.9M`.
+fn format_count(n: u64) -> String {
+    match n {
+        0..=9_999 => n.to_string(),
+        10_000..=999_999 => format!("{:.1}K", n as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.1}M", n as f64 / 1e6),
+        _ => format!("{:.1}B", n as f64 / 1e9),
+    }
+}
+
+fn draw_dot(paint: &Painter, pos: Pos2, radius: f32, shape: DotShape, col: Color32) {
+    match shape {
+        DotShape::Circle => paint.circle_filled(pos, radius, col),
+        DotShape::Square => paint.rect_filled(
+            Rect::from_center_size(pos, Vec2::splat(radius * 2.0)),
+            0.0,
+            col,
+        ),
+    }
+}
+
+fn draw_grid(paint: &Painter, rect: Rect, view: View) {
+    let min = view.to_world(rect.center(), rect.min);
+    let max = view.to_world(rect.center(), rect.max);
+    for (step, gray) in [(GRID_SUBSTEP, 25), (GRID_STEP, 50)] {
+        // too dense to be useful when zoomed far out
+        if step * view.zoom < 6.0 {
+            continue;
+        }
+        let stroke = Stroke::new(1.0, Color32::from_gray(gray));
+        let mut x = (min.x / step).ceil() * step;
+        while x <= max.x {
+            let sx = view.to_screen(rect.center(), Vec2::new(x, 0.0)).x;
+            paint.vline(sx, rect.y_range(), stroke);
+            x += step;
+        }
+        let mut y = (min.y / step).ceil() * step;
+        while y <= max.y {
+            let sy = view.to_screen(rect.center(), Vec2::new(0.0, y)).y;
+            paint.hline(rect.x_range(), sy, stroke);
+            y += step;
+        }
+    }
+}
+
+/// Draws arrows across the world showing which way the flow pushes.
+fn draw_flow<const N: usize>(paint: &Painter, sim: &Simulation<N>, view: View, center: Pos2) {
+    let world = Vec2::new(sim.world_w, sim.world_h);
+    let spacing = world.max_elem() / FLOW_ARROWS as f32;
+    let (cols, rows) = ((world.x / spacing) as usize, (world.y / spacing) as usize);
+    let points: Vec<_> = (0..cols * rows)
+        .map(|k| {
+            let cell = Vec2::new((k % cols) as f32, (k / cols) as f32);
+            let pos = (cell + Vec2::splat(0.5)) * spacing;
+            (pos, sim.flow.at(pos, world))
+        })
+        .collect();
+    let max = points
+        .iter()
+        .map(|(_, f)| f.length())
+        .fold(f32::EPSILON, f32::max);
+    let stroke = Stroke::new(1.0, Color32::from_white_alpha(40));
+    for (pos, f) in points {
+        // the longest arrows nearly reach the next one
+        let vec = f / max * spacing * 0.8 * view.zoom;
+        if vec.length() < 2.0 {
+            continue;
+        }
+        paint.arrow(view.to_screen(center, pos) - vec / 2.0, vec, stroke);
+    }
+}
+
+fn draw_scale_bar(paint: &Painter, rect: Rect, zoom: f32) {
+    // round the length down to 1, 2 or 5 times a power of ten
+    let len = SCALE_BAR_LEN / zoom;
+    let pow = 10f32.powf(len.log10().floor());
+    let len = [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * pow)
+        .find(|&l| l <= len)
+        .unwrap_or(pow);
+
+    let start = rect.left_bottom() + Vec2::new(10.0, -10.0);
+    let end = start + Vec2::X * len * zoom;
+    let stroke = Stroke::new(2.0, Color32::WHITE);
+    paint.line_segment([start, end], stroke);
+    paint.line_segment([start, start - Vec2::Y * 5.0], stroke);
+    paint.line_segment([end, end - Vec2::Y * 5.0], stroke);
+    paint.text(
+        start - Vec2::Y * 8.0,
+        Align2::LEFT_BOTTOM,
+        format!("{} units", len),
+        FontId::default(),
+        Color32::WHITE,
+    );
+}
+
+fn heading(name: &str) -> String {
+    format!("Type {}", name)
+}
+
+fn class_combo(ui: &mut Ui, id_source: impl Hash, names: &[String], class: &mut usize) -> bool {
+    let prev = *class;
+    ComboBox::from_id_source(id_source)
+        .selected_text(&names[*class])
+        .width(70.0)
+        .show_ui(ui, |ui| {
+            for (i, name) in names.iter().enumerate() {
+                ui.selectable_value(class, i, name);
+            }
+        });
+    *class != prev
+}
+
+fn heat_color(t: f32) -> Color32 {
+    let t = t.clamp(0.0, 1.0);
+    Color32::from_rgb((255.0 * t) as u8, 0, (255.0 * (1.0 - t)) as u8)
+}
+
+fn heat_legend(ui: &mut Ui, low: &str, high: &str) {
+    ui.horizontal(|ui| {
+        ui.label(low);
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(120.0, 12.0), Sense::hover());
+        const STEPS: usize = 32;
+        let step_w = rect.width() / STEPS as f32;
+        for i in 0..STEPS {
+            let min = rect.min + Vec2::new(i as f32 * step_w, 0.0);
+            let step = Rect::from_min_size(min, Vec2::new(step_w + 0.5, rect.height()));
+            let t = i as f32 / (STEPS - 1) as f32;
+            ui.painter().rect_filled(step, 0.0, heat_color(t));
+        }
+        ui.label(high);
+    });
+}
+
+fn uniform(mut rng: SmallRng) -> impl FnMut(f32, f32) -> f32 {
+    move |min, max| min + (max - min) * rng.sample::<f32, _>(OpenClosed01)
+}
+
+impl<const N: usize> App for Smarticles<N> {
+    fn save(&mut self, storage: &mut dyn Storage) {
+        // autosaves mustn't overwrite a session that's still waiting to be restored
+        match &self.recovered {
+            Some(session) => session.save(storage),
+            None => self.session().save(storage),
+        }
+        storage.set_string("clean_exit", self.closing.to_string());
+        let history: Vec<_> = self.history.iter().map(HistoryEntry::encode).collect();
+        storage.set_string("history", history.join("\n"));
+        let presets: Vec<_> = self.presets.iter().map(Preset::encode).collect();
+        storage.set_string("presets", presets.join("\n"));
+        self.appearance.save(storage);
+        storage.set_string("keep_simulating", self.keep_simulating.to_string());
+        storage.set_string("step_budget", self.step_budget.to_string());
+        storage.set_string("step_interval", self.step_interval.to_string());
+        storage.set_string("auto_limit", self.auto_limit.to_string());
+        let bookmarks: Vec<_> = (self.bookmarks.iter())
+            .map(|view| view.map(View::encode).unwrap_or_default())
+            .collect();
+        storage.set_string("bookmarks", bookmarks.join("\n"));
+        storage.set_string("smooth_bookmarks", self.smooth_bookmarks.to_string());
+        let side = match self.panel_side {
+            Side::Left => "left",
+            Side::Right => "right",
+        };
+        storage.set_string("panel_side", side.to_string());
+        storage.set_string("replay", self.replay.is_some().to_string());
+        storage.set_string("replay_secs", self.replay_secs.to_string());
+        storage.set_string("replay_size", self.replay_size.to_string());
+        storage.set_string("settle_speed", self.sim.settle_speed.to_string());
+        storage.set_string("settle_steps", self.sim.settle_steps.to_string());
+        storage.set_string("settle_action", self.settle_action.key().to_string());
+        let device = self.audio_device.clone().unwrap_or_default();
+        storage.set_string("audio_device", device);
+        for (target, mapping) in ModTarget::ALL.into_iter().zip(&self.mappings) {
+            storage.set_string(&format!("modulate_{}", target.key()), mapping.encode());
+        }
+    }
+
+    fn auto_save_interval(&self) -> Duration {
+        AUTOSAVE_INTERVAL
+    }
+
+    // this also runs for the Quit button, and the final save comes after it
+    #[cfg(not(target_arch = "wasm32"))]
+    fn on_close_event(&mut self) -> bool {
+        self.closing = true;
+        true
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.stop_recording();
+        self.stop_frames();
+        // waits for the listener threads to finish
+        self.control = None;
+    }
+
+    // the frame only controls the window, which doesn't exist on the web
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
+        // the saved window state is restored before the first frame, so
+        // command-line options are applied on top of it here
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(args) = self.window_args.take() {
+            if let Some(fullscreen) = args.fullscreen {
+                frame.set_fullscreen(fullscreen);
+            }
+            if let Some(size) = args.size {
+                frame.set_window_size(size);
+            }
+        }
+
+        push_time(&mut self.frame_times, Instant::now());
+
+        // hold off while nobody's looking, without pausing, so it carries on where it was.
+        // winit can't tell if the window is minimized, but minimized windows lose focus,
+        // and on Windows they also shrink to nothing
+        let hidden = !ctx.input().raw.has_focus;
+        #[cfg(not(target_arch = "wasm32"))]
+        let hidden = hidden || frame.info().window_info.size == Vec2::ZERO;
+        let background = hidden && !self.keep_simulating;
+
+        if let Some(player) = self.player.as_mut().filter(|p| p.playing && !background) {
+            // recordings hold one frame per step, and steps run at up to 60 per second
+            if let Err(err) = player.advance(ctx.input().stable_dt * 60.0) {
+                self.playback_error = Some(err.to_string());
+                self.player = None;
+            }
+            ctx.request_repaint();
+        }
+
+        if self.play && self.player.is_none() && !background {
+            let time = Instant::now();
+            let delta = time - self.prev_time;
+            if delta > Duration::from_secs_f32(self.step_interval / 1000.0) {
+                self.prev_time = time;
+                self.step();
+            }
+            ctx.request_repaint();
+        }
+
+        self.handle_shortcuts(ctx);
+        self.update_glide(ctx);
+
+        if let Some(writer) = &self.gif_writer {
+            if writer.is_finished() {
+                let result = self.gif_writer.take().unwrap().finish();
+                self.export_status = Some(result.map(|path| format!("Saved {}", path.display())));
+            } else {
+                // the progress bar only moves if something redraws it
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+
+        if let Some(audio) = &mut self.audio {
+            self.levels = audio.levels();
+        }
+
+        let requests: Vec<_> = self.control.iter().flat_map(|c| c.requests()).collect();
+        for request in requests {
+            let result = self.run_command(request.command);
+            request.reply.send(result);
+        }
+
+        if self.recovered.is_some() {
+            let mut restore = None;
+            Window::new("Restore Session")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label("Smarticles didn't shut down cleanly last time.");
+                    ui.label("Restore the previous session?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            restore = Some(true);
+                        }
+                        if ui.button("Discard").clicked() {
+                            restore = Some(false);
+                        }
+                    });
+                });
+            if let Some(restore) = restore {
+                let session = self.recovered.take().unwrap();
+                if restore {
+                    self.stop();
+                    self.restore_session(session);
+                    self.update_history();
+                }
+            }
+        }
+
+        let dropped = ctx.input().raw.dropped_files.clone();
+        for file in &dropped {
+            self.toast = Some((self.load_dropped(file), Instant::now()));
+        }
+
+        if self.show_panel {
+            SidePanel::new(self.panel_side, "settings").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Settings");
+                    if ui.button("Hide").on_hover_text("Tab").clicked() {
+                        self.show_panel = false;
+                    }
+                    if ui
+                        .button("⇄")
+                        .on_hover_text("Move to the other side")
+                        .clicked()
+                    {
+                        self.panel_side = match self.panel_side {
+                            Side::Left => Side::Right,
+                            Side::Right => Side::Left,
+                        };
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Respawn").clicked() {
+                        self.spawn();
+                    }
+                    if self.play {
+                        if ui.button("Pause").clicked() {
+                            self.stop();
+                        }
+                    } else {
+                        if ui.button("Play").clicked() {
+                            self.play();
+                        }
+                    }
+
+                    // one step per press, then keep stepping every frame if it's held down
+                    let step = ui.add_enabled(!self.play, Button::new("Step"));
+                    if step.is_pointer_button_down_on() {
+                        let now = Instant::now();
+                        match self.step_held_since {
+                            None => {
+                                self.step_held_since = Some(now);
+                                self.step();
+                            }
+                            Some(since) if now - since > Duration::from_secs_f32(0.4) => {
+                                self.step()
+                            }
+                            Some(_) => {}
+                        }
+                        ctx.request_repaint();
+                    } else {
+                        self.step_held_since = None;
+                    }
+
+                    let odds = format!(
+                        "Picks two words and a number, about {:.0} bits of randomness",
+                        self.seed_bits()
+                    );
+                    if ui.button("Randomize").on_hover_text(odds).clicked() {
+                        self.randomize();
+                    }
+
+                    if ui.button("Reset").clicked() {
+                        self.restart();
+                    }
+
+                    // the browser owns the window on the web
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        let fullscreen = frame.info().window_info.fullscreen;
+                        if ui
+                            .button(if fullscreen { "Windowed" } else { "Fullscreen" })
+                            .clicked()
+                        {
+                            frame.set_fullscreen(!fullscreen);
+                        }
+
+                        if ui.button("Quit").clicked() {
+                            frame.close();
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Randomize Forces").clicked() {
+                        self.randomize_forces();
+                    }
+                    if ui.button("Randomize Counts").clicked() {
+                        self.randomize_counts();
+                    }
+                    if ui
+                        .add_enabled(
+                            last_word(&self.seed).is_some(),
+                            Button::new("Reroll Last Word"),
+                        )
+                        .on_hover_text("Keeps the rest of the seed and picks a new last word")
+                        .clicked()
+                    {
+                        self.reroll_last_word();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Mutate").clicked() {
+                        self.mutate();
+                    }
+                    ui.add(
+                        Slider::new(&mut self.mutation_strength, MIN_MUTATION..=MAX_MUTATION)
+                            .suffix("%"),
+                    );
+                    if ui.button("Clear Locks").clicked() {
+                        self.locks = [Locks::default(); N];
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!self.undo.is_empty(), Button::new("Undo"))
+                        .clicked()
+                    {
+                        self.undo();
+                    }
+                    if ui
+                        .add_enabled(!self.redo.is_empty(), Button::new("Redo"))
+                        .clicked()
+                    {
+                        self.redo();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Seed:");
+                    let edit = ui.text_edit_singleline(&mut self.seed);
+                    if edit.changed() {
+                        self.seed_error = None;
+                        self.apply_seed();
+                        self.spawn();
+                        self.stop();
+                    }
+                    if edit.lost_focus() {
+                        self.update_history();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("History:");
+                    let mut selected = None;
+                    ComboBox::from_id_source("history")
+                        .width(200.0)
+                        .selected_text(self.history.front().map_or("", |e| e.label.as_str()))
+                        .show_ui(ui, |ui| {
+                            for (i, entry) in self.history.iter().enumerate() {
+                                if ui.selectable_label(false, &entry.label).clicked() {
+                                    selected = Some(i);
+                                }
+                            }
+                        });
+                    // the code brings back exactly what was there, whatever the locks are now
+                    if let Some(entry) = selected.and_then(|i| self.history.remove(i)) {
+                        self.seed = entry.code.clone();
+                        self.seed_error = None;
+                        self.apply_seed();
+                        self.spawn();
+                        self.history.push_front(entry);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Copy").clicked() {
+                        ui.output().copied_text = self.sim.export();
+                    }
+                    // browsers only hand over the clipboard in a paste event,
+                    // see `handle_shortcuts`
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button("Paste & Apply").clicked() {
+                        self.seed_error = self.paste_seed().err();
+                    }
+                    if let Some(err) = &self.seed_error {
+                        ui.colored_label(Color32::RED, err);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Frame: {} ({})   Step Time: {:.2} ms",
+                        self.sim.steps(),
+                        format_time(self.sim.time()),
+                        self.step_time.as_secs_f64() * 1000.0
+                    ));
+                    if self.sim.settled() {
+                        ui.colored_label(Color32::LIGHT_GREEN, "Settled");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    // stale samples are dropped on the next push, so count recent ones only
+                    let rate = |times: &VecDeque<Instant>| {
+                        times.iter().filter(|t| t.elapsed() < RATE_WINDOW).count()
+                    };
+                    let steps = rate(&self.step_times);
+                    if self.play {
+                        let target = 1000.0 / self.step_interval;
+                        let col = match steps as f32 {
+                            s if s >= target * 0.5 => Color32::GREEN,
+                            s if s >= target / 3.0 => Color32::YELLOW,
+                            _ => Color32::RED,
+                        };
+                        ui.colored_label(col, "●");
+                    }
+                    ui.label(format!(
+                        "Steps/s: {}   FPS: {}",
+                        steps,
+                        rate(&self.frame_times)
+                    ));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Step Interval:");
+                    ui.add(
+                        Slider::new(
+                            &mut self.step_interval,
+                            MIN_STEP_INTERVAL..=MAX_STEP_INTERVAL,
+                        )
+                        .suffix(" ms"),
+                    )
+                    .on_hover_text(
+                        "How long to wait between steps. Steps that take longer than this run \
+                         back to back instead.",
+                    );
+                });
+                let steps = self.step_times.iter().filter(|t| t.elapsed() < RATE_WINDOW);
+                let steps = steps.count();
+                if self.play && steps > 0 {
+                    let actual = RATE_WINDOW.as_secs_f32() * 1000.0 / steps as f32;
+                    let text = format!(
+                        "Aiming for {:.0} ms between steps, getting {:.0} ms",
+                        self.step_interval, actual
+                    );
+                    // the display's refresh rate rounds it up a little anyway
+                    if actual > self.step_interval * 1.25 {
+                        ui.colored_label(Color32::YELLOW, text);
+                    } else {
+                        ui.label(text);
+                    }
+                }
+                ui.label(format!(
+                    "Interactions per Step: {}",
+                    format_count(self.sim.interactions_per_step(self.requested_total()))
+                ))
+                .on_hover_text(
+                    "Pairs of particles compared each step, which the step time grows with",
+                );
+                ui.horizontal(|ui| {
+                    let limit = ui
+                        .checkbox(&mut self.auto_limit, "Auto-Limit Counts")
+                        .on_hover_text("Spawn fewer particles when the counts would be too slow");
+                    ui.label("Step Budget:");
+                    let budget =
+                        Slider::new(&mut self.step_budget, MIN_STEP_BUDGET..=MAX_STEP_BUDGET)
+                            .suffix(" ms");
+                    if (limit.changed() | ui.add(budget).changed()) && self.update_count_scale() {
+                        self.sim.fit_counts(&mut SmallRng::from_entropy());
+                    }
+                });
+                let requested = self.requested_total();
+                let spawned: usize = (0..N).map(|i| self.sim.spawn_count(i)).sum();
+                if spawned < requested {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        format!(
+                            "Auto-limit is spawning {} of {} particles",
+                            spawned, requested
+                        ),
+                    );
+                } else if let Some(ms) = self
+                    .estimated_step_ms()
+                    .filter(|&ms| ms > self.step_budget as f64)
+                {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        format!("⚠ These counts will take about {:.0} ms per step", ms),
+                    );
+                }
+                ui.checkbox(&mut self.keep_simulating, "Keep Simulating In Background")
+                    .on_hover_text("Otherwise the simulation waits while the window isn't focused");
+
+                ui.horizontal(|ui| {
+                    ui.label("World Width:");
+                    if ui
+                        .add(Slider::new(&mut self.sim.world_w, 100.0..=1000.0))
+                        .changed()
+                    {
+                        self.sim.spawn_radius =
+                            self.sim.spawn_radius.min(self.sim.max_spawn_radius());
+                        self.seed = self.sim.export();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("World Height:");
+                    if ui
+                        .add(Slider::new(&mut self.sim.world_h, 100.0..=1000.0))
+                        .changed()
+                    {
+                        self.sim.spawn_radius =
+                            self.sim.spawn_radius.min(self.sim.max_spawn_radius());
+                        self.seed = self.sim.export();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Spawn Radius:");
+                    let max = self.sim.max_spawn_radius();
+                    if ui
+                        .add(Slider::new(
+                            &mut self.sim.spawn_radius,
+                            MIN_SPAWN_RADIUS..=max,
+                        ))
+                        .changed()
+                    {
+                        self.seed = self.sim.export();
+                    }
+                });
+                if ui
+                    .checkbox(&mut self.sim.spawn_rings, "Spawn Types in Rings")
+                    .on_hover_text("Each type starts in its own ring, from the middle outwards")
+                    .changed()
+                {
+                    self.seed = self.sim.export();
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Border:");
+                    let prev = self.sim.border;
+                    ComboBox::from_id_source("border")
+                        .selected_text(self.sim.border.name())
+                        .show_ui(ui, |ui| {
+                            for behavior in BorderBehavior::ALL {
+                                ui.selectable_value(
+                                    &mut self.sim.border,
+                                    behavior,
+                                    behavior.name(),
+                                );
+                            }
+                        });
+                    if self.sim.border != prev {
+                        self.seed = self.sim.export();
+                    }
+                });
+                if self.sim.border == BorderBehavior::Bounce {
+                    ui.horizontal(|ui| {
+                        ui.label("Restitution:");
+                        if ui
+                            .add(Slider::new(&mut self.sim.restitution, 0.0..=1.0))
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Reset View").clicked() {
+                        self.reset_view();
+                    }
+                    if ui
+                        .button("Fit World")
+                        .on_hover_text("Or double-click an empty spot")
+                        .clicked()
+                    {
+                        self.fit_view();
+                    }
+                    ui.label(format!("Zoom: {:.2}x", self.view.zoom));
+                    ui.checkbox(&mut self.show_minimap, "Minimap");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bookmarks:");
+                    for slot in 0..BOOKMARK_SLOTS {
+                        let saved = self.bookmarks[slot].is_some();
+                        let button = ui
+                            .add(Button::new((slot + 1).to_string()).fill(if saved {
+                                ui.visuals().widgets.active.bg_fill
+                            } else {
+                                ui.visuals().widgets.inactive.bg_fill
+                            }))
+                            .on_hover_text(if saved {
+                                "Click to go to this view, right-click to replace it"
+                            } else {
+                                "Click to save the view here"
+                            });
+                        if button.secondary_clicked() || button.clicked() && !saved {
+                            self.store_bookmark(slot);
+                        } else if button.clicked() {
+                            self.recall_bookmark(slot);
+                        }
+                    }
+                    ui.checkbox(&mut self.smooth_bookmarks, "Smooth");
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_clusters, "Clusters")
+                        .on_hover_text("Outline groups of particles and count their members");
+                    if self.show_clusters {
+                        ui.label("Distance:");
+                        let slider = Slider::new(
+                            &mut self.cluster_distance,
+                            MIN_CLUSTER_DISTANCE..=MAX_CLUSTER_DISTANCE,
+                        );
+                        if ui.add(slider).changed() {
+                            // look again straight away rather than on the next interval
+                            self.cluster_frame = 0;
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_grid, "Grid");
+                    ui.checkbox(&mut self.show_radius, "Selected Radius")
+                        .on_hover_text("Circle the selected particle with the selected radius");
+                    ui.checkbox(&mut self.smooth_motion, "Smooth Motion")
+                        .on_hover_text(
+                            "Draw particles between their last two steps on fast displays",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    let prev_class = self.follow_class;
+                    let mut follow = self.follow;
+                    ui.label("Follow:");
+                    ComboBox::from_id_source("follow")
+                        .selected_text(self.follow.name())
+                        .show_ui(ui, |ui| {
+                            for mode in Follow::ALL {
+                                ui.selectable_value(&mut follow, mode, mode.name());
+                            }
+                        });
+                    if follow != self.follow {
+                        self.set_follow(follow);
+                    }
+                    if self.follow == Follow::ClassCenterOfMass {
+                        ComboBox::from_id_source("follow_class")
+                            .selected_text(&self.sim.params[self.follow_class].name)
+                            .show_ui(ui, |ui| {
+                                for (i, p) in self.sim.params.iter().enumerate() {
+                                    ui.selectable_value(&mut self.follow_class, i, &p.name);
+                                }
+                            });
+                    }
+                    // start tracking from wherever the new target is, so the camera doesn't jump
+                    if self.follow_class != prev_class {
+                        self.prev_follow_pos = None;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Trail Length:");
+                    ui.add(Slider::new(&mut self.trail_len, 0..=MAX_TRAIL_LEN));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Render:");
+                    ComboBox::from_id_source("render_mode")
+                        .selected_text(self.render_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in RenderMode::ALL {
+                                ui.selectable_value(&mut self.render_mode, mode, mode.name());
+                            }
+                        });
+                });
+                match self.render_mode {
+                    RenderMode::ClassColor | RenderMode::DensityField => {}
+                    RenderMode::SpeedHeatmap => heat_legend(ui, "Slow", "Fast"),
+                    RenderMode::DensityHeatmap => heat_legend(ui, "Sparse", "Dense"),
+                    RenderMode::PairForce => {
+                        let (i, j) = self.selected_param;
+                        ui.horizontal(|ui| {
+                            ui.colored_label(self.sim.params[i].color, &self.sim.params[i].name);
+                            ui.label("pulled (green) or pushed (red) by");
+                            ui.colored_label(self.sim.params[j].color, &self.sim.params[j].name);
+                        });
+                    }
+                }
+
+                CollapsingHeader::new("Appearance").show(ui, |ui| {
+                    let appearance = &mut self.appearance;
+                    ui.horizontal(|ui| {
+                        ui.label("Background:");
+                        ui.color_edit_button_srgba(&mut appearance.background);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Boundary:");
+                        ui.color_edit_button_srgba(&mut appearance.boundary);
+                        ui.add(
+                            Slider::new(&mut appearance.boundary_width, 0.0..=5.0).text("Width"),
+                        );
+                    });
+                    ui.checkbox(&mut appearance.scale_with_zoom, "Scale Particles With Zoom");
+                    ui.checkbox(&mut appearance.fade_with_age, "Fade Out Dying Particles");
+                    ui.checkbox(&mut appearance.hud, "Stats Overlay")
+                        .on_hover_text("Also drawn into saved images, GIFs and frame sequences");
+                    ui.add_enabled_ui(appearance.hud, |ui| {
+                        ui.horizontal(|ui| {
+                            ComboBox::from_id_source("hud_corner")
+                                .selected_text(appearance.hud_corner.name())
+                                .show_ui(ui, |ui| {
+                                    for corner in Corner::ALL {
+                                        ui.selectable_value(
+                                            &mut appearance.hud_corner,
+                                            corner,
+                                            corner.name(),
+                                        );
+                                    }
+                                });
+                            ui.add(
+                                Slider::new(&mut appearance.hud_size, MIN_HUD_SIZE..=MAX_HUD_SIZE)
+                                    .text("Size"),
+                            )
+                            .on_hover_text("The text size in a 720 point tall view");
+                        });
+                        ui.horizontal(|ui| {
+                            for field in HudField::ALL {
+                                let shown = &mut appearance.hud_fields[field as usize];
+                                ui.checkbox(shown, field.name());
+                            }
+                        });
+                    });
+                    if ui.button("Reset Appearance").clicked() {
+                        *appearance = Appearance::default();
+                    }
+                });
+
+                // there's no file system to save to or load from on the web
+                if cfg!(target_arch = "wasm32") {
+                    ui.label(
+                        "Exporting, recording, playback, streaming and audio input need the \
+                        desktop app.",
+                    );
+                } else {
+                    CollapsingHeader::new("Export").show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Width:");
+                            ComboBox::from_id_source("image_size")
+                                .selected_text(format!("{}px", self.image_size))
+                                .show_ui(ui, |ui| {
+                                    for size in IMAGE_SIZES {
+                                        ui.selectable_value(
+                                            &mut self.image_size,
+                                            size,
+                                            format!("{}px", size),
+                                        );
+                                    }
+                                });
+                            ui.checkbox(&mut self.image_whole_world, "Whole World");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Folder:");
+                            ui.text_edit_singleline(&mut self.output_dir);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Save Image").clicked() {
+                                self.export_status = Some(
+                                    self.save_image()
+                                        .map(|path| format!("Saved {}", path.display())),
+                                );
+                            }
+                            if ui.button("Export Positions (CSV)").clicked() {
+                                self.export_status = Some(
+                                    self.save_csv()
+                                        .map(|path| format!("Saved {}", path.display())),
+                                );
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Config:");
+                            ui.text_edit_singleline(&mut self.config_path);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Save Config").clicked() {
+                                self.config_warnings.clear();
+                                self.export_status = Some(
+                                    self.save_config()
+                                        .map(|path| format!("Saved {}", path.display())),
+                                );
+                            }
+                            if ui.button("Load Config").clicked() {
+                                self.config_warnings.clear();
+                                self.export_status = Some(
+                                    self.load_config()
+                                        .map(|()| format!("Loaded {}", self.config_path)),
+                                );
+                            }
+                        });
+                        for warning in &self.config_warnings {
+                            ui.colored_label(Color32::YELLOW, warning);
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Matrix:");
+                            ui.text_edit_singleline(&mut self.matrix_path);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Export Matrix (CSV)").clicked() {
+                                self.export_status = Some(
+                                    self.save_matrix()
+                                        .map(|path| format!("Saved {}", path.display())),
+                                );
+                            }
+                            if ui.button("Import Matrix").clicked() {
+                                self.export_status = Some(
+                                    self.load_matrix()
+                                        .map(|()| format!("Loaded {}", self.matrix_path)),
+                                );
+                            }
+                        });
+                        // frames use the size and framing above, so zooming won't shake the video
+                        ui.horizontal(|ui| {
+                            let mut rendering = self.frame_writer.is_some();
+                            if ui.toggle_value(&mut rendering, "Render Frames").changed() {
+                                if rendering {
+                                    self.start_frames();
+                                } else {
+                                    self.stop_frames();
+                                }
+                            }
+                            if let Some(writer) = &self.frame_writer {
+                                ui.label(format!("{} frames", writer.frames()));
+                                if writer.dropped() > 0 {
+                                    ui.colored_label(
+                                        Color32::RED,
+                                        format!("{} dropped", writer.dropped()),
+                                    );
+                                }
+                            }
+                        });
+                        self.replay_ui(ui);
+                        match &self.export_status {
+                            Some(Ok(msg)) => {
+                                ui.label(msg);
+                            }
+                            Some(Err(err)) => {
+                                ui.colored_label(Color32::RED, err);
+                            }
+                            None => {}
+                        }
+                    });
+
+                    CollapsingHeader::new("Recording").show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Folder:");
+                            ui.add_enabled(
+                                self.recorder.is_none(),
+                                TextEdit::singleline(&mut self.output_dir),
+                            );
+                        });
+                        match &self.recorder {
+                            None => {
+                                if ui.button("Record").clicked() {
+                                    self.start_recording();
+                                }
+                            }
+                            Some(recorder) => {
+                                let mut stop = false;
+                                ui.horizontal(|ui| {
+                                    stop = ui.button("Stop Recording").clicked();
+                                    ui.colored_label(Color32::RED, "●");
+                                    ui.label(format!(
+                                        "{:.1}s, {:.1} MB",
+                                        recorder.elapsed().as_secs_f32(),
+                                        recorder.bytes() as f32 / 1_000_000.0
+                                    ));
+                                });
+                                if recorder.dropped() > 0 {
+                                    ui.label(format!("Dropped frames: {}", recorder.dropped()));
+                                }
+                                if stop {
+                                    self.stop_recording();
+                                }
+                            }
+                        }
+                        match &self.record_status {
+                            Some(Ok(msg)) => {
+                                ui.label(msg);
+                            }
+                            Some(Err(err)) => {
+                                ui.colored_label(Color32::RED, err);
+                            }
+                            None => {}
+                        }
+                    });
+
+                    CollapsingHeader::new("Playback").show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut self.playback_path);
+                            if ui.button("Load").clicked() {
+                                self.load_recording();
+                            }
+                        });
+                        if let Some(err) = &self.playback_error {
+                            ui.colored_label(Color32::RED, err);
+                        }
+                        let Some(player) = &mut self.player else {
+                            return;
+                        };
+                        let mut seek = None;
+                        ui.horizontal(|ui| {
+                            let last = player.recording.len() - 1;
+                            if ui.button("⏮").clicked() {
+                                seek = Some(0);
+                            }
+                            if ui.button("◀").clicked() {
+                                seek = Some(player.frame.saturating_sub(1));
+                            }
+                            let label = if player.playing { "Pause" } else { "Play" };
+                            if ui.button(label).clicked() {
+                                if player.frame == last {
+                                    seek = Some(0);
+                                }
+                                player.playing = !player.playing;
+                            }
+                            if ui.button("▶").clicked() {
+                                seek = Some(player.frame + 1);
+                            }
+                            let mut frame = player.frame;
+                            if ui.add(Slider::new(&mut frame, 0..=last)).changed() {
+                                seek = Some(frame);
+                            }
+                            // recordings hold one frame per step
+                            ui.label(format_time(player.frame as f32 / STEPS_PER_SEC));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Speed:");
+                            ui.add(Slider::new(&mut player.speed, 0.1..=4.0).suffix("x"));
+                        });
+                        if ui.button("Close Recording").clicked() {
+                            self.player = None;
+                        } else if let Some(frame) = seek {
+                            self.seek_recording(frame);
+                        }
+                    });
+
+                    CollapsingHeader::new("Remote Control").show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            let mut listening = self.control.is_some();
+                            if ui.toggle_value(&mut listening, "Listen").changed() {
+                                if listening {
+                                    self.start_control(ui.ctx());
+                                } else {
+                                    self.control = None;
+                                }
+                            }
+                            ui.label("Host:Port:");
+                            ui.add_enabled(
+                                self.control.is_none(),
+                                TextEdit::singleline(&mut self.control_addr).desired_width(120.0),
+                            );
+                        });
+                        match (&self.control, &self.control_error) {
+                            (Some(control), _) => {
+                                ui.colored_label(
+                                    Color32::GREEN,
+                                    format!("● Listening on {}", control.addr()),
+                                );
+                            }
+                            (None, Some(err)) => {
+                                ui.colored_label(Color32::RED, err);
+                            }
+                            (None, None) => {}
+                        }
+                        ui.label("Commands: play, pause, respawn, randomize, seed <seed>");
+                    });
+
+                    CollapsingHeader::new("Network Stream").show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            let mut streaming = self.streamer.is_some();
+                            if ui.toggle_value(&mut streaming, "Stream").changed() {
+                                if streaming {
+                                    self.start_stream();
+                                } else {
+                                    self.streamer = None;
+                                }
+                            }
+                            ui.label("Host:Port:");
+                            ui.add_enabled(
+                                self.streamer.is_none(),
+                                TextEdit::singleline(&mut self.stream_target).desired_width(120.0),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Rate:");
+                            ui.add(Slider::new(&mut self.stream_rate, 1.0..=60.0).suffix(" Hz"));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Every Nth Particle:");
+                            ui.add(Slider::new(&mut self.stream_stride, 1..=16));
+                        });
+                        match (&self.streamer, &self.stream_error) {
+                            (Some(streamer), _) => match streamer.error() {
+                                Some(err) => {
+                                    ui.colored_label(Color32::RED, format!("● {}", err));
+                                }
+                                None => {
+                                    ui.colored_label(
+                                        Color32::GREEN,
+                                        format!(
+                                            "● {} packets sent to {}",
+                                            streamer.packets(),
+                                            streamer.target()
+                                        ),
+                                    );
+                                }
+                            },
+                            (None, Some(err)) => {
+                                ui.colored_label(Color32::RED, err);
+                            }
+                            (None, None) => {}
+                        }
+                    });
+
+                    CollapsingHeader::new("Modulation").show(ui, |ui| self.modulation_ui(ui));
+                }
+
+                CollapsingHeader::new("Gallery").show(ui, |ui| {
+                    ui.label("Some setups to start from. Clicking one starts it playing.");
+                    let mut load = None;
+                    for (i, preset) in self.gallery.iter().enumerate() {
+                        if ui
+                            .button(&preset.name)
+                            .on_hover_text(&preset.description)
+                            .clicked()
+                        {
+                            load = Some(i);
+                        }
+                    }
+                    if let Some(i) = load {
+                        self.load_preset(&self.gallery[i].clone());
+                        self.play();
+                    }
+                });
+
+                CollapsingHeader::new("Presets").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut self.preset_name);
+                        if ui.button("Save").clicked() {
+                            self.save_preset();
+                        }
+                    });
+                    let mut load = None;
+                    let mut delete = None;
+                    for (i, preset) in self.presets.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button("Load").clicked() {
+                                load = Some(i);
+                            }
+                            if ui.button("Delete").clicked() {
+                                delete = Some(i);
+                            }
+                            ui.label(&preset.name);
+                        });
+                    }
+                    if let Some(i) = load {
+                        self.load_preset(&self.presets[i].clone());
+                    }
+                    if let Some(i) = delete {
+                        self.presets.remove(i);
+                    }
+                });
+
+                CollapsingHeader::new("Advanced Physics").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Integrator:");
+                        let prev = self.sim.integrator;
+                        ComboBox::from_id_source("integrator")
+                            .selected_text(self.sim.integrator.name())
+                            .show_ui(ui, |ui| {
+                                for integrator in Integrator::ALL {
+                                    ui.selectable_value(
+                                        &mut self.sim.integrator,
+                                        integrator,
+                                        integrator.name(),
+                                    );
+                                }
+                            });
+                        if self.sim.integrator != prev {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Force Scale:");
+                        if ui
+                            .add(Slider::new(
+                                &mut self.sim.physics.force_scale,
+                                0.0..=MAX_FORCE_SCALE,
+                            ))
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Friction:");
+                        if ui
+                            .add(Slider::new(&mut self.sim.physics.friction, 0.0..=1.0))
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Substeps:");
+                        if ui
+                            .add(Slider::new(
+                                &mut self.sim.physics.substeps,
+                                1..=MAX_SUBSTEPS,
+                            ))
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Border Margin:");
+                        if ui
+                            .add(Slider::new(
+                                &mut self.sim.physics.border_margin,
+                                0.0..=MAX_BORDER_MARGIN,
+                            ))
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Gravity:");
+                        if ui
+                            .add(Slider::new(
+                                &mut self.sim.physics.gravity,
+                                0.0..=MAX_GRAVITY,
+                            ))
+                            .on_hover_text("Slowly pulls distant clusters together")
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let physics = &mut self.sim.physics;
+                        let mut changed = ui
+                            .checkbox(&mut physics.remove_drift, "Remove Net Drift")
+                            .on_hover_text("Stops uneven forces from moving everything at once")
+                            .changed();
+                        ui.add_enabled_ui(physics.remove_drift, |ui| {
+                            changed |= ui
+                                .checkbox(&mut physics.recenter, "Recenter")
+                                .on_hover_text("Slowly moves everything back to the middle")
+                                .changed();
+                        });
+                        if changed {
+                            self.seed = self.sim.export();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Force Expression:");
+                        let mut script = self.sim.force_script().to_string();
+                        let edit = TextEdit::singleline(&mut script)
+                            .hint_text("force")
+                            .desired_width(160.0);
+                        // edits past the longest script are ignored rather than dropping it
+                        if ui.add(edit).changed() && script.len() <= expr::MAX_LEN {
+                            // errors are shown below, the builtin kernel runs until it's fixed
+                            let _ = self.sim.set_force_script(&script);
+                            self.seed = self.sim.export();
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Force between two particles, using r, radius and force.\n\
+                     Leave empty for the builtin kernel, which is just `force`.",
+                    );
+                    if let Some(err) = self.sim.script_error() {
+                        ui.colored_label(Color32::RED, err);
+                    } else if !self.sim.force_script().trim().is_empty() {
+                        ui.colored_label(
+                            Color32::YELLOW,
+                            "Scripted forces are slower to simulate.",
+                        );
+                    }
+                });
+
+                CollapsingHeader::new("Reactions").show(ui, |ui| {
+                    ui.label("Particles that stay close to another type can turn into a third.");
+                    self.reactions_ui(ui);
+                });
+
+                CollapsingHeader::new("Attractors").show(ui, |ui| {
+                    ui.label("Fixed points that pull in every particle nearby.");
+                    self.attractors_ui(ui);
+                });
+
+                CollapsingHeader::new("Bonds").show(ui, |ui| {
+                    ui.label("Springs between particles, for building bodies out of them.");
+                    self.bonds_ui(ui);
+                });
+
+                CollapsingHeader::new("Flow").show(ui, |ui| {
+                    let prev = self.sim.flow;
+                    let flow = &mut self.sim.flow;
+                    ui.horizontal(|ui| {
+                        ui.label("Kind:");
+                        ComboBox::from_id_source("flow_kind")
+                            .selected_text(flow.kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in FlowKind::ALL {
+                                    ui.selectable_value(&mut flow.kind, kind, kind.name());
+                                }
+                            });
+                        ui.checkbox(&mut self.show_flow, "Show Arrows");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Strength:");
+                        ui.add(Slider::new(
+                            &mut flow.strength,
+                            -MAX_FLOW_STRENGTH..=MAX_FLOW_STRENGTH,
+                        ))
+                        .on_hover_text("0 turns the flow off");
+                    });
+                    ui.add_enabled_ui(flow.kind != FlowKind::Vortex, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Angle:");
+                            ui.add(Slider::new(&mut flow.angle, 0.0..=360.0).suffix("°"));
+                        });
+                    });
+                    if ui.button("Turn Off").clicked() {
+                        flow.strength = 0.0;
+                    }
+                    if self.sim.flow != prev {
+                        self.seed = self.sim.export();
+                    }
+                });
+
+                CollapsingHeader::new("Settling").show(ui, |ui| {
+                    ui.label("Notices when the particles have nearly stopped moving.");
+                    ui.horizontal(|ui| {
+                        ui.label("Speed:");
+                        ui.add(
+                            Slider::new(&mut self.sim.settle_speed, 0.0..=MAX_SETTLE_SPEED)
+                                .logarithmic(true),
+                        )
+                        .on_hover_text(
+                            "Settled once particles move less than this far per step on \
+                             average. 0 turns it off.",
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("For:");
+                        ui.add(
+                            Slider::new(&mut self.sim.settle_steps, 1..=MAX_SETTLE_STEPS)
+                                .suffix(" steps"),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Then:");
+                        ComboBox::from_id_source("settle_action")
+                            .selected_text(self.settle_action.name())
+                            .show_ui(ui, |ui| {
+                                for action in SettleAction::ALL {
+                                    ui.selectable_value(
+                                        &mut self.settle_action,
+                                        action,
+                                        action.name(),
+                                    );
+                                }
+                            });
+                    });
+                    ui.label(format!("Moving {:.3} per step", self.sim.motion()));
+                    if self.sim.settled() {
+                        ui.colored_label(Color32::LIGHT_GREEN, "Settled");
+                    }
+                });
+
+                CollapsingHeader::new("Auto-Tune").show(ui, |ui| {
+                    ui.label(
+                        "Keeps mutating the forces and counts, and keeps the changes that do \
+                         better at the goal.",
+                    );
+                    let tuning = self.tuner.is_some();
+                    ui.add_enabled_ui(!tuning, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Goal:");
+                            ComboBox::from_id_source("tune_goal")
+                                .selected_text(self.tune_goal.name())
+                                .show_ui(ui, |ui| {
+                                    for goal in TuneGoal::ALL {
+                                        let name = goal.name();
+                                        ui.selectable_value(&mut self.tune_goal, goal, name);
+                                    }
+                                });
+                        });
+                        if self.tune_goal == TuneGoal::TargetSpeed {
+                            ui.horizontal(|ui| {
+                                ui.label("Speed:");
+                                ui.add(Slider::new(&mut self.tune_speed, 0.0..=MAX_TUNE_SPEED))
+                                    .on_hover_text(
+                                        "How far particles should move per step on average.",
+                                    );
+                            });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Try For:");
+                        ui.add(
+                            Slider::new(&mut self.tune_steps, MIN_TUNE_STEPS..=MAX_TUNE_STEPS)
+                                .suffix(" steps"),
+                        )
+                        .on_hover_text(
+                            "Each try settles in for the first half and is measured over the \
+                            second.",
+                        );
+                    });
+                    ui.label("Changes are as big as the mutation strength.");
+                    if !tuning && ui.button("Start Tuning").clicked() {
+                        self.start_tuning();
+                    }
+                    let Some(tuner) = &self.tuner else {
+                        return;
+                    };
+                    let progress = tuner.steps as f32 / self.tune_steps as f32;
+                    ui.add(ProgressBar::new(progress).text(format!("Try {}", tuner.tries + 1)));
+                    let best = match tuner.best_score {
+                        Some(score) => format!("Best: {}", tuner.goal.describe(score)),
+                        None => "Measuring the starting setup".to_string(),
+                    };
+                    ui.label(best);
+                    ui.label(format!(
+                        "Kept {} of {} changes",
+                        tuner.accepted.len(),
+                        tuner.tries.saturating_sub(1)
+                    ));
+                    for &(try_num, score) in tuner.accepted.iter().rev().take(5) {
+                        ui.label(format!("Try {}: {}", try_num, tuner.goal.describe(score)));
+                    }
+                    if ui
+                        .button("Stop Tuning")
+                        .on_hover_text(
+                            "Goes back to the best setup so far, which is also in History.",
+                        )
+                        .clicked()
+                    {
+                        self.stop_tuning();
+                    }
+                });
+
+                let inspector = CollapsingHeader::new("Particle Inspector")
+                    .open(std::mem::take(&mut self.reveal_inspector).then_some(true))
+                    .show(ui, |ui| {
+                        self.group_ui(ui);
+                        ui.separator();
+
+                        let (class, index) = &mut self.selected_particle;
+                        ui.horizontal(|ui| {
+                            ui.label("Type:");
+                            ComboBox::from_id_source("inspector_type")
+                                .selected_text(&self.sim.params[*class].name)
+                                .show_ui(ui, |ui| {
+                                    for (i, p) in self.sim.params.iter().enumerate() {
+                                        ui.selectable_value(class, i, &p.name);
+                                    }
+                                });
+                        });
+                        let dots = &self.sim.dots[*class];
+                        if dots.is_empty() {
+                            ui.label("No particles of this type.");
+                            return;
+                        }
+                        *index = (*index).min(dots.len() - 1);
+                        ui.horizontal(|ui| {
+                            ui.label("Index:");
+                            ui.add(Slider::new(index, 0..=dots.len() - 1));
+                        });
+                        let Dot { pos, vel, .. } = dots[*index];
+                        let mut following = self.follow == Follow::SelectedParticle;
+                        if ui
+                            .checkbox(&mut following, "Follow Selected Particle")
+                            .changed()
+                        {
+                            self.set_follow(if following {
+                                Follow::SelectedParticle
+                            } else {
+                                Follow::Off
+                            });
+                        }
+                        ui.checkbox(&mut self.track_selected, "Track Across Respawns")
+                            .on_hover_text(
+                                "Respawning picks the particle of this type nearest to where \
+                                 this one was, instead of keeping the index",
+                            );
+                        ui.label(format!("Position: ({:.1}, {:.1})", pos.x, pos.y));
+                        ui.label(format!("Velocity: ({:.2}, {:.2})", vel.x, vel.y));
+                        ui.label(format!("Speed: {:.2}", vel.length()));
+                    });
+                self.inspector_open = inspector.body_returned.is_some();
+
+                CollapsingHeader::new("Statistics").show(ui, |ui| {
+                    ui.checkbox(&mut self.collect_stats, "Collect Statistics");
+                    if let Some(latest) = self.stats.back() {
+                        ui.label(format!(
+                            "Speed: {:.2} mean, {:.2} max",
+                            latest.mean_speed, latest.max_speed
+                        ));
+                        ui.label(format!("Kinetic Energy: {:.1}", latest.kinetic_energy));
+                        ui.label(format!("Clusters: {}", latest.clusters));
+                    }
+
+                    let line = |name: &str, value: fn(&SimStats) -> f32| {
+                        let points: PlotPoints = self
+                            .stats
+                            .iter()
+                            .map(|s| [s.time as f64, value(s) as f64])
+                            .collect();
+                        Line::new(points).name(name)
+                    };
+                    let plot = |id: &str| {
+                        Plot::new(id)
+                            .height(80.0)
+                            .include_y(0.0)
+                            .allow_drag(false)
+                            .allow_zoom(false)
+                            .allow_scroll(false)
+                            .legend(Legend::default())
+                    };
+                    plot("speed_plot").show(ui, |plot| {
+                        plot.line(line("Mean Speed", |s| s.mean_speed));
+                        plot.line(line("Max Speed", |s| s.max_speed));
+                    });
+                    plot("energy_plot").show(ui, |plot| {
+                        plot.line(line("Kinetic Energy", |s| s.kinetic_energy));
+                    });
+                    plot("cluster_plot").show(ui, |plot| {
+                        plot.line(line("Clusters", |s| s.clusters as f32));
+                    });
+                });
+
+                CollapsingHeader::new("Activity").show(ui, |ui| {
+                    Plot::new("activity_plot")
+                        .height(120.0)
+                        .include_y(0.0)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .legend(Legend::default())
+                        .show(ui, |plot| {
+                            for (i, p) in self.sim.params.iter().enumerate() {
+                                let points: PlotPoints = self
+                                    .activity
+                                    .iter()
+                                    .map(|(time, speeds)| [*time as f64, speeds[i] as f64])
+                                    .collect();
+                                plot.line(Line::new(points).color(p.color).name(&p.name));
+                            }
+                        });
+                });
+
+                CollapsingHeader::new("Force Matrix").show(ui, |ui| {
+                    Grid::new("force_matrix")
+                        .spacing(Vec2::splat(2.0))
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for p in &self.sim.params {
+                                ui.colored_label(p.color, &p.name);
+                            }
+                            ui.end_row();
+
+                            for i in 0..N {
+                                ui.colored_label(
+                                    self.sim.params[i].color,
+                                    &self.sim.params[i].name,
+                                );
+                                for j in 0..N {
+                                    let (rect, resp) = ui.allocate_exact_size(
+                                        Vec2::splat(24.0),
+                                        Sense::click_and_drag(),
+                                    );
+                                    if resp.clicked() || resp.drag_started() {
+                                        self.selected_param = (i, j);
+                                    }
+                                    if resp.dragged() {
+                                        let power = &mut self.sim.params[i].power[j];
+                                        *power = (*power - resp.drag_delta().y * 0.5)
+                                            .clamp(MIN_POWER, MAX_POWER);
+                                        self.seed = self.sim.export();
+                                    }
+
+                                    // red pulls in, blue pushes away, and disabled pairs are blank
+                                    let power = self.sim.params[i].power[j];
+                                    let enabled = self.sim.params[i].enabled[j];
+                                    let t = if enabled {
+                                        (power.abs() / MAX_POWER).min(1.0)
+                                    } else {
+                                        0.0
+                                    };
+                                    let strong = if power > 0.0 {
+                                        Color32::from_rgb(230, 40, 40)
+                                    } else {
+                                        Color32::from_rgb(40, 80, 230)
+                                    };
+                                    let col = Rgba::from(Color32::from_gray(30)) * (1.0 - t)
+                                        + Rgba::from(strong) * t;
+                                    ui.painter().rect_filled(rect, 2.0, col);
+                                    if self.selected_param == (i, j) {
+                                        ui.painter().rect_stroke(
+                                            rect,
+                                            2.0,
+                                            Stroke::new(1.5, Color32::WHITE),
+                                        );
+                                    }
+                                    if !enabled {
+                                        let stroke = Stroke::new(1.0, Color32::from_gray(80));
+                                        ui.painter().line_segment(
+                                            [rect.left_bottom(), rect.right_top()],
+                                            stroke,
+                                        );
+                                    }
+                                    resp.on_hover_text(if enabled {
+                                        format!("{power:.1}")
+                                    } else {
+                                        "disabled".to_string()
+                                    });
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    let (i, j) = self.selected_param;
+                    ui.horizontal(|ui| {
+                        ui.colored_label(self.sim.params[i].color, &self.sim.params[i].name);
+                        ui.label("→");
+                        ui.colored_label(self.sim.params[j].color, &self.sim.params[j].name);
+                    });
+                    let (p, locks) = (&mut self.sim.params[i], &mut self.locks[i]);
+                    let mut changed = ui.checkbox(&mut p.enabled[j], "Enabled").changed();
+                    let enabled = p.enabled[j];
+                    ui.horizontal(|ui| {
+                        ui.set_enabled(enabled);
+                        ui.label("Power:");
+                        let slider = Slider::new(&mut p.power[j], MIN_POWER..=MAX_POWER);
+                        changed |= locked_slider(ui, &mut locks.power[j], slider).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.set_enabled(enabled);
+                        ui.label("Radius:");
+                        let slider = Slider::new(&mut p.radius[j], MIN_RADIUS..=MAX_RADIUS);
+                        changed |= locked_slider(ui, &mut locks.radius[j], slider).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.set_enabled(enabled);
+                        ui.label("Spin:");
+                        let slider = Slider::new(&mut p.spin[j], MIN_SPIN..=MAX_SPIN);
+                        changed |= locked_slider(ui, &mut locks.spin[j], slider).changed();
+                    });
+                    if changed {
+                        self.seed = self.sim.export();
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Force:").on_hover_text(
+                            "Drag the curve up or down to change the power, \
+                            or its edge to change the radius",
+                        );
+                        ui.checkbox(&mut self.curve_reverse, "Reverse");
+                        ui.checkbox(&mut self.curve_all, "All Types");
+                    });
+                    let pairs: Vec<(usize, usize)> = if self.curve_all {
+                        (0..N).map(|j| (i, j)).collect()
+                    } else if self.curve_reverse && i != j {
+                        vec![(i, j), (j, i)]
+                    } else {
+                        vec![(i, j)]
+                    };
+                    // stretch to the widest radius in use, so big kernels aren't cut off
+                    let x_max = match self.curve_drag {
+                        Some(CurveDrag::Radius { x_max }) => x_max,
+                        _ => {
+                            let max_radius = (self.sim.params.iter())
+                                .flat_map(|p| p.radius)
+                                .fold(MIN_RADIUS, f32::max);
+                            max_radius * 1.1
+                        }
+                    };
+                    let radius = self.sim.params[i].radius[j];
+                    let plot = Plot::new("force_plot")
+                        .height(100.0)
+                        .include_x(0.0)
+                        .include_x(x_max)
+                        .include_y(0.0)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .legend(Legend::default())
+                        .show(ui, |plot| {
+                            for (a, b) in pairs {
+                                let points: PlotPoints = (0..=200)
+                                    .map(|k| {
+                                        let r = k as f32 / 200.0 * x_max;
+                                        [r as f64, self.sim.pull(a, b, r) as f64]
+                                    })
+                                    .collect();
+                                let (p, q) = (&self.sim.params[a], &self.sim.params[b]);
+                                let name = format!("{} → {}", p.name, q.name);
+                                plot.line(Line::new(points).color(q.color).name(name));
+                            }
+                            let edge = plot.screen_from_plot(PlotPoint::new(radius, 0.0)).x;
+                            (edge, plot.pointer_coordinate_drag_delta())
+                        });
+                    self.drag_curve(ui, plot.response, plot.inner, x_max);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Distances:");
+                        let refresh = ui.button("Refresh").clicked();
+                        let fresh = self.distances.as_ref().is_some_and(|h| {
+                            h.pair == (i, j) && h.time.elapsed() < HISTOGRAM_INTERVAL
+                        });
+                        if refresh || !fresh {
+                            let (a, b) = (&self.sim.dots[i], &self.sim.dots[j]);
+                            let (bins, sample_every) = distance_histogram(a, b, i == j);
+                            self.distances = Some(DistanceHistogram {
+                                pair: (i, j),
+                                bins,
+                                sample_every,
+                                time: Instant::now(),
+                            });
+                        }
+                        if let Some(h) = self.distances.as_ref().filter(|h| h.sample_every > 1) {
+                            ui.label(format!("(1 in {} sampled)", h.sample_every));
+                        }
+                    });
+                    if let Some(h) = &self.distances {
+                        let width = (MAX_RADIUS * 2.0 / HISTOGRAM_BINS as f32) as f64;
+                        let bars = (h.bins.iter().enumerate())
+                            .map(|(k, &n)| {
+                                Bar::new((k as f64 + 0.5) * width, n as f64).width(width)
+                            })
+                            .collect();
+                        let radius = self.sim.params[i].radius[j];
+                        Plot::new("distance_plot")
+                            .height(80.0)
+                            .include_x(0.0)
+                            .include_x(MAX_RADIUS * 2.0)
+                            .include_y(0.0)
+                            .allow_drag(false)
+                            .allow_zoom(false)
+                            .allow_scroll(false)
+                            .show(ui, |plot| {
+                                plot.bar_chart(BarChart::new(bars).color(self.sim.params[j].color));
+                                plot.vline(VLine::new(radius).color(Color32::WHITE).name("Radius"));
+                            });
+                    }
+                });
+
+                CollapsingHeader::new("Shortcuts").show(ui, |ui| {
+                    ui.label("Space: play / pause");
+                    ui.label("R: respawn");
+                    ui.label("Right Arrow: step while paused");
+                    ui.label("N: randomize");
+                    ui.label("Ctrl+Z / Ctrl+Shift+Z: undo / redo");
+                    ui.label("Ctrl+V: paste & apply a seed");
+                    ui.label("Home: reset view");
+                    ui.label("Tab: hide / show this panel");
+                    ui.label("1-5 / Ctrl+1-5: go to / save a bookmarked view");
+                    ui.label("+ / -: zoom in / out");
+                    ui.label("Click: select particle");
+                    ui.label("Shift+Drag: select a group of particles");
+                });
+
+                ui.add_space(10.0);
+                ui.horizontal_wrapped(|ui| {
+                    ui.selectable_value(&mut self.class_tab, None, "All Types");
+                    for (i, p) in self.sim.params.iter().enumerate() {
+                        let name = RichText::new(&p.name).color(p.color);
+                        ui.selectable_value(&mut self.class_tab, Some(i), name);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Hide Pairs Weaker Than:");
+                    ui.add(Slider::new(&mut self.force_filter, 0.0..=MAX_POWER))
+                        .on_hover_text(
+                            "Only shows the pairs whose power is at least this strong either \
+                             way, to find the ones that matter",
+                        );
+                });
+
+                let tab = self.class_tab;
+                for i in (0..N).filter(|&i| tab.is_none_or(|tab| tab == i)) {
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        let name = heading(&self.sim.params[i].name);
+                        ui.colored_label(self.sim.params[i].color, name);
+                        if ui
+                            .small_button("Randomize")
+                            .on_hover_text(
+                                "New forces between this type and every type, both ways, and a \
+                                 new count. Lock anything to keep it.",
+                            )
+                            .clicked()
+                        {
+                            self.randomize_class(i);
+                        }
+                    });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        let name =
+                            TextEdit::singleline(&mut self.sim.params[i].name).desired_width(80.0);
+                        if ui.add(name).changed() {
+                            let name = &mut self.sim.params[i].name;
+                            if let Some((end, _)) = name.char_indices().nth(MAX_NAME_LEN) {
+                                name.truncate(end);
+                            }
+                            self.seed = self.sim.export();
+                        }
+                        ui.label("Color:");
+                        let mut rgb = [
+                            self.sim.params[i].color.r(),
+                            self.sim.params[i].color.g(),
+                            self.sim.params[i].color.b(),
+                        ];
+                        if ui.color_edit_button_rgb(&mut rgb).changed() {
+                            self.sim.params[i].color = Rgba::from_rgb(rgb[0], rgb[1], rgb[2]);
+                            self.seed = self.sim.export();
+                        }
+                    });
+
+                    // only changes how the type is drawn, so it's saved with the appearance
+                    ui.horizontal(|ui| {
+                        let style = self.appearance.dot_style_mut(i);
+                        ui.label("Size:");
+                        ui.add(Slider::new(&mut style.size, MIN_DOT_SIZE..=MAX_DOT_SIZE));
+                        ComboBox::from_id_source(("dot_shape", i))
+                            .selected_text(style.shape.name())
+                            .show_ui(ui, |ui| {
+                                for shape in DotShape::ALL {
+                                    ui.selectable_value(&mut style.shape, shape, shape.name());
+                                }
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Count:");
+                        if locked_slider(
+                            ui,
+                            &mut self.locks[i].count,
+                            Slider::new(&mut self.sim.params[i].count, MIN_COUNT..=MAX_COUNT),
+                        )
+                        .changed()
+                        {
+                            // otherwise removed particles would linger until the next respawn
+                            self.update_count_scale();
+                            self.sim.fit_counts(&mut SmallRng::from_entropy());
+                            self.seed = self.sim.export();
+                        }
+                        let spawned = self.sim.spawn_count(i);
+                        if spawned != self.sim.params[i].count {
+                            ui.colored_label(Color32::YELLOW, format!("({} spawned)", spawned));
+                        }
+                        // reactions move particles between types as it runs
+                        let live = self.sim.dots[i].len();
+                        if live != spawned {
+                            ui.colored_label(Color32::LIGHT_BLUE, format!("({} now)", live));
+                        }
+                    });
+
+                    // not part of the seed, since it's a tradeoff for slow machines
+                    ui.horizontal(|ui| {
+                        ui.label("Update Every:");
+                        ui.add(
+                            Slider::new(&mut self.sim.strides[i], 1..=MAX_STRIDE).suffix(" steps"),
+                        )
+                        .on_hover_text(
+                            "Reuses the forces from this type in between, which is faster \
+                            but less accurate",
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Lifetime:");
+                        let p = &mut self.sim.params[i];
+                        if ui
+                            .add(Slider::new(&mut p.lifetime, 0.0..=MAX_LIFETIME).suffix(" s"))
+                            .on_hover_text("0 lives forever")
+                            .changed()
+                        {
+                            self.seed = self.sim.export();
+                        }
+                        let p = &mut self.sim.params[i];
+                        let prev = p.death;
+                        ui.add_enabled_ui(p.lifetime > 0.0, |ui| {
+                            ComboBox::from_id_source(("death", i))
+                                .selected_text(p.death.name())
+                                .width(80.0)
+                                .show_ui(ui, |ui| {
+                                    for death in Death::ALL {
+                                        ui.selectable_value(&mut p.death, death, death.name());
+                                    }
+                                });
+                        });
+                        if self.sim.params[i].death != prev {
+                            self.seed = self.sim.export();
+                        }
+                    });
+
+                    // the selected pair stays, so one being dragged below the filter doesn't vanish
+                    let shown: Vec<usize> = (0..N)
+                        .filter(|&j| {
+                            self.sim.params[i].power[j].abs() >= self.force_filter
+                                || self.selected_param == (i, j)
+                        })
+                        .collect();
+                    ui.horizontal(|ui| {
+                        ui.vertical(|ui| {
+                            for &j in &shown {
+                                ui.horizontal(|ui| {
+                                    if ui
+                                        .checkbox(&mut self.sim.params[i].enabled[j], "")
+                                        .on_hover_text("Whether this type feels the other at all")
+                                        .changed()
+                                    {
+                                        self.seed = self.sim.export();
+                                    }
+                                    ui.label("Power (");
+                                    ui.colored_label(
+                                        self.sim.params[j].color,
+                                        &self.sim.params[j].name,
+                                    );
+                                    ui.label(")");
+                                    ui.set_enabled(self.sim.params[i].enabled[j]);
+                                    if locked_slider(
+                                        ui,
+                                        &mut self.locks[i].power[j],
+                                        Slider::new(
+                                            &mut self.sim.params[i].power[j],
+                                            MIN_POWER..=MAX_POWER,
+                                        ),
+                                    )
+                                    .changed()
+                                    {
+                                        self.selected_param = (i, j);
+                                        self.seed = self.sim.export();
+                                    }
+                                });
+                            }
+                        });
+                        ui.vertical(|ui| {
+                            for &j in &shown {
+                                ui.horizontal(|ui| {
+                                    ui.label("Radius (");
+                                    ui.colored_label(
+                                        self.sim.params[j].color,
+                                        &self.sim.params[j].name,
+                                    );
+                                    ui.label(")");
+                                    ui.set_enabled(self.sim.params[i].enabled[j]);
+                                    if locked_slider(
+                                        ui,
+                                        &mut self.locks[i].radius[j],
+                                        Slider::new(
+                                            &mut self.sim.params[i].radius[j],
+                                            MIN_RADIUS..=MAX_RADIUS,
+                                        ),
+                                    )
+                                    .changed()
+                                    {
+                                        self.selected_param = (i, j);
+                                        self.seed = self.sim.export();
+                                    }
+                                });
+                            }
+                        });
+                        ui.vertical(|ui| {
+                            for &j in &shown {
+                                ui.horizontal(|ui| {
+                                    ui.label("Spin (");
+                                    ui.colored_label(
+                                        self.sim.params[j].color,
+                                        &self.sim.params[j].name,
+                                    );
+                                    ui.label(")");
+                                    ui.set_enabled(self.sim.params[i].enabled[j]);
+                                    if locked_slider(
+                                        ui,
+                                        &mut self.locks[i].spin[j],
+                                        Slider::new(
+                                            &mut self.sim.params[i].spin[j],
+                                            MIN_SPIN..=MAX_SPIN,
+                                        ),
+                                    )
+                                    .changed()
+                                    {
+                                        self.selected_param = (i, j);
+                                        self.seed = self.sim.export();
+                                    }
+                                });
+                            }
+                        });
+                    });
+                    if shown.len() < N {
+                        ui.label(format!("{} weaker pairs hidden", N - shown.len()));
+                    }
+                }
+            });
+        } else {
+            self.panel_strip(ctx);
+        }
+
+        let background = egui::Frame::none().fill(self.appearance.background);
+        CentralPanel::default().frame(background).show(ctx, |ui| {
+            let (resp, paint) =
+                ui.allocate_painter(ui.available_size_before_wrap(), Sense::click_and_drag());
+            let center = resp.rect.center();
+            self.view_size = resp.rect.size();
+
+            // move the view along with the followed target, so panning still offsets it
+            let followed = self.follow_pos();
+            if let (Some(pos), Some(prev)) = (followed, self.prev_follow_pos) {
+                self.view.pos += pos - prev;
+            }
+            self.prev_follow_pos = followed;
+
+            // fit the whole world into the bottom right corner
+            let map_scale = MINIMAP_SIZE / self.sim.world_w.max(self.sim.world_h);
+            let map_size = Vec2::new(self.sim.world_w, self.sim.world_h) * map_scale;
+            let map_rect =
+                Rect::from_min_size(resp.rect.max - map_size - Vec2::splat(10.0), map_size);
+            // only presses egui handed to the view count, so one that started on a widget
+            // and wandered over the minimap doesn't move the view
+            let on_minimap = self.show_minimap
+                && resp.is_pointer_button_down_on()
+                && ui
+                    .input()
+                    .pointer
+                    .press_origin()
+                    .is_some_and(|pos| map_rect.contains(pos));
+
+            let scroll = ui.input().scroll_delta.y;
+            if on_minimap || resp.dragged() || resp.hovered() && scroll != 0.0 {
+                self.glide = None;
+            }
+
+            // the group's indices would point at different particles once the counts change
+            let counts = self.sim.dots.each_ref().map(Vec::len);
+            if self.group_counts != counts {
+                self.group.clear();
+            }
+            if self.bond_from.is_some_and(|(_, picked)| picked != counts) {
+                self.bond_from = None;
+            }
+            if resp.drag_started() && ui.input().modifiers.shift && self.player.is_none() {
+                self.group_box = ui.input().pointer.press_origin();
+            }
+
+            if on_minimap {
+                if let Some(pos) = resp.interact_pointer_pos() {
+                    let pos = pos.clamp(map_rect.min, map_rect.max);
+                    self.view.pos = (pos - map_rect.min) / map_scale;
+                }
+            } else if let Some(start) = self.group_box {
+                if resp.drag_released() {
+                    let end = ui.input().pointer.interact_pos().unwrap_or(start);
+                    let (a, b) = (
+                        self.view.to_world(center, start),
+                        self.view.to_world(center, end),
+                    );
+                    self.select_group(a, b);
+                    self.group_box = None;
+                }
+            } else if resp.dragged() {
+                self.view.pos -= resp.drag_delta() / self.view.zoom;
+            } else if resp.clicked() {
+                // egui doesn't report a click once the pointer has moved far enough to drag
+                if let Some(pos) = resp.interact_pointer_pos() {
+                    let pos = self.view.to_world(center, pos);
+                    let max_dist = PICK_RADIUS / self.view.zoom;
+                    if self.bond_tool && self.player.is_none() {
+                        self.bond_click(pos, max_dist);
+                        ctx.request_repaint();
+                    } else if let Some(selected) = self.particle_at(pos, max_dist) {
+                        self.selected_particle = selected;
+                        self.reveal_inspector = !self.inspector_open;
+                        self.prev_follow_pos = None;
+                        ctx.request_repaint();
+                    } else if resp.double_clicked() {
+                        self.fit_view();
+                    }
+                }
+            }
+            if resp.hovered() && scroll != 0.0 {
+                // keep the world point under the cursor (or the followed particle) in place
+                let anchor = match followed {
+                    Some(pos) => self.view.to_screen(center, pos),
+                    None => resp.hover_pos().unwrap_or(center),
+                };
+                let world = self.view.to_world(center, anchor);
+                self.view.zoom =
+                    (self.view.zoom * (scroll * ZOOM_SPEED).exp()).clamp(MIN_ZOOM, MAX_ZOOM);
+                self.view.pos = world - (anchor - center) / self.view.zoom;
+            }
+            let view = self.view;
+
+            if self.appearance.boundary_width > 0.0 {
+                let world = Rect::from_min_max(
+                    view.to_screen(center, Vec2::ZERO),
+                    view.to_screen(center, self.sim.border_size()),
+                );
+                let stroke = Stroke::new(self.appearance.boundary_width, self.appearance.boundary);
+                paint.rect_stroke(world, 0.0, stroke);
+            }
+
+            if self.show_grid {
+                draw_grid(&paint, resp.rect, view);
+            }
+
+            if !self.play {
+                let spawn_center = Vec2::new(self.sim.world_w, self.sim.world_h) / 2.0;
+                let rings = if self.sim.spawn_rings { N } else { 1 };
+                for ring in 1..=rings {
+                    let radius = self.sim.spawn_radius * ring as f32 / rings as f32;
+                    let points: Vec<_> = (0..=64)
+                        .map(|i| {
+                            let angle = i as f32 / 64.0 * std::f32::consts::TAU;
+                            view.to_screen(center, spawn_center + Vec2::angled(angle) * radius)
+                        })
+                        .collect();
+                    let stroke = Stroke::new(1.0, Color32::from_gray(60));
+                    paint.extend(Shape::dashed_line(&points, stroke, 6.0, 6.0));
+                }
+            }
+
+            if self.trail_len > 0 && self.player.is_none() {
+                let len = self.trails.len();
+                for (age, (prev, next)) in self
+                    .trails
+                    .iter()
+                    .zip(self.trails.iter().skip(1))
+                    .enumerate()
+                {
+                    let alpha = (age + 1) as f32 / len as f32 * 0.5;
+                    for i in 0..N {
+                        // counts changed since this snapshot, so its indices no longer line up
+                        if prev[i].len() != self.sim.dots[i].len()
+                            || next[i].len() != self.sim.dots[i].len()
+                        {
+                            continue;
+                        }
+                        let col: Color32 = (self.sim.params[i].color * alpha).into();
+                        let stroke = Stroke::new(1.0, col);
+                        for (&a, &b) in prev[i].iter().zip(&next[i]) {
+                            // skip particles that were teleported by the border
+                            if (b - a).length_sq() < 50.0 * 50.0 {
+                                paint.line_segment(
+                                    [view.to_screen(center, a), view.to_screen(center, b)],
+                                    stroke,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+
+            // how far along to the next step we are, which lags a step behind but
+            // keeps motion smooth when the display refreshes faster than it steps
+            let t = match self.smooth_motion && self.play {
+                true => {
+                    (self.prev_time.elapsed().as_secs_f32() * 1000.0 / self.step_interval).min(1.0)
+                }
+                false => 1.0,
+            };
+            let shown_pos = |i: usize, j: usize, dot: &Dot| {
+                // respawns and count changes leave nothing to move from
+                let prev = &self.prev_positions[i];
+                let smooth = t < 1.0 && prev.len() == self.sim.dots[i].len();
+                // don't slide particles that were teleported by the border
+                match smooth && (dot.pos - prev[j]).length_sq() < 50.0 * 50.0 {
+                    true => prev[j] + (dot.pos - prev[j]) * t,
+                    false => dot.pos,
+                }
+            };
+
+            if let Some(player) = &self.player {
+                let classes = player.recording.classes();
+                for (i, (dots, (_, col))) in player.positions.iter().zip(classes).enumerate() {
+                    let radius = self.appearance.dot_radius(i, view.zoom);
+                    let shape = self.appearance.dot_style(i).shape;
+                    for &pos in dots {
+                        draw_dot(&paint, view.to_screen(center, pos), radius, shape, *col);
+                    }
+                }
+            } else if self.render_mode == RenderMode::DensityField {
+                self.field.update(&self.sim);
+                paint.add(Shape::mesh(self.field.mesh(view, center)));
+            } else {
+                let color = self.dot_colors();
+                for (i, dots) in self.sim.dots.iter().enumerate() {
+                    let radius = self.appearance.dot_radius(i, view.zoom);
+                    let shape = self.appearance.dot_style(i).shape;
+                    for (j, dot) in dots.iter().enumerate() {
+                        let pos = view.to_screen(center, shown_pos(i, j, dot));
+                        draw_dot(&paint, pos, radius, shape, color(i, j, dot));
+                    }
+                }
+            }
+
+            if self.player.is_none() {
+                let screen_pos = |(i, j): (usize, usize)| {
+                    let dot = self.sim.dots[i].get(j)?;
+                    Some(view.to_screen(center, shown_pos(i, j, dot)))
+                };
+                let stroke = Stroke::new(1.0, Color32::from_white_alpha(100));
+                for bond in self.sim.bonds() {
+                    if let (Some(a), Some(b)) = (screen_pos(bond.a), screen_pos(bond.b)) {
+                        paint.line_segment([a, b], stroke);
+                    }
+                }
+                if let Some(pos) = self.bond_from.and_then(|(dot, _)| screen_pos(dot)) {
+                    paint.circle_stroke(pos, 6.0, Stroke::new(1.5, Color32::WHITE));
+                }
+            }
+
+            if self.show_flow && self.sim.flow.strength != 0.0 {
+                draw_flow(&paint, &self.sim, view, center);
+            }
+
+            for a in &self.sim.attractors {
+                let pos = view.to_screen(center, a.pos);
+                let col = match a.strength >= 0.0 {
+                    true => Color32::from_rgb(60, 220, 60),
+                    false => Color32::from_rgb(230, 50, 50),
+                };
+                let stroke = Stroke::new(1.5, col);
+                paint.line_segment([pos - Vec2::X * 6.0, pos + Vec2::X * 6.0], stroke);
+                paint.line_segment([pos - Vec2::Y * 6.0, pos + Vec2::Y * 6.0], stroke);
+                let faint = Stroke::new(1.0, col.linear_multiply(0.25));
+                paint.circle_stroke(pos, a.radius * view.zoom, faint);
+            }
+
+            if self.player.is_none() {
+                let stroke = Stroke::new(1.0, Color32::from_rgb(255, 220, 80));
+                for dot in self.group_dots() {
+                    paint.circle_stroke(view.to_screen(center, dot.pos), 4.0, stroke);
+                }
+            }
+            if let Some(start) = self.group_box {
+                if let Some(end) = ui.input().pointer.interact_pos() {
+                    let rect = Rect::from_two_pos(start, end);
+                    paint.rect_filled(rect, 0.0, Color32::from_white_alpha(12));
+                    paint.rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::from_white_alpha(120)));
+                }
+            }
+
+            // a click above may have just changed the selection
+            let (class, index) = self.selected_particle;
+            let highlight = self.player.is_none() && self.inspector_open
+                || self.reveal_inspector
+                || self.follow == Follow::SelectedParticle;
+            if let Some(dot) = self.sim.dots[class].get(index).filter(|_| highlight) {
+                let stroke = Stroke::new(1.0, Color32::WHITE);
+                let pos = view.to_screen(center, dot.pos);
+                paint.circle_stroke(pos, 6.0, stroke);
+                // velocities are only a few units long, so stretch the arrow to be readable
+                paint.arrow(pos, dot.vel * 10.0 * view.zoom, stroke);
+            }
+            if let Some((mark, since)) = self.respawn_mark {
+                let fade = 1.0 - since.elapsed().as_secs_f32() / RESPAWN_MARK_TIME;
+                if fade > 0.0 {
+                    let col = Color32::from_white_alpha((fade * 200.0) as u8);
+                    let pos = view.to_screen(center, mark);
+                    paint.circle_stroke(pos, 6.0, Stroke::new(1.0, col));
+                    if let Some(dot) = self.sim.dots[class].get(index).filter(|_| highlight) {
+                        let to = view.to_screen(center, dot.pos);
+                        paint.line_segment([pos, to], Stroke::new(1.0, col));
+                    }
+                    ctx.request_repaint();
+                } else {
+                    self.respawn_mark = None;
+                }
+            }
+
+            // how far the selected particle senses the type of the selected param
+            let show_radius = self.show_radius && self.player.is_none();
+            if let Some(dot) = self.sim.dots[class].get(index).filter(|_| show_radius) {
+                let other = self.selected_param.1;
+                let radius = self.sim.params[class].radius[other] * view.zoom;
+                let col: Color32 = self.sim.params[other].color.into();
+                paint.circle_stroke(
+                    view.to_screen(center, dot.pos),
+                    radius,
+                    Stroke::new(1.0, col),
+                );
+            }
+
+            if self.show_clusters && self.player.is_none() {
+                let col = Color32::from_white_alpha(90);
+                for cluster in &self.clusters {
+                    let pos = view.to_screen(center, cluster.center);
+                    let radius = cluster.spread * 2.0 * view.zoom + 4.0;
+                    paint.circle_stroke(pos, radius, Stroke::new(1.0, col));
+                    paint.text(
+                        pos,
+                        Align2::CENTER_CENTER,
+                        cluster.count.to_string(),
+                        FontId::default(),
+                        col,
+                    );
+                }
+            }
+
+            if self.show_grid {
+                draw_scale_bar(&paint, resp.rect, view.zoom);
+            }
+
+            if self.appearance.hud {
+                let corner = self.appearance.hud_corner;
+                let mut rect = resp.rect;
+                // keep clear of the minimap
+                if self.show_minimap && corner == Corner::BottomRight {
+                    rect.max.y = map_rect.min.y - 10.0;
+                }
+                let size = self.appearance.hud_text_size(resp.rect.height());
+                draw_hud(&paint, rect, &self.hud_lines(), size, corner);
+            }
+
+            if self.show_minimap {
+                let map = paint.with_clip_rect(map_rect.intersect(resp.rect));
+                let to_map = |pos: Vec2| map_rect.min + pos * map_scale;
+                map.rect_filled(map_rect, 0.0, Color32::from_black_alpha(200));
+                if let Some(player) = &self.player {
+                    let classes = player.recording.classes();
+                    for (dots, (_, col)) in player.positions.iter().zip(classes) {
+                        for &pos in dots.iter().step_by(MINIMAP_STRIDE) {
+                            map.circle_filled(to_map(pos), 1.0, *col);
+                        }
+                    }
+                } else {
+                    for i in 0..N {
+                        let col: Color32 = self.sim.params[i].color.into();
+                        for dot in self.sim.dots[i].iter().step_by(MINIMAP_STRIDE) {
+                            map.circle_filled(to_map(dot.pos), 1.0, col);
+                        }
+                    }
+                }
+                let visible = Rect::from_min_max(
+                    to_map(view.to_world(center, resp.rect.min)),
+                    to_map(view.to_world(center, resp.rect.max)),
+                );
+                map.rect_stroke(visible, 0.0, Stroke::new(1.0, Color32::YELLOW));
+                map.rect_stroke(map_rect, 0.0, Stroke::new(1.0, Color32::GRAY));
+            }
         });
+
+        if let Some((msg, since)) = &self.toast {
+            let remaining = Duration::from_secs_f32(TOAST_TIME).saturating_sub(since.elapsed());
+            if remaining.is_zero() {
+                self.toast = None;
+            } else {
+                Area::new("toast")
+                    .anchor(Align2::CENTER_BOTTOM, Vec2::new(0.0, -20.0))
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| match msg {
+                            Ok(msg) => ui.label(msg),
+                            Err(err) => ui.colored_label(Color32::RED, err),
+                        });
+                    });
+                // wake up once more to take it down, rather than repainting until then
+                ctx.request_repaint_after(remaining);
+            }
+        }
+
+        self.track_changes(ctx);
     }
 }
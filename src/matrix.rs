@@ -0,0 +1,189 @@
+//! Reading and writing the force matrices as CSV, for editing them in a spreadsheet.
+//!
+//! The file has a header row, then one row per type for each matrix. The first
+//! column says which matrix the row belongs to (`power`, `radius` or `spin`),
+//! the second names the type feeling the force, and the rest hold the force
+//! towards every type in order:
+//!
+//! ```text
+//! matrix,class,red,blue
+//! power,red,10,-5
+//! power,blue,0,20
+//! radius,red,80,80
+//! radius,blue,120,60
+//! ```
+//!
+//! Matrices left out of a file are not changed when it's imported, so a file
+//! with only `power` rows is fine.
+//!
+//! ```
+//! use egui::Rgba;
+//! use smarticles::{matrix, Simulation};
+//!
+//! let classes = [(); 8].map(|_| ("class", Rgba::WHITE));
+//! let mut sim = Simulation::new(800.0, 800.0, classes);
+//! for (i, p) in sim.params.iter_mut().enumerate() {
+//!     for j in 0..8 {
+//!         p.power[j] = (i * 8 + j) as f32 - 31.5;
+//!         p.radius[j] = (i * 8 + j) as f32 * 7.25;
+//!         p.spin[j] = j as f32 - i as f32;
+//!     }
+//! }
+//!
+//! let csv = matrix::to_csv(&sim);
+//! let mut copy = Simulation::new(800.0, 800.0, classes);
+//! matrix::apply_csv(&mut copy, &csv).unwrap();
+//! for (a, b) in sim.params.iter().zip(&copy.params) {
+//!     assert_eq!((a.power, a.radius, a.spin), (b.power, b.radius, b.spin));
+//! }
+//!
+//! // a matrix for 4 types doesn't fit 8
+//! let small = Simulation::new(800.0, 800.0, [(); 4].map(|_| ("class", Rgba::WHITE)));
+//! assert!(matrix::apply_csv(&mut copy, &matrix::to_csv(&small)).is_err());
+//! ```
+
+use crate::Simulation;
+use crate::{MAX_POWER, MAX_RADIUS, MAX_SPIN, MIN_POWER, MIN_RADIUS, MIN_SPIN};
+
+const MATRICES: [(&str, f32, f32); 3] = [
+    ("power", MIN_POWER, MAX_POWER),
+    ("radius", MIN_RADIUS, MAX_RADIUS),
+    ("spin", MIN_SPIN, MAX_SPIN),
+];
+
+/// Writes the power, radius and spin matrices of a simulation.
+pub fn to_csv<const N: usize>(sim: &Simulation<N>) -> String {
+    let mut csv = String::from("matrix,class");
+    for p in &sim.params {
+        csv += &format!(",{}", quote(&p.name));
+    }
+    csv += "\n";
+    for (matrix, _, _) in MATRICES {
+        for p in &sim.params {
+            let row = match matrix {
+                "power" => &p.power,
+                "radius" => &p.radius,
+                _ => &p.spin,
+            };
+            csv += &format!("{},{}", matrix, quote(&p.name));
+            for value in row {
+                csv += &format!(",{}", value);
+            }
+            csv += "\n";
+        }
+    }
+    csv
+}
+
+/// Reads matrices written by [`to_csv`] into a simulation, changing nothing if any are invalid.
+pub fn apply_csv<const N: usize>(sim: &mut Simulation<N>, text: &str) -> Result<(), String> {
+    // rows read so far for each matrix
+    let mut rows: [Vec<[f32; N]>; 3] = Default::default();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header) = lines.next().ok_or("The file is empty.")?;
+    let columns = split(header).len();
+    if columns != N + 2 {
+        return Err(format!(
+            "The header has {} types, but the simulation has {}.",
+            columns.saturating_sub(2),
+            N
+        ));
+    }
+
+    for (i, line) in lines {
+        let line_num = i + 1;
+        let fields = split(line);
+        let matrix = fields[0].trim();
+        let Some(m) = MATRICES.iter().position(|&(name, _, _)| name == matrix) else {
+            return Err(format!(
+                "Line {}: `{}` isn't power, radius or spin.",
+                line_num, matrix
+            ));
+        };
+        let (_, min, max) = MATRICES[m];
+        if fields.len() != N + 2 {
+            return Err(format!(
+                "Line {}: expected {} values, found {}.",
+                line_num,
+                N,
+                fields.len().saturating_sub(2)
+            ));
+        }
+        if rows[m].len() == N {
+            return Err(format!(
+                "Line {}: there are more than {} {} rows.",
+                line_num, N, matrix
+            ));
+        }
+        let mut row = [0.0; N];
+        for (value, field) in row.iter_mut().zip(&fields[2..]) {
+            let field = field.trim();
+            *value = field
+                .parse()
+                .map_err(|_| format!("Line {}: `{}` isn't a number.", line_num, field))?;
+            if !(min..=max).contains(value) {
+                return Err(format!(
+                    "Line {}: {} {} is outside {}..{}.",
+                    line_num, matrix, value, min, max
+                ));
+            }
+        }
+        rows[m].push(row);
+    }
+
+    for ((matrix, _, _), rows) in MATRICES.iter().zip(&rows) {
+        if !rows.is_empty() && rows.len() != N {
+            return Err(format!(
+                "There are {} {} rows, but the simulation has {} types.",
+                rows.len(),
+                matrix,
+                N
+            ));
+        }
+    }
+    if rows.iter().all(Vec::is_empty) {
+        return Err("The file has no power, radius or spin rows.".to_string());
+    }
+
+    let [power, radius, spin] = rows;
+    for (i, p) in sim.params.iter_mut().enumerate() {
+        if let Some(row) = power.get(i) {
+            p.power = *row;
+        }
+        if let Some(row) = radius.get(i) {
+            p.radius = *row;
+        }
+        if let Some(row) = spin.get(i) {
+            p.spin = *row;
+        }
+    }
+    Ok(())
+}
+
+// names can be edited, so quote them in case they contain commas or quotes
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Splits a CSV line into fields, unquoting the quoted ones.
+fn split(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
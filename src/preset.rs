@@ -0,0 +1,85 @@
+//! Named setups stored as text, which is how the app keeps the user's presets and how the
+//! gallery of presets that comes with it is bundled.
+//!
+//! Presets are stored one per line as tab-separated `key=value` fields, so unknown keys from
+//! newer versions are simply skipped, and so are lines without a name and a seed:
+//!
+//! ```text
+//! name=Tiny\tdescription=Two types in a small world.\tseed=@//8QAA...\tclasses=red,blue
+//! ```
+//!
+//! Every preset in the gallery imports cleanly and keeps stepping:
+//!
+//! ```
+//! use egui::Rgba;
+//! use rand::rngs::SmallRng;
+//! use rand::SeedableRng;
+//! use smarticles::{preset, Simulation};
+//!
+//! let gallery = preset::gallery();
+//! assert!(gallery.len() >= 5);
+//! for preset in gallery {
+//!     let classes = [(); 4].map(|_| ("class", Rgba::WHITE));
+//!     let mut sim = Simulation::new(800.0, 800.0, classes);
+//!     let bytes = base64::decode(preset.seed.strip_prefix('@').unwrap()).unwrap();
+//!     sim.import(&bytes).unwrap();
+//!     assert!(!preset.description.is_empty());
+//!     assert_eq!(preset.classes.len(), 4);
+//!
+//!     sim.spawn_with(&mut SmallRng::seed_from_u64(sim.spawn_seed()));
+//!     for _ in 0..10 {
+//!         sim.step();
+//!     }
+//!     let mut dots = sim.dots.iter().flatten();
+//!     assert!(dots.clone().count() > 0);
+//!     assert!(dots.all(|d| d.pos.x.is_finite() && d.pos.y.is_finite()));
+//! }
+//! ```
+
+const GALLERY: &str = include_str!("gallery.txt");
+
+/// A saved setup that can be recalled by name.
+#[derive(Clone)]
+pub struct Preset {
+    pub name: String,
+    /// What to expect from it, in a sentence. Only the gallery's presets have one.
+    pub description: String,
+    pub seed: String,
+    /// The names of the types, which older seed codes didn't hold.
+    pub classes: Vec<String>,
+}
+
+impl Preset {
+    pub fn parse(line: &str) -> Option<Self> {
+        let (mut name, mut description, mut seed, mut classes) =
+            (None, String::new(), None, Vec::new());
+        for field in line.split('\t') {
+            match field.split_once('=') {
+                Some(("name", value)) => name = Some(value.to_string()),
+                Some(("description", value)) => description = value.to_string(),
+                Some(("seed", value)) => seed = Some(value.to_string()),
+                Some(("classes", value)) => classes = value.split(',').map(String::from).collect(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            name: name.filter(|name| !name.is_empty())?,
+            description,
+            seed: seed?,
+            classes,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let mut line = format!("name={}", self.name);
+        if !self.description.is_empty() {
+            line += &format!("\tdescription={}", self.description);
+        }
+        line + &format!("\tseed={}\tclasses={}", self.seed, self.classes.join(","))
+    }
+}
+
+/// The presets that come with the app, for showing what good settings look like.
+pub fn gallery() -> Vec<Preset> {
+    GALLERY.lines().filter_map(Preset::parse).collect()
+}
@@ -0,0 +1,434 @@
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use egui::{Color32, Vec2};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use smarticles::Dot;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const MAGIC: &[u8; 4] = b"SMRC";
+pub const VERSION: u8 = 1;
+
+/// How many encoded frames can wait for the writer before new ones are dropped.
+const QUEUE_LEN: usize = 120;
+/// Rendered frames are much bigger, so fewer of them are allowed to pile up.
+const FRAME_QUEUE_LEN: usize = 16;
+/// Trades a little color accuracy for GIFs that encode about ten times faster than the
+/// best quality, on a scale of 1 to 30.
+const GIF_SPEED: i32 = 10;
+
+// Recordings are laid out as:
+//
+//   magic, version: u8, frame_count: u32, index_offset: u64,
+//   world_w: f32, world_h: f32, classes: u8,
+//   per class: r, g, b: u8, name_len: u8, name bytes
+//   frames...
+//   index: frame_count * u64 frame offsets
+//
+// and every frame is, per class, a u32 count followed by that many u16 x/y
+// pairs, scaled so 0..=u16::MAX spans the world. The count and index offset are
+// patched in when the recording is finished, and the index makes seeking cheap
+// even though frames differ in size.
+
+/// Streams particle positions to a file on a background thread.
+pub struct Recorder {
+    sender: SyncSender<Vec<u8>>,
+    writer: JoinHandle<io::Result<u32>>,
+    world: (f32, f32),
+    started: Instant,
+    bytes: u64,
+    dropped: u32,
+}
+
+impl Recorder {
+    pub fn start(
+        path: &Path,
+        world_w: f32,
+        world_h: f32,
+        classes: &[(&str, Color32)],
+    ) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_u8(VERSION)?;
+        file.write_u32::<LE>(0)?;
+        file.write_u64::<LE>(0)?;
+        file.write_f32::<LE>(world_w)?;
+        file.write_f32::<LE>(world_h)?;
+        file.write_u8(classes.len() as u8)?;
+        for (name, color) in classes {
+            file.write_all(&color.to_array()[..3])?;
+            let name = &name.as_bytes()[..name.len().min(u8::MAX as usize)];
+            file.write_u8(name.len() as u8)?;
+            file.write_all(name)?;
+        }
+
+        let (sender, receiver) = sync_channel::<Vec<u8>>(QUEUE_LEN);
+        let writer = std::thread::spawn(move || {
+            let mut offsets = Vec::new();
+            let mut offset = file.stream_position()?;
+            for frame in receiver {
+                offsets.push(offset);
+                file.write_all(&frame)?;
+                offset += frame.len() as u64;
+            }
+
+            // the sender was dropped, so finish off the file
+            for &offset in &offsets {
+                file.write_u64::<LE>(offset)?;
+            }
+            file.seek(SeekFrom::Start(MAGIC.len() as u64 + 1))?;
+            file.write_u32::<LE>(offsets.len() as u32)?;
+            file.write_u64::<LE>(offset)?;
+            file.flush()?;
+            Ok(offsets.len() as u32)
+        });
+
+        Ok(Self {
+            sender,
+            writer,
+            world: (world_w, world_h),
+            started: Instant::now(),
+            bytes: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Queues a frame, dropping it instead of waiting if the writer has fallen behind.
+    pub fn push(&mut self, dots: &[Vec<Dot>]) {
+        let (world_w, world_h) = self.world;
+        let quantize = |v: f32, size: f32| ((v / size).clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+
+        let len = dots.iter().map(|dots| 4 + dots.len() * 4).sum();
+        let mut frame = Vec::with_capacity(len);
+        for dots in dots {
+            frame.write_u32::<LE>(dots.len() as u32).unwrap();
+            for dot in dots {
+                frame.write_u16::<LE>(quantize(dot.pos.x, world_w)).unwrap();
+                frame.write_u16::<LE>(quantize(dot.pos.y, world_h)).unwrap();
+            }
+        }
+
+        match self.sender.try_send(frame) {
+            Ok(()) => self.bytes += len as u64,
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            // the writer hit an error, which `finish` will report
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Bytes of frame data queued so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Waits for the queued frames to be written and returns how many there were.
+    pub fn finish(self) -> io::Result<u32> {
+        drop(self.sender);
+        self.writer
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("writer panicked")))
+    }
+}
+
+/// A finished recording, read one frame at a time.
+pub struct Recording {
+    file: BufReader<File>,
+    world_w: f32,
+    world_h: f32,
+    classes: Vec<(String, Color32)>,
+    index: Vec<u64>,
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a smarticles recording"));
+        }
+        if file.read_u8()? > VERSION {
+            return Err(invalid("recording is from a newer version"));
+        }
+        let frame_count = file.read_u32::<LE>()? as usize;
+        let index_offset = file.read_u64::<LE>()?;
+        if frame_count == 0 {
+            return Err(invalid("recording is empty or was never finished"));
+        }
+        let world_w = file.read_f32::<LE>()?;
+        let world_h = file.read_f32::<LE>()?;
+        let mut classes = Vec::new();
+        for _ in 0..file.read_u8()? {
+            let mut rgb = [0; 3];
+            file.read_exact(&mut rgb)?;
+            let mut name = vec![0; file.read_u8()? as usize];
+            file.read_exact(&mut name)?;
+            let color = Color32::from_rgb(rgb[0], rgb[1], rgb[2]);
+            classes.push((String::from_utf8_lossy(&name).into_owned(), color));
+        }
+
+        file.seek(SeekFrom::Start(index_offset))?;
+        let index = (0..frame_count)
+            .map(|_| file.read_u64::<LE>())
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            file,
+            world_w,
+            world_h,
+            classes,
+            index,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn classes(&self) -> &[(String, Color32)] {
+        &self.classes
+    }
+
+    /// Reads the positions of every particle in a frame, grouped by type.
+    pub fn frame(&mut self, frame: usize) -> io::Result<Vec<Vec<Vec2>>> {
+        let scale = Vec2::new(self.world_w, self.world_h) / u16::MAX as f32;
+
+        self.file.seek(SeekFrom::Start(self.index[frame]))?;
+        let mut positions = Vec::with_capacity(self.classes.len());
+        for _ in &self.classes {
+            let count = self.file.read_u32::<LE>()? as usize;
+            let mut dots = Vec::with_capacity(count);
+            for _ in 0..count {
+                let x = self.file.read_u16::<LE>()? as f32;
+                let y = self.file.read_u16::<LE>()? as f32;
+                dots.push(Vec2::new(x, y) * scale);
+            }
+            positions.push(dots);
+        }
+        Ok(positions)
+    }
+}
+
+/// Plays back a recording in place of the live simulation.
+pub struct Player {
+    pub recording: Recording,
+    pub positions: Vec<Vec<Vec2>>,
+    pub frame: usize,
+    pub playing: bool,
+    pub speed: f32,
+    progress: f32,
+}
+
+impl Player {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut recording = Recording::open(path)?;
+        let positions = recording.frame(0)?;
+        Ok(Self {
+            recording,
+            positions,
+            frame: 0,
+            playing: false,
+            speed: 1.0,
+            progress: 0.0,
+        })
+    }
+
+    pub fn seek(&mut self, frame: usize) -> io::Result<()> {
+        let frame = frame.min(self.recording.len() - 1);
+        self.positions = self.recording.frame(frame)?;
+        self.frame = frame;
+        Ok(())
+    }
+
+    /// Moves playback along by `frames` recorded frames, stopping at the end.
+    pub fn advance(&mut self, frames: f32) -> io::Result<()> {
+        self.progress += frames * self.speed;
+        let skip = self.progress as usize;
+        self.progress -= skip as f32;
+        if skip > 0 {
+            self.seek(self.frame + skip)?;
+        }
+        if self.frame + 1 == self.recording.len() {
+            self.playing = false;
+        }
+        Ok(())
+    }
+}
+
+/// Saves numbered PNG frames on the rayon pool, for turning into a video later.
+pub struct FrameWriter {
+    sender: SyncSender<(PathBuf, RgbaImage)>,
+    writer: JoinHandle<Result<(), String>>,
+    dir: PathBuf,
+    frames: u32,
+    dropped: u32,
+}
+
+impl FrameWriter {
+    pub fn start(dir: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let (sender, receiver) = sync_channel::<(PathBuf, RgbaImage)>(FRAME_QUEUE_LEN);
+        let writer = std::thread::spawn(move || {
+            receiver
+                .into_iter()
+                .par_bridge()
+                .try_for_each(|(path, img)| img.save(&path).map_err(|err| err.to_string()))
+        });
+        Ok(Self {
+            sender,
+            writer,
+            dir: dir.to_path_buf(),
+            frames: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Queues a frame, dropping it instead of waiting if encoding has fallen behind.
+    pub fn push(&mut self, img: RgbaImage) {
+        let path = self.dir.join(format!("frame_{:06}.png", self.frames + 1));
+        match self.sender.try_send((path, img)) {
+            Ok(()) => self.frames += 1,
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            // an encode failed, which `finish` will report
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Waits for the queued frames to be saved and returns how many there were.
+    pub fn finish(self) -> Result<u32, String> {
+        drop(self.sender);
+        self.writer
+            .join()
+            .unwrap_or_else(|_| Err("frame writer panicked".to_string()))?;
+        Ok(self.frames)
+    }
+}
+
+/// Keeps the last few seconds of rendered frames, so something that already happened can
+/// still be saved.
+pub struct ReplayBuffer {
+    frames: VecDeque<RgbaImage>,
+    len: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(len: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(len),
+            len,
+        }
+    }
+
+    /// Adds a frame, dropping the oldest once the buffer is full.
+    pub fn push(&mut self, img: RgbaImage) {
+        // a GIF can't change size partway through, so start over when the framing does
+        if (self.frames.back()).is_some_and(|last| last.dimensions() != img.dimensions()) {
+            self.frames.clear();
+        }
+        while self.frames.len() >= self.len {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(img);
+    }
+
+    /// Changes how many frames are kept, dropping the oldest if there are too many.
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.max(1);
+        while self.frames.len() > self.len {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// How much memory the frames take up.
+    pub fn bytes(&self) -> usize {
+        self.frames.iter().map(|img| img.as_raw().len()).sum()
+    }
+
+    /// Starts saving the frames as a looping GIF on a background thread, emptying the
+    /// buffer so they don't have to be copied.
+    pub fn save_gif(&mut self, path: &Path, fps: u32) -> io::Result<GifWriter> {
+        let file = BufWriter::new(File::create(path)?);
+        let frames = std::mem::take(&mut self.frames);
+        let total = frames.len();
+        let done = Arc::new(AtomicUsize::new(0));
+        let writer = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|err| err.to_string())?;
+                let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+                for img in frames {
+                    let frame = Frame::from_parts(img, 0, 0, delay);
+                    encoder.encode_frame(frame).map_err(|err| err.to_string())?;
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(())
+            })
+        };
+        Ok(GifWriter {
+            writer,
+            done,
+            total,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// A GIF being encoded by [`ReplayBuffer::save_gif`].
+pub struct GifWriter {
+    writer: JoinHandle<Result<(), String>>,
+    done: Arc<AtomicUsize>,
+    total: usize,
+    path: PathBuf,
+}
+
+impl GifWriter {
+    /// How far along encoding is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.writer.is_finished()
+    }
+
+    /// Waits for encoding to finish and returns where the GIF was saved.
+    pub fn finish(self) -> Result<PathBuf, String> {
+        self.writer
+            .join()
+            .unwrap_or_else(|_| Err("GIF writer panicked".to_string()))?;
+        Ok(self.path)
+    }
+}
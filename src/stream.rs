@@ -0,0 +1,135 @@
+use egui::Vec2;
+use instant::{Duration, Instant};
+use smarticles::Dot;
+use std::io;
+use std::net::UdpSocket;
+
+/// The most particles sent in one packet, which keeps packets under a typical 1500 byte MTU.
+pub const MAX_PACKET_DOTS: usize = 160;
+
+// Every packet is a single OSC message:
+//
+//   address: /class/N/positions
+//   arguments: frame: i32, first: i32, total: i32, positions: blob
+//
+// where `total` is how many particles of class N are sent this frame after the
+// stride is applied, `first` is the index of the first one in this packet, and
+// the blob holds their x/y pairs as big-endian f32s, scaled so 0..1 spans the
+// world. Classes with more than MAX_PACKET_DOTS particles are split over
+// several packets. A class without particles still gets a packet with an empty
+// blob, so receivers can tell it's empty.
+
+/// Sends particle positions to another program as OSC over UDP.
+pub struct Streamer {
+    socket: UdpSocket,
+    target: String,
+    last_sent: Option<Instant>,
+    frame: i32,
+    packets: u64,
+    error: Option<String>,
+}
+
+impl Streamer {
+    pub fn start(target: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+        // a slow network should drop packets, not stall the simulation
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            target: target.to_string(),
+            last_sent: None,
+            frame: 0,
+            packets: 0,
+            error: None,
+        })
+    }
+
+    /// Sends every `stride`th particle, unless a frame was sent less than `1 / rate` seconds ago.
+    pub fn push(&mut self, dots: &[Vec<Dot>], world: Vec2, rate: f32, stride: usize) {
+        let now = Instant::now();
+        if let Some(last) = self.last_sent {
+            if now - last < Duration::from_secs_f32(1.0 / rate) {
+                return;
+            }
+        }
+        self.last_sent = Some(now);
+
+        for (class, dots) in dots.iter().enumerate() {
+            let address = format!("/class/{}/positions", class);
+            let dots: Vec<_> = dots.iter().step_by(stride.max(1)).collect();
+            let mut first = 0;
+            loop {
+                let chunk = &dots[first..(first + MAX_PACKET_DOTS).min(dots.len())];
+                let mut blob = Vec::with_capacity(chunk.len() * 8);
+                for dot in chunk {
+                    blob.extend_from_slice(&(dot.pos.x / world.x).to_be_bytes());
+                    blob.extend_from_slice(&(dot.pos.y / world.y).to_be_bytes());
+                }
+                let packet = osc_message(
+                    &address,
+                    &[self.frame, first as i32, dots.len() as i32],
+                    &blob,
+                );
+                self.send(&packet);
+                first += chunk.len();
+                if first >= dots.len() {
+                    break;
+                }
+            }
+        }
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    fn send(&mut self, packet: &[u8]) {
+        // errors are only reported, since the receiver may just not be running yet
+        match self.socket.send(packet) {
+            Ok(_) => {
+                self.packets += 1;
+                self.error = None;
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// The last send error, cleared again once a packet gets through.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Encodes an OSC message with some int arguments followed by a blob.
+fn osc_message(address: &str, ints: &[i32], blob: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64 + blob.len());
+    osc_string(&mut packet, address);
+    osc_string(&mut packet, &format!(",{}b", "i".repeat(ints.len())));
+    for int in ints {
+        packet.extend_from_slice(&int.to_be_bytes());
+    }
+    packet.extend_from_slice(&(blob.len() as i32).to_be_bytes());
+    packet.extend_from_slice(blob);
+    osc_pad(&mut packet);
+    packet
+}
+
+// strings are null terminated, then padded to a multiple of 4 bytes
+fn osc_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    packet.push(0);
+    osc_pad(packet);
+}
+
+fn osc_pad(packet: &mut Vec<u8>) {
+    while !packet.len().is_multiple_of(4) {
+        packet.push(0);
+    }
+}